@@ -0,0 +1,69 @@
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+extern crate panic_itm;
+
+use cortex_m;
+use cortex_m_rt::entry;
+use stm32h7xx_hal::gpio::{ErasedPin, Output, PushPull};
+use stm32h7xx_hal::hal::digital::v2::OutputPin;
+use stm32h7xx_hal::{pac, prelude::*};
+
+use cortex_m_log::println;
+use cortex_m_log::{
+    destination::Itm, printer::itm::InterruptSync as InterruptSyncItm,
+};
+
+#[entry]
+fn main() -> ! {
+    let cp = cortex_m::Peripherals::take().unwrap();
+    let dp = pac::Peripherals::take().unwrap();
+    let mut log = InterruptSyncItm::new(Itm::new(cp.ITM));
+
+    // Constrain and Freeze power
+    println!(log, "Setup PWR...                  ");
+    let pwr = dp.PWR.constrain();
+    let vos = pwr.freeze();
+
+    // Constrain and Freeze clock
+    println!(log, "Setup RCC...                  ");
+    let rcc = dp.RCC.constrain();
+    let mut ccdr = rcc.sys_ck(100.mhz()).freeze(vos, &dp.SYSCFG);
+
+    println!(log, "");
+    println!(log, "stm32h7xx-hal example - Erased pins");
+    println!(log, "");
+
+    let gpioa = dp.GPIOA.split(&mut ccdr.ahb4);
+    let gpiob = dp.GPIOB.split(&mut ccdr.ahb4);
+    let gpiog = dp.GPIOG.split(&mut ccdr.ahb4);
+
+    // Chip selects spread over three ports, stored in one array
+    let mut cs: [ErasedPin<Output<PushPull>>; 8] = [
+        gpioa.pa3.into_push_pull_output().erase(),
+        gpioa.pa4.into_push_pull_output().erase(),
+        gpiob.pb0.into_push_pull_output().erase(),
+        gpiob.pb1.into_push_pull_output().erase(),
+        gpiob.pb2.into_push_pull_output().erase(),
+        gpiog.pg9.into_push_pull_output().erase(),
+        gpiog.pg10.into_push_pull_output().erase(),
+        gpiog.pg12.into_push_pull_output().erase(),
+    ];
+    for pin in cs.iter_mut() {
+        pin.set_high().unwrap();
+    }
+
+    // Get the delay provider.
+    let mut delay = cp.SYST.delay(ccdr.clocks);
+
+    loop {
+        for pin in cs.iter_mut() {
+            pin.set_low().unwrap();
+            delay.delay_ms(100_u16);
+
+            pin.set_high().unwrap();
+        }
+    }
+}
@@ -29,9 +29,12 @@ fn main() -> ! {
     let mut ccdr = rcc.sys_ck(100.mhz()).freeze(vos, &dp.SYSCFG);
     let gpiob = dp.GPIOB.split(&mut ccdr.ahb4);
 
-    // Configure the SCL and the SDA pin for our I2C bus
-    let scl = gpiob.pb8.into_alternate_af4().set_open_drain();
-    let sda = gpiob.pb9.into_alternate_af4().set_open_drain();
+    // Configure the SCL and the SDA pin for our I2C bus, with the
+    // internal pull-ups enabled
+    let mut scl = gpiob.pb8.into_alternate_af4_open_drain();
+    let mut sda = gpiob.pb9.into_alternate_af4_open_drain();
+    scl.internal_pull_up(true);
+    sda.internal_pull_up(true);
 
     let mut i2c = dp.I2C1.i2c((scl, sda), 100.khz(), &ccdr);
 
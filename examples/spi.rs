@@ -48,15 +48,19 @@ fn main() -> ! {
     println!(log, "");
 
     // Initialise the SPI peripheral.
-    let mut spi =
-        dp.SPI3
-            .spi((sck, miso, mosi), spi::MODE_0, 3.mhz(), &mut ccdr);
+    let mut spi = dp.SPI3.spi(
+        (sck, miso, mosi),
+        spi::MODE_0,
+        3.mhz(),
+        ccdr.peripheral.SPI3,
+        &ccdr.clocks,
+    );
 
     // Write fixed data
-    spi.write(&[0x11, 0x22, 0x33]).unwrap();
+    spi.write(&[0x11u8, 0x22, 0x33]).unwrap();
 
     // Echo what is received on the SPI
-    let mut received = 0;
+    let mut received: u8 = 0;
     loop {
         block!(spi.send(received)).ok();
         received = block!(spi.read()).unwrap();
@@ -0,0 +1,91 @@
+//! Stress test for `Config::master_rx_auto_suspend`: read 1M words at
+//! 50 MHz into a consumer that is slower than the link.
+//!
+//! With a 100 MHz core clock a frame arrives every 16 core cycles,
+//! which the word-by-word read loop in `read_frames` cannot keep up
+//! with. The master clock is suspended whenever the receive FIFO
+//! fills, so no overrun occurs.
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+extern crate panic_itm;
+
+use cortex_m;
+use cortex_m_rt::entry;
+use stm32h7xx_hal::{pac, prelude::*, spi};
+
+use cortex_m_log::println;
+use cortex_m_log::{
+    destination::Itm, printer::itm::InterruptSync as InterruptSyncItm,
+};
+
+const WORDS: usize = 1_000_000;
+const CHUNK: usize = 50_000;
+
+#[entry]
+fn main() -> ! {
+    let cp = cortex_m::Peripherals::take().unwrap();
+    let dp = pac::Peripherals::take().unwrap();
+    let mut log = InterruptSyncItm::new(Itm::new(cp.ITM));
+
+    // Constrain and Freeze power
+    println!(log, "Setup PWR...                  ");
+    let pwr = dp.PWR.constrain();
+    let vos = pwr.freeze();
+
+    // Constrain and Freeze clock. The SPI kernel clock is pll1_q_ck
+    println!(log, "Setup RCC...                  ");
+    let rcc = dp.RCC.constrain();
+    let mut ccdr = rcc
+        .sys_ck(100.mhz())
+        .pll1_q_ck(100.mhz())
+        .freeze(vos, &dp.SYSCFG);
+
+    let gpioa = dp.GPIOA.split(&mut ccdr.ahb4);
+
+    let sck = gpioa.pa5.into_alternate_af5();
+    let miso = gpioa.pa6.into_alternate_af5();
+
+    println!(log, "");
+    println!(log, "stm32h7xx-hal example - SPI RX auto-suspend");
+    println!(log, "");
+
+    let config = spi::Config::new(spi::MODE_0)
+        .communication_mode(spi::CommunicationMode::Receiver)
+        .master_rx_auto_suspend();
+
+    let mut spi = dp.SPI1.spi(
+        (sck, miso, spi::NoMosi),
+        config,
+        50.mhz(),
+        ccdr.peripheral.SPI1,
+        &ccdr.clocks,
+    );
+
+    let mut buf = [0u8; CHUNK];
+    let mut checksum: u32 = 0;
+    let mut received = 0;
+
+    while received < WORDS {
+        spi.read_frames(&mut buf).unwrap();
+        received += buf.len();
+
+        for word in buf.iter() {
+            checksum = checksum.rotate_left(5) ^ u32::from(*word);
+        }
+    }
+
+    let overrun = spi.read_status().contains(spi::StatusFlags::OVR);
+    println!(
+        log,
+        "Received {} words, checksum {:08x}", received, checksum
+    );
+    println!(log, "Overrun: {}", overrun);
+    assert!(!overrun);
+
+    loop {
+        cortex_m::asm::nop()
+    }
+}
@@ -2,9 +2,19 @@
 
 use core::marker::PhantomData;
 
+use cortex_m::interrupt;
+
+use embedded_hal::digital::v2::{
+    InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin,
+};
+
 use crate::rcc::AHB4;
 
-use crate::stm32::{EXTI, SYSCFG};
+use crate::pwr::{Wakeup, WakeupPolarity, WakeupPull};
+use crate::stm32::{exti, EXTI, GPIOA, SYSCFG};
+use crate::Never;
+
+pub use embedded_hal::digital::v2::PinState;
 
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
@@ -44,7 +54,11 @@ pub struct OpenDrain;
 /// Analog mode (type state)
 pub struct Analog;
 
+/// Mode set at run time (type state). See `DynamicPin`
+pub struct Dynamic;
+
 /// GPIO Pin speed selection
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Speed {
     Low = 0,
     Medium = 1,
@@ -52,10 +66,22 @@ pub enum Speed {
     VeryHigh = 3,
 }
 
+/// Edge that triggers an external interrupt. See `ExtiPin`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Edge {
-    RISING,
-    FALLING,
-    RISING_FALLING,
+    Rising,
+    Falling,
+    Both,
+}
+
+#[allow(non_upper_case_globals)]
+impl Edge {
+    #[deprecated(note = "use Edge::Rising")]
+    pub const RISING: Edge = Edge::Rising;
+    #[deprecated(note = "use Edge::Falling")]
+    pub const FALLING: Edge = Edge::Falling;
+    #[deprecated(note = "use Edge::Both")]
+    pub const RISING_FALLING: Edge = Edge::Both;
 }
 
 /// Alternate function 0 (type state)
@@ -91,25 +117,681 @@ pub struct AF14;
 /// Alternate function 15 (type state)
 pub struct AF15;
 
+/// Pins that can wake the device from Standby through a WKUP line of
+/// the PWR unit. Implemented for PA0, PA2, PI8, PC13, PI11 and PC1
+pub trait WakeupPin: Sized {
+    /// WKUP line of this pin, 1 - 6
+    const LINE: u8;
+
+    /// Enables this pin as a wakeup source, on the given edge and with
+    /// a pull resistor applied by the PWR unit. In Standby mode, the
+    /// PWR unit overrides the GPIO configuration of the pin
+    fn into_wakeup_source(
+        self,
+        wakeup: &mut Wakeup,
+        polarity: WakeupPolarity,
+        pull: WakeupPull,
+    ) -> Self {
+        wakeup.enable(Self::LINE, polarity, pull);
+        self
+    }
+
+    /// Disables this pin as a wakeup source
+    fn disable_wakeup_source(&mut self, wakeup: &mut Wakeup) {
+        wakeup.disable(Self::LINE);
+    }
+
+    /// Returns true if this pin caused a wakeup
+    fn check_wakeup_flag(&self, wakeup: &Wakeup) -> bool {
+        wakeup.is_flag_set(Self::LINE)
+    }
+
+    /// Clears the wakeup flag of this pin
+    fn clear_wakeup_flag(&mut self, wakeup: &mut Wakeup) {
+        wakeup.clear_flag(Self::LINE);
+    }
+}
+
+macro_rules! wakeup_pins {
+    ($($port:ident::$PXi:ident: $line:literal,)+) => {
+        $(
+            impl<MODE> WakeupPin for $port::$PXi<MODE> {
+                const LINE: u8 = $line;
+            }
+        )+
+    };
+}
+
+wakeup_pins! {
+    gpioa::PA0: 1,
+    gpioa::PA2: 2,
+    gpioi::PI8: 3,
+    gpioc::PC13: 4,
+    gpioi::PI11: 5,
+    gpioc::PC1: 6,
+}
+
+/// Alternate function number `N`. See `into_alternate`
+pub struct AfN<const N: u8>;
+
+/// Type state of an alternate function number
+pub trait AfMode {
+    /// Alternate function (type state)
+    type Mode;
+}
+
+macro_rules! af_mode {
+    ($($N:literal: $AF:ident,)+) => {
+        $(
+            impl AfMode for AfN<$N> {
+                type Mode = $AF;
+            }
+        )+
+    };
+}
+
+af_mode! {
+    0: AF0, 1: AF1, 2: AF2, 3: AF3, 4: AF4, 5: AF5, 6: AF6, 7: AF7,
+    8: AF8, 9: AF9, 10: AF10, 11: AF11, 12: AF12, 13: AF13, 14: AF14,
+    15: AF15,
+}
+
+/// Implemented for a pin if alternate function `N` is connected on that
+/// pin. Required by `into_alternate` and the `into_alternate_afX` methods
+///
+/// The table is generated from the alternate function matrix in the
+/// datasheet of the STM32H742/743/750/753, see `tools/pin_af.py`. There
+/// is no table for the STM32H747 yet, so on that part every alternate
+/// function is accepted.
+pub trait PinAf<const N: u8> {}
+
 /// External Interrupt Pin
+///
+/// Pin `Pxn` drives EXTI line `n`, so only one of PA0, PB0, PC0... can
+/// be an interrupt source at a time.
+///
+/// ```rust
+/// let mut button = gpioc.pc13.into_floating_input();
+/// button.make_interrupt_source(&mut dp.SYSCFG);
+/// button.trigger_on_edge(&mut dp.EXTI, Edge::Rising);
+/// button.enable_interrupt(&mut dp.EXTI);
+///
+/// // In the EXTI15_10 interrupt handler
+/// if button.check_interrupt() {
+///     button.clear_interrupt_pending_bit();
+/// }
+/// ```
 pub trait ExtiPin {
+    /// Route the EXTI line of this pin to this pin's port
+    ///
+    /// # Panics
+    ///
+    /// Panics if the EXTI line is routed to a pin on another port, and
+    /// its interrupt is enabled. Call `disable_interrupt` on the other
+    /// pin first.
     fn make_interrupt_source(&mut self, syscfg: &mut SYSCFG);
-    fn trigger_on_edge(&mut self, exti: &mut EXTI, level: Edge);
+    /// Generate an interrupt on a rising edge, falling edge or both
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge);
+    /// Enable external interrupts from this pin
     fn enable_interrupt(&mut self, exti: &mut EXTI);
+    /// Disable external interrupts from this pin
     fn disable_interrupt(&mut self, exti: &mut EXTI);
+    /// Clear the interrupt pending bit for this pin
     fn clear_interrupt_pending_bit(&mut self);
+    /// Returns true if the interrupt pending bit for this pin is set
+    fn check_interrupt(&self) -> bool;
+}
+
+/// Route EXTI `line` to `port`, where 0 is GPIOA
+fn exti_make_interrupt_source(syscfg: &mut SYSCFG, port: u32, line: u8) {
+    let offset = 4 * (line % 4);
+
+    // The line must not be enabled for another port between the check
+    // and the write of EXTICR
+    interrupt::free(|_| {
+        let exticr = match line {
+            0..=3 => syscfg.exticr1.read().bits(),
+            4..=7 => syscfg.exticr2.read().bits(),
+            8..=11 => syscfg.exticr3.read().bits(),
+            _ => syscfg.exticr4.read().bits(),
+        };
+        let current = (exticr >> offset) & 0xf;
+
+        // NOTE(unsafe) atomic read with no side effects
+        let imr1 = exti_imr1(unsafe { &*EXTI::ptr() });
+        assert!(
+            current == port || imr1 & (1 << line) == 0,
+            "EXTI line {} is in use by another port",
+            line
+        );
+
+        let bits = (exticr & !(0xf << offset)) | (port << offset);
+        // NOTE(unsafe) only the field for this line changes
+        match line {
+            0..=3 => syscfg.exticr1.write(|w| unsafe { w.bits(bits) }),
+            4..=7 => syscfg.exticr2.write(|w| unsafe { w.bits(bits) }),
+            8..=11 => syscfg.exticr3.write(|w| unsafe { w.bits(bits) }),
+            _ => syscfg.exticr4.write(|w| unsafe { w.bits(bits) }),
+        }
+    });
+}
+
+fn exti_trigger_on_edge(exti: &mut EXTI, line: u8, edge: Edge) {
+    let (rising, falling) = match edge {
+        Edge::Rising => (true, false),
+        Edge::Falling => (false, true),
+        Edge::Both => (true, true),
+    };
+    let mask = 1 << line;
+
+    // NOTE(unsafe) lines 0 - 15 are configurable events
+    exti.rtsr1.modify(|r, w| unsafe {
+        w.bits(if rising { r.bits() | mask } else { r.bits() & !mask })
+    });
+    exti.ftsr1.modify(|r, w| unsafe {
+        w.bits(if falling { r.bits() | mask } else { r.bits() & !mask })
+    });
+}
+
+/// Interrupt mask register 1 for this CPU
+fn exti_imr1(exti: &exti::RegisterBlock) -> u32 {
+    #[cfg(feature = "singlecore")]
+    let imr1 = &exti.cpuimr1;
+    #[cfg(all(feature = "dualcore", feature = "cm7"))]
+    let imr1 = &exti.c1imr1;
+    #[cfg(all(feature = "dualcore", feature = "cm4"))]
+    let imr1 = &exti.c2imr1;
+
+    imr1.read().bits()
+}
+
+fn exti_set_interrupt(exti: &mut EXTI, line: u8, enable: bool) {
+    #[cfg(feature = "singlecore")]
+    let imr1 = &exti.cpuimr1;
+    #[cfg(all(feature = "dualcore", feature = "cm7"))]
+    let imr1 = &exti.c1imr1;
+    #[cfg(all(feature = "dualcore", feature = "cm4"))]
+    let imr1 = &exti.c2imr1;
+
+    let mask = 1 << line;
+    imr1.modify(|r, w| unsafe {
+        w.bits(if enable { r.bits() | mask } else { r.bits() & !mask })
+    });
+}
+
+fn exti_clear_pending(line: u8) {
+    // NOTE(unsafe) write 1 to clear, only this line is affected
+    unsafe {
+        #[cfg(feature = "singlecore")]
+        let pr1 = &(*EXTI::ptr()).cpupr1;
+        #[cfg(all(feature = "dualcore", feature = "cm7"))]
+        let pr1 = &(*EXTI::ptr()).c1pr1;
+        #[cfg(all(feature = "dualcore", feature = "cm4"))]
+        let pr1 = &(*EXTI::ptr()).c2pr1;
+
+        pr1.write(|w| w.bits(1 << line));
+    }
+}
+
+fn exti_is_pending(line: u8) -> bool {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe {
+        #[cfg(feature = "singlecore")]
+        let pr1 = &(*EXTI::ptr()).cpupr1;
+        #[cfg(all(feature = "dualcore", feature = "cm7"))]
+        let pr1 = &(*EXTI::ptr()).c1pr1;
+        #[cfg(all(feature = "dualcore", feature = "cm4"))]
+        let pr1 = &(*EXTI::ptr()).c2pr1;
+
+        pr1.read().bits() & (1 << line) != 0
+    }
+}
+
+/// BSRR value that inverts pin `i`, given the current ODR
+const fn toggle_bits(odr: u32, i: u8) -> u32 {
+    if odr & (1 << i) != 0 {
+        1 << (i + 16)
+    } else {
+        1 << i
+    }
+}
+
+/// BSRR value that sets the bits of `value` within `mask`, and resets
+/// the others
+const fn port_bsrr(mask: u16, value: u16) -> u32 {
+    let set = (value & mask) as u32;
+    let reset = (!value & mask) as u32;
+    set | (reset << 16)
+}
+
+/// Fully erased pin
+///
+/// Both the port and the pin number are stored at run time, so pins
+/// from different ports have the same type. Created with `erase`.
+///
+/// ```rust
+/// let mut cs: [ErasedPin<Output<PushPull>>; 2] = [
+///     gpioa.pa4.into_push_pull_output().erase(),
+///     gpiog.pg10.into_push_pull_output().erase(),
+/// ];
+/// ```
+pub struct ErasedPin<MODE> {
+    // 0 for GPIOA, 1 for GPIOB...
+    port: u8,
+    i: u8,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> ErasedPin<MODE> {
+    /// Pin number within its port
+    pub fn get_id(&self) -> u8 {
+        self.i
+    }
+
+    /// Port index, where 0 is GPIOA
+    pub fn get_port(&self) -> u8 {
+        self.port
+    }
+
+    fn block(&self) -> &crate::stm32::gpioa::RegisterBlock {
+        port_block(self.port)
+    }
+}
+
+/// Registers of `port`, where 0 is GPIOA
+pub(crate) fn port_block(port: u8) -> &'static crate::stm32::gpioa::RegisterBlock {
+    // NOTE(unsafe) the GPIO ports are spaced 0x400 bytes apart, and all
+    // share the layout of GPIOA
+    let base = GPIOA::ptr() as *const u8;
+    unsafe {
+        &*(base.add(0x400 * port as usize)
+            as *const crate::stm32::gpioa::RegisterBlock)
+    }
+}
+
+impl<MODE> OutputPin for ErasedPin<Output<MODE>> {
+    type Error = Never;
+
+    fn set_high(&mut self) -> Result<(), Never> {
+        // NOTE(unsafe) atomic write to a stateless register
+        self.block().bsrr.write(|w| unsafe { w.bits(1 << self.i) });
+
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Never> {
+        // NOTE(unsafe) atomic write to a stateless register
+        self.block()
+            .bsrr
+            .write(|w| unsafe { w.bits(1 << (self.i + 16)) });
+
+        Ok(())
+    }
+}
+
+impl<MODE> StatefulOutputPin for ErasedPin<Output<MODE>> {
+    fn is_set_high(&self) -> Result<bool, Never> {
+        self.is_set_low().map(|v| !v)
+    }
+
+    fn is_set_low(&self) -> Result<bool, Never> {
+        Ok(self.block().odr.read().bits() & (1 << self.i) == 0)
+    }
+}
+
+impl<MODE> ToggleableOutputPin for ErasedPin<Output<MODE>> {
+    type Error = Never;
+
+    fn toggle(&mut self) -> Result<(), Never> {
+        let odr = self.block().odr.read().bits();
+        // NOTE(unsafe) atomic write to a stateless register
+        self.block()
+            .bsrr
+            .write(|w| unsafe { w.bits(toggle_bits(odr, self.i)) });
+
+        Ok(())
+    }
+}
+
+impl<MODE> InputPin for ErasedPin<Output<MODE>> {
+    type Error = Never;
+
+    fn is_high(&self) -> Result<bool, Never> {
+        self.is_low().map(|v| !v)
+    }
+
+    fn is_low(&self) -> Result<bool, Never> {
+        Ok(self.block().idr.read().bits() & (1 << self.i) == 0)
+    }
+}
+
+impl<MODE> InputPin for ErasedPin<Input<MODE>> {
+    type Error = Never;
+
+    fn is_high(&self) -> Result<bool, Never> {
+        self.is_low().map(|v| !v)
+    }
+
+    fn is_low(&self) -> Result<bool, Never> {
+        Ok(self.block().idr.read().bits() & (1 << self.i) == 0)
+    }
+}
+
+/// Saves the configuration of a pin, and restores it when dropped. Used
+/// by the `with_*` methods of the pins
+struct ModeGuard {
+    port: u8,
+    i: u8,
+    moder: u32,
+    otyper: u32,
+    pupdr: u32,
+}
+
+impl ModeGuard {
+    fn save(port: u8, i: u8) -> Self {
+        let gpio = port_block(port);
+        let offset = 2 * i;
+
+        ModeGuard {
+            port,
+            i,
+            moder: (gpio.moder.read().bits() >> offset) & 0b11,
+            otyper: (gpio.otyper.read().bits() >> i) & 0b1,
+            pupdr: (gpio.pupdr.read().bits() >> offset) & 0b11,
+        }
+    }
+}
+
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        let gpio = port_block(self.port);
+        let offset = 2 * self.i;
+
+        // NOTE(unsafe) only the fields of this pin change. MODER is
+        // restored last, after the output type and pull resistors
+        unsafe {
+            gpio.pupdr.modify(|r, w| {
+                w.bits((r.bits() & !(0b11 << offset)) | (self.pupdr << offset))
+            });
+            gpio.otyper.modify(|r, w| {
+                w.bits((r.bits() & !(1 << self.i)) | (self.otyper << self.i))
+            });
+            gpio.moder.modify(|r, w| {
+                w.bits((r.bits() & !(0b11 << offset)) | (self.moder << offset))
+            });
+        }
+    }
+}
+
+/// Current mode of a `DynamicPin`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DynamicMode {
+    FloatingInput,
+    PullUpInput,
+    PullDownInput,
+    PushPullOutput,
+    OpenDrainOutput,
+    Analog,
+}
+
+/// A `DynamicPin` was used in a mode that does not support the
+/// operation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PinModeError;
+
+/// Pin with a mode that is changed at run time
+///
+/// Changing mode is a few register writes, without the type state
+/// conversions of the typed pins. Reading the pin is an error in analog
+/// mode, and setting its level is an error unless it is an output.
+///
+/// ```rust
+/// let mut pin = gpioa.pa0.into_dynamic();
+/// pin.make_push_pull_output_in_state(PinState::Low);
+/// pin.make_floating_input();
+/// let charged = pin.is_high()?;
+///
+/// // Back to a typed pin
+/// let pa0 = pin.into_inner().into_analog();
+/// ```
+pub struct DynamicPin<PIN> {
+    pin: PIN,
+    port: u8,
+    i: u8,
+    mode: DynamicMode,
+}
+
+impl<PIN> DynamicPin<PIN> {
+    /// The current mode
+    pub fn mode(&self) -> DynamicMode {
+        self.mode
+    }
+
+    /// Returns the typed pin. Convert it with the `into_*` methods
+    pub fn into_inner(self) -> PIN {
+        self.pin
+    }
+
+    /// Changes to a floating input
+    pub fn make_floating_input(&mut self) {
+        self.set_mode(DynamicMode::FloatingInput, 0b00, false, 0b00);
+    }
+
+    /// Changes to an input with the internal pull up
+    pub fn make_pull_up_input(&mut self) {
+        self.set_mode(DynamicMode::PullUpInput, 0b00, false, 0b01);
+    }
+
+    /// Changes to an input with the internal pull down
+    pub fn make_pull_down_input(&mut self) {
+        self.set_mode(DynamicMode::PullDownInput, 0b00, false, 0b10);
+    }
+
+    /// Changes to a push pull output. The output level is
+    /// the previous content of the output data register
+    pub fn make_push_pull_output(&mut self) {
+        self.set_mode(DynamicMode::PushPullOutput, 0b01, false, 0b00);
+    }
+
+    /// Changes to a push pull output, with the output level set before
+    /// the pin becomes an output
+    pub fn make_push_pull_output_in_state(&mut self, state: PinState) {
+        self.set_state(state);
+        self.make_push_pull_output();
+    }
+
+    /// Changes to an open drain output. The output level is
+    /// the previous content of the output data register
+    pub fn make_open_drain_output(&mut self) {
+        self.set_mode(DynamicMode::OpenDrainOutput, 0b01, true, 0b00);
+    }
+
+    /// Changes to an open drain output, with the output level set before
+    /// the pin becomes an output
+    pub fn make_open_drain_output_in_state(&mut self, state: PinState) {
+        self.set_state(state);
+        self.make_open_drain_output();
+    }
+
+    /// Changes to analog mode
+    pub fn make_analog(&mut self) {
+        self.set_mode(DynamicMode::Analog, 0b11, false, 0b00);
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(
+            self.mode,
+            DynamicMode::PushPullOutput | DynamicMode::OpenDrainOutput
+        )
+    }
+
+    fn set_state(&mut self, state: PinState) {
+        let bits = match state {
+            PinState::High => 1 << self.i,
+            PinState::Low => 1 << (self.i + 16),
+        };
+        // NOTE(unsafe) atomic write to a stateless register
+        port_block(self.port)
+            .bsrr
+            .write(|w| unsafe { w.bits(bits) });
+    }
+
+    fn set_mode(
+        &mut self,
+        mode: DynamicMode,
+        moder: u32,
+        od: bool,
+        pupd: u32,
+    ) {
+        let gpio = port_block(self.port);
+        let offset = 2 * self.i;
+
+        // NOTE(unsafe) only the fields of this pin change
+        unsafe {
+            gpio.pupdr.modify(|r, w| {
+                w.bits((r.bits() & !(0b11 << offset)) | (pupd << offset))
+            });
+            gpio.otyper.modify(|r, w| {
+                w.bits((r.bits() & !(1 << self.i)) | ((od as u32) << self.i))
+            });
+            gpio.moder.modify(|r, w| {
+                w.bits((r.bits() & !(0b11 << offset)) | (moder << offset))
+            });
+        }
+        self.mode = mode;
+    }
+}
+
+impl<PIN> OutputPin for DynamicPin<PIN> {
+    type Error = PinModeError;
+
+    fn set_high(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError);
+        }
+        self.set_state(PinState::High);
+
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError);
+        }
+        self.set_state(PinState::Low);
+
+        Ok(())
+    }
+}
+
+impl<PIN> InputPin for DynamicPin<PIN> {
+    type Error = PinModeError;
+
+    fn is_high(&self) -> Result<bool, PinModeError> {
+        self.is_low().map(|v| !v)
+    }
+
+    fn is_low(&self) -> Result<bool, PinModeError> {
+        if self.mode == DynamicMode::Analog {
+            // The input buffer is disabled in analog mode
+            return Err(PinModeError);
+        }
+
+        Ok(port_block(self.port).idr.read().bits() & (1 << self.i) == 0)
+    }
+}
+
+/// Pin with a locked configuration. See `lock`
+///
+/// The mode, output type, speed, pull-up / pull-down and alternate
+/// function of the pin cannot change until the next reset. The output
+/// level can still be set.
+pub struct LockedPin<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> LockedPin<PIN> {
+    /// Borrow the locked pin
+    pub fn pin(&self) -> &PIN {
+        &self.pin
+    }
+}
+
+impl<PIN: OutputPin> OutputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high()
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()
+    }
+}
+
+impl<PIN: StatefulOutputPin> StatefulOutputPin for LockedPin<PIN> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_high()
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_low()
+    }
+}
+
+impl<PIN: ToggleableOutputPin> ToggleableOutputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self.pin.toggle()
+    }
+}
+
+impl<PIN: InputPin> InputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+/// Implements `LockPins` for tuples of pins
+macro_rules! lock_tuples {
+    () => {};
+    (($T:ident, $t:ident) $(($Tn:ident, $tn:ident))*) => {
+        impl<$T: LockPins, $($Tn: LockPins),*> LockPins for ($T, $($Tn,)*) {
+            type Locked = ($T::Locked, $($Tn::Locked,)*);
+
+            fn mask(&self) -> u16 {
+                let ($t, $($tn,)*) = self;
+                $t.mask() $(| $tn.mask())*
+            }
+
+            fn into_locked(self) -> Self::Locked {
+                let ($t, $($tn,)*) = self;
+                ($t.into_locked(), $($tn.into_locked(),)*)
+            }
+        }
+
+        lock_tuples!($(($Tn, $tn))*);
+    };
 }
 
 macro_rules! gpio {
     ($GPIOX:ident, $gpiox:ident, $iopxenr:ident, $iopxrst:ident, $PXx:ident, $extigpionr:expr, [
-        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty, $exticri:ident),)+
+        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty),)+
     ]) => {
         /// GPIO
         pub mod $gpiox {
             use core::marker::PhantomData;
 
             use embedded_hal::digital::v2::{InputPin, OutputPin,
-                                            StatefulOutputPin, toggleable};
+                                            StatefulOutputPin,
+                                            ToggleableOutputPin};
 
             use crate::rcc::AHB4;
             use crate::stm32::$GPIOX;
@@ -118,7 +800,9 @@ macro_rules! gpio {
                 Alternate, Floating, GpioExt, Input, OpenDrain,
                 Output, Speed, PullDown, PullUp, PushPull, AF0, AF1,
                 AF2, AF3, AF4, AF5, AF6, AF7, AF8, AF9, AF10, AF11,
-                AF12, AF13, AF14, AF15, Analog, Edge, ExtiPin, };
+                AF12, AF13, AF14, AF15, Analog, Edge, ErasedPin, ExtiPin,
+                port_bsrr, toggle_bits, Dynamic, DynamicMode, DynamicPin,
+                LockedPin, ModeGuard, PinState, AfMode, AfN, PinAf, };
 
             use crate::Never;
 
@@ -156,6 +840,165 @@ macro_rules! gpio {
                 pub fn get_id(&self) -> u8 {
                     self.i
                 }
+
+                /// Erases the port from the type. See `ErasedPin`
+                pub fn erase(self) -> ErasedPin<MODE> {
+                    ErasedPin {
+                        port: $extigpionr,
+                        i: self.i,
+                        _mode: self._mode,
+                    }
+                }
+
+                /// Enables / disables the internal pull up. Disables the
+                /// internal pull down when enabled, but leaves it in place
+                /// when disabled
+                pub fn internal_pull_up(&mut self, on: bool) {
+                    if on {
+                        _set_pull(self.i, 0b01);
+                    } else {
+                        _clear_pull(self.i, 0b01);
+                    }
+                }
+
+                /// Enables / disables the internal pull down. Disables the
+                /// internal pull up when enabled, but leaves it in place
+                /// when disabled
+                pub fn internal_pull_down(&mut self, on: bool) {
+                    if on {
+                        _set_pull(self.i, 0b10);
+                    } else {
+                        _clear_pull(self.i, 0b10);
+                    }
+                }
+            }
+
+            /// Pins of this port that can be locked together. Implemented
+            /// for each pin, and for tuples of up to 8 pins
+            pub trait LockPins {
+                /// The locked pins
+                type Locked;
+
+                /// Bits of these pins in the lock register
+                fn mask(&self) -> u16;
+
+                /// Wraps the pins after they have been locked
+                fn into_locked(self) -> Self::Locked;
+            }
+
+            lock_tuples!((A, a) (B, b) (C, c) (D, d) (E, e) (F, f) (G, g) (H, h));
+
+            /// Locks the configuration of several pins of this port
+            ///
+            /// The lock register can only be written once per port
+            /// until the next reset, so all the pins of a port that
+            /// should be locked must be locked together.
+            ///
+            /// ```rust
+            /// let (enable, sck) = gpioa::lock_all((enable, sck));
+            /// ```
+            ///
+            /// # Panics
+            ///
+            /// Panics if the configuration of this port is already
+            /// locked, unless all of `pins` were included.
+            pub fn lock_all<PINS: LockPins>(pins: PINS) -> PINS::Locked {
+                const LCKK: u32 = 1 << 16;
+                let bits = LCKK | pins.mask() as u32;
+
+                // NOTE(unsafe) the lock key write sequence only affects
+                // `pins`, which are owned here
+                unsafe {
+                    let lckr = &(*$GPIOX::ptr()).lckr;
+                    lckr.write(|w| w.bits(bits));
+                    lckr.write(|w| w.bits(bits & !LCKK));
+                    lckr.write(|w| w.bits(bits));
+                    let _ = lckr.read();
+
+                    assert!(
+                        lckr.read().bits() & bits == bits,
+                        "{} configuration is already locked",
+                        stringify!($GPIOX)
+                    );
+                }
+
+                pins.into_locked()
+            }
+
+            impl<MODE> LockPins for $PXx<MODE> {
+                type Locked = LockedPin<Self>;
+
+                fn mask(&self) -> u16 {
+                    1 << self.i
+                }
+
+                fn into_locked(self) -> Self::Locked {
+                    LockedPin { pin: self }
+                }
+            }
+
+            impl<MODE> $PXx<MODE> {
+                /// Locks the configuration of this pin. See `lock_all`
+                pub fn lock(self) -> LockedPin<Self> {
+                    lock_all(self)
+                }
+            }
+
+            /// Writes several output pins of this port at once
+            ///
+            /// Consumes the pins, so that they cannot be written
+            /// individually at the same time.
+            ///
+            /// ```rust
+            /// let pins = [
+            ///     gpiod.pd0.into_push_pull_output().downgrade(),
+            ///     gpiod.pd1.into_push_pull_output().downgrade(),
+            /// ];
+            /// let mut port = PortWriter::new(pins);
+            /// port.write_bits(0b11, 0b01);
+            /// ```
+            pub struct PortWriter<MODE, const N: usize> {
+                pins: [$PXx<Output<MODE>>; N],
+                mask: u16,
+            }
+
+            impl<MODE, const N: usize> PortWriter<MODE, N> {
+                /// Takes ownership of `pins`
+                pub fn new(pins: [$PXx<Output<MODE>>; N]) -> Self {
+                    let mask = pins.iter().fold(0, |m, p| m | (1 << p.i));
+
+                    PortWriter { pins, mask }
+                }
+
+                /// Bits of the pins owned by this writer
+                pub fn mask(&self) -> u16 {
+                    self.mask
+                }
+
+                /// Sets the pins in `mask` to the corresponding bits of
+                /// `value`, in a single write. Bits of pins that are not
+                /// owned by this writer are ignored
+                pub fn write_bits(&mut self, mask: u16, value: u16) {
+                    let bsrr = port_bsrr(mask & self.mask, value);
+
+                    // NOTE(unsafe) atomic write to a stateless register,
+                    // only affecting pins owned by this writer
+                    unsafe { (*$GPIOX::ptr()).bsrr.write(|w| w.bits(bsrr)) }
+                }
+
+                /// Reads the input level of the pins in `mask`. Bits of
+                /// pins that are not owned by this writer read as zero
+                pub fn read_bits(&self, mask: u16) -> u16 {
+                    // NOTE(unsafe) atomic read with no side effects
+                    let idr = unsafe { (*$GPIOX::ptr()).idr.read().bits() };
+
+                    (idr as u16) & mask & self.mask
+                }
+
+                /// Releases the pins
+                pub fn release(self) -> [$PXx<Output<MODE>>; N] {
+                    self.pins
+                }
             }
 
             impl<MODE> OutputPin for $PXx<Output<MODE>> {
@@ -191,7 +1034,21 @@ macro_rules! gpio {
                 }
             }
 
-            impl<MODE> toggleable::Default for $PXx<Output<MODE>> {}
+            impl<MODE> ToggleableOutputPin for $PXx<Output<MODE>> {
+                type Error = Never;
+
+                fn toggle(&mut self) -> Result<(), Never> {
+                    // NOTE(unsafe) atomic read, then atomic write to a
+                    // stateless register
+                    unsafe {
+                        let odr = (*$GPIOX::ptr()).odr.read().bits();
+                        (*$GPIOX::ptr()).bsrr
+                            .write(|w| w.bits(toggle_bits(odr, self.i)))
+                    }
+
+                    Ok(())
+                }
+            }
 
             impl<MODE> InputPin for $PXx<Output<MODE>> {
                 type Error = Never;
@@ -222,89 +1079,61 @@ macro_rules! gpio {
             }
 
             impl<MODE> ExtiPin for $PXx<Input<MODE>> {
-                /// Make corresponding EXTI line sensitive to this pin
                 fn make_interrupt_source(&mut self, syscfg: &mut SYSCFG) {
-                    let offset = 4 * (self.i % 4);
-                    match self.i {
-                        0..=3 => {
-                            syscfg.exticr1.modify(|r, w| unsafe {
-                                w.bits((r.bits() & !(0xf << offset)) | ($extigpionr << offset))
-                            });
-                        },
-                        4..=7 => {
-                            syscfg.exticr2.modify(|r, w| unsafe {
-                                w.bits((r.bits() & !(0xf << offset)) | ($extigpionr << offset))
-                            });
-                        },
-                        8..=11 => {
-                            syscfg.exticr3.modify(|r, w| unsafe {
-                                w.bits((r.bits() & !(0xf << offset)) | ($extigpionr << offset))
-                            });
-                        },
-                        12..=15 => {
-                            syscfg.exticr4.modify(|r, w| unsafe {
-                                w.bits((r.bits() & !(0xf << offset)) | ($extigpionr << offset))
-                            });
-                        },
-                        _ => {}
-                    }
+                    super::exti_make_interrupt_source(syscfg, $extigpionr, self.i);
                 }
 
-                /// Generate interrupt on rising edge, falling edge or both
                 fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
-                    match edge {
-                        Edge::RISING => {
-                            exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.i)) });
-                            exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << self.i)) });
-                        },
-                        Edge::FALLING => {
-                            exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.i)) });
-                            exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << self.i)) });
-                        },
-                        Edge::RISING_FALLING => {
-                            exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.i)) });
-                            exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.i)) });
-                        }
-                    }
+                    super::exti_trigger_on_edge(exti, self.i, edge);
                 }
 
-                /// Enable external interrupts from this pin.
                 fn enable_interrupt(&mut self, exti: &mut EXTI) {
-                    #[cfg(any(feature = "singlecore"))]
-                    let imr1 = &exti.cpuimr1;
-                    #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                    let imr1 = &exti.c1imr1;
-                    #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                    let imr1 = &exti.c2imr1;
-
-                    imr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.i)) });
+                    super::exti_set_interrupt(exti, self.i, true);
                 }
 
-                /// Disable external interrupts from this pin
                 fn disable_interrupt(&mut self, exti: &mut EXTI) {
-                    #[cfg(any(feature = "singlecore"))]
-                    let imr1 = &exti.cpuimr1;
-                    #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                    let imr1 = &exti.c1imr1;
-                    #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                    let imr1 = &exti.c2imr1;
-
-                    imr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << self.i)) });
+                    super::exti_set_interrupt(exti, self.i, false);
                 }
 
-                /// Clear the interrupt pending bit for this pin
                 fn clear_interrupt_pending_bit(&mut self) {
-                    unsafe {
-                        #[cfg(any(feature = "singlecore"))]
-                        let pr1 = &(*EXTI::ptr()).cpupr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                        let pr1 = &(*EXTI::ptr()).c1pr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                        let pr1 = &(*EXTI::ptr()).c2pr1;
+                    super::exti_clear_pending(self.i);
+                }
+
+                fn check_interrupt(&self) -> bool {
+                    super::exti_is_pending(self.i)
+                }
+            }
 
+            fn _set_state(index: u8, state: PinState) {
+                let bits = match state {
+                    PinState::High => 1 << index,
+                    PinState::Low => 1 << (index + 16),
+                };
+                // NOTE(unsafe) atomic write to a stateless register
+                unsafe { (*$GPIOX::ptr()).bsrr.write(|w| w.bits(bits)) }
+            }
 
-                        pr1.write(|w| w.bits(1 << self.i) );
-                    }
+            fn _set_pull(index: u8, pupd: u32) {
+                let offset = 2 * index;
+                unsafe {
+                    &(*$GPIOX::ptr()).pupdr.modify(|r, w| {
+                        w.bits((r.bits() & !(0b11 << offset)) | (pupd << offset))
+                    });
+                }
+            }
+
+            /// Clears the pull of pin `index` if it is `pupd`, so that
+            /// disabling one pull leaves the other in place
+            fn _clear_pull(index: u8, pupd: u32) {
+                let offset = 2 * index;
+                unsafe {
+                    (*$GPIOX::ptr()).pupdr.modify(|r, w| {
+                        if (r.bits() >> offset) & 0b11 == pupd {
+                            w.bits(r.bits() & !(0b11 << offset))
+                        } else {
+                            w.bits(r.bits())
+                        }
+                    });
                 }
             }
 
@@ -335,101 +1164,315 @@ macro_rules! gpio {
                     _mode: PhantomData<MODE>,
                 }
 
+                #[cfg(feature = "dualcore")]
+                impl<MODE, const N: u8> PinAf<N> for $PXi<MODE> {}
+
                 impl<MODE> $PXi<MODE> {
-                    /// Configures the pin to operate in AF0 mode
-                    pub fn into_alternate_af0(self) -> $PXi<Alternate<AF0>> {
-                        _set_alternate_mode($i, 0);
+                    /// Configures the pin to operate in alternate
+                    /// function `N`. Fails to compile if alternate
+                    /// function `N` is not connected on this pin, see
+                    /// `PinAf`
+                    ///
+                    /// ```rust
+                    /// let scl = gpiob.pb8.into_alternate::<4>();
+                    /// ```
+                    pub fn into_alternate<const N: u8>(
+                        self,
+                    ) -> $PXi<Alternate<<AfN<N> as AfMode>::Mode>>
+                    where
+                        AfN<N>: AfMode,
+                        Self: PinAf<N>,
+                    {
+                        _set_alternate_mode($i, N as u32);
                         $PXi { _mode: PhantomData }
                     }
 
-                    /// Configures the pin to operate in AF1 mode
-                    pub fn into_alternate_af1(self) -> $PXi<Alternate<AF1>> {
-                        _set_alternate_mode($i, 1);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF0 mode. Fails to
+                    /// compile if AF0 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af0(self) -> $PXi<Alternate<AF0>>
+                    where
+                        Self: PinAf<0>,
+                    {
+                        self.into_alternate::<0>()
                     }
 
-                    /// Configures the pin to operate in AF2 mode
-                    pub fn into_alternate_af2(self) -> $PXi<Alternate<AF2>> {
-                        _set_alternate_mode($i, 2);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF1 mode. Fails to
+                    /// compile if AF1 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af1(self) -> $PXi<Alternate<AF1>>
+                    where
+                        Self: PinAf<1>,
+                    {
+                        self.into_alternate::<1>()
                     }
 
-                    /// Configures the pin to operate in AF3 mode
-                    pub fn into_alternate_af3(self) -> $PXi<Alternate<AF3>> {
-                        _set_alternate_mode($i, 3);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF2 mode. Fails to
+                    /// compile if AF2 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af2(self) -> $PXi<Alternate<AF2>>
+                    where
+                        Self: PinAf<2>,
+                    {
+                        self.into_alternate::<2>()
                     }
 
-                    /// Configures the pin to operate in AF4 mode
-                    pub fn into_alternate_af4(self) -> $PXi<Alternate<AF4>> {
-                        _set_alternate_mode($i, 4);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF3 mode. Fails to
+                    /// compile if AF3 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af3(self) -> $PXi<Alternate<AF3>>
+                    where
+                        Self: PinAf<3>,
+                    {
+                        self.into_alternate::<3>()
                     }
 
-                    /// Configures the pin to operate in AF5 mode
-                    pub fn into_alternate_af5(self) -> $PXi<Alternate<AF5>> {
-                        _set_alternate_mode($i, 5);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF4 mode. Fails to
+                    /// compile if AF4 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af4(self) -> $PXi<Alternate<AF4>>
+                    where
+                        Self: PinAf<4>,
+                    {
+                        self.into_alternate::<4>()
                     }
 
-                    /// Configures the pin to operate in AF6 mode
-                    pub fn into_alternate_af6(self) -> $PXi<Alternate<AF6>> {
-                        _set_alternate_mode($i, 6);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF5 mode. Fails to
+                    /// compile if AF5 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af5(self) -> $PXi<Alternate<AF5>>
+                    where
+                        Self: PinAf<5>,
+                    {
+                        self.into_alternate::<5>()
                     }
 
-                    /// Configures the pin to operate in AF7 mode
-                    pub fn into_alternate_af7(self) -> $PXi<Alternate<AF7>> {
-                        _set_alternate_mode($i, 7);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF6 mode. Fails to
+                    /// compile if AF6 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af6(self) -> $PXi<Alternate<AF6>>
+                    where
+                        Self: PinAf<6>,
+                    {
+                        self.into_alternate::<6>()
                     }
 
-                    /// Configures the pin to operate in AF8 mode
-                    pub fn into_alternate_af8(self) -> $PXi<Alternate<AF8>> {
-                        _set_alternate_mode($i, 8);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF7 mode. Fails to
+                    /// compile if AF7 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af7(self) -> $PXi<Alternate<AF7>>
+                    where
+                        Self: PinAf<7>,
+                    {
+                        self.into_alternate::<7>()
                     }
 
-                    /// Configures the pin to operate in AF9 mode
-                    pub fn into_alternate_af9(self) -> $PXi<Alternate<AF9>> {
-                        _set_alternate_mode($i, 9);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF8 mode. Fails to
+                    /// compile if AF8 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af8(self) -> $PXi<Alternate<AF8>>
+                    where
+                        Self: PinAf<8>,
+                    {
+                        self.into_alternate::<8>()
                     }
 
-                    /// Configures the pin to operate in AF10 mode
-                    pub fn into_alternate_af10(self) -> $PXi<Alternate<AF10>> {
-                        _set_alternate_mode($i, 10);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF9 mode. Fails to
+                    /// compile if AF9 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af9(self) -> $PXi<Alternate<AF9>>
+                    where
+                        Self: PinAf<9>,
+                    {
+                        self.into_alternate::<9>()
                     }
 
-                    /// Configures the pin to operate in AF11 mode
-                    pub fn into_alternate_af11(self) -> $PXi<Alternate<AF11>> {
-                        _set_alternate_mode($i, 11);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF10 mode. Fails to
+                    /// compile if AF10 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af10(self) -> $PXi<Alternate<AF10>>
+                    where
+                        Self: PinAf<10>,
+                    {
+                        self.into_alternate::<10>()
                     }
 
-                    /// Configures the pin to operate in AF12 mode
-                    pub fn into_alternate_af12(self) -> $PXi<Alternate<AF12>> {
-                        _set_alternate_mode($i, 12);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF11 mode. Fails to
+                    /// compile if AF11 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af11(self) -> $PXi<Alternate<AF11>>
+                    where
+                        Self: PinAf<11>,
+                    {
+                        self.into_alternate::<11>()
                     }
 
-                    /// Configures the pin to operate in AF13 mode
-                    pub fn into_alternate_af13(self) -> $PXi<Alternate<AF13>> {
-                        _set_alternate_mode($i, 13);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF12 mode. Fails to
+                    /// compile if AF12 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af12(self) -> $PXi<Alternate<AF12>>
+                    where
+                        Self: PinAf<12>,
+                    {
+                        self.into_alternate::<12>()
                     }
 
-                    /// Configures the pin to operate in AF14 mode
-                    pub fn into_alternate_af14(self) -> $PXi<Alternate<AF14>> {
-                        _set_alternate_mode($i, 14);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF13 mode. Fails to
+                    /// compile if AF13 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af13(self) -> $PXi<Alternate<AF13>>
+                    where
+                        Self: PinAf<13>,
+                    {
+                        self.into_alternate::<13>()
                     }
 
-                    /// Configures the pin to operate in AF15 mode
-                    pub fn into_alternate_af15(self) -> $PXi<Alternate<AF15>> {
-                        _set_alternate_mode($i, 15);
-                        $PXi { _mode: PhantomData }
+                    /// Configures the pin to operate in AF14 mode. Fails to
+                    /// compile if AF14 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af14(self) -> $PXi<Alternate<AF14>>
+                    where
+                        Self: PinAf<14>,
+                    {
+                        self.into_alternate::<14>()
+                    }
+
+                    /// Configures the pin to operate in AF15 mode. Fails to
+                    /// compile if AF15 is not connected on this pin, see
+                    /// `PinAf`
+                    pub fn into_alternate_af15(self) -> $PXi<Alternate<AF15>>
+                    where
+                        Self: PinAf<15>,
+                    {
+                        self.into_alternate::<15>()
+                    }
+
+                    /// Configures the pin to operate in AF0 open drain mode
+                    pub fn into_alternate_af0_open_drain(self) -> $PXi<Alternate<AF0>>
+                    where
+                        Self: PinAf<0>,
+                    {
+                        self.into_alternate_af0().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF1 open drain mode
+                    pub fn into_alternate_af1_open_drain(self) -> $PXi<Alternate<AF1>>
+                    where
+                        Self: PinAf<1>,
+                    {
+                        self.into_alternate_af1().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF2 open drain mode
+                    pub fn into_alternate_af2_open_drain(self) -> $PXi<Alternate<AF2>>
+                    where
+                        Self: PinAf<2>,
+                    {
+                        self.into_alternate_af2().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF3 open drain mode
+                    pub fn into_alternate_af3_open_drain(self) -> $PXi<Alternate<AF3>>
+                    where
+                        Self: PinAf<3>,
+                    {
+                        self.into_alternate_af3().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF4 open drain mode
+                    pub fn into_alternate_af4_open_drain(self) -> $PXi<Alternate<AF4>>
+                    where
+                        Self: PinAf<4>,
+                    {
+                        self.into_alternate_af4().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF5 open drain mode
+                    pub fn into_alternate_af5_open_drain(self) -> $PXi<Alternate<AF5>>
+                    where
+                        Self: PinAf<5>,
+                    {
+                        self.into_alternate_af5().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF6 open drain mode
+                    pub fn into_alternate_af6_open_drain(self) -> $PXi<Alternate<AF6>>
+                    where
+                        Self: PinAf<6>,
+                    {
+                        self.into_alternate_af6().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF7 open drain mode
+                    pub fn into_alternate_af7_open_drain(self) -> $PXi<Alternate<AF7>>
+                    where
+                        Self: PinAf<7>,
+                    {
+                        self.into_alternate_af7().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF8 open drain mode
+                    pub fn into_alternate_af8_open_drain(self) -> $PXi<Alternate<AF8>>
+                    where
+                        Self: PinAf<8>,
+                    {
+                        self.into_alternate_af8().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF9 open drain mode
+                    pub fn into_alternate_af9_open_drain(self) -> $PXi<Alternate<AF9>>
+                    where
+                        Self: PinAf<9>,
+                    {
+                        self.into_alternate_af9().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF10 open drain mode
+                    pub fn into_alternate_af10_open_drain(self) -> $PXi<Alternate<AF10>>
+                    where
+                        Self: PinAf<10>,
+                    {
+                        self.into_alternate_af10().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF11 open drain mode
+                    pub fn into_alternate_af11_open_drain(self) -> $PXi<Alternate<AF11>>
+                    where
+                        Self: PinAf<11>,
+                    {
+                        self.into_alternate_af11().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF12 open drain mode
+                    pub fn into_alternate_af12_open_drain(self) -> $PXi<Alternate<AF12>>
+                    where
+                        Self: PinAf<12>,
+                    {
+                        self.into_alternate_af12().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF13 open drain mode
+                    pub fn into_alternate_af13_open_drain(self) -> $PXi<Alternate<AF13>>
+                    where
+                        Self: PinAf<13>,
+                    {
+                        self.into_alternate_af13().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF14 open drain mode
+                    pub fn into_alternate_af14_open_drain(self) -> $PXi<Alternate<AF14>>
+                    where
+                        Self: PinAf<14>,
+                    {
+                        self.into_alternate_af14().set_open_drain()
+                    }
+
+                    /// Configures the pin to operate in AF15 open drain mode
+                    pub fn into_alternate_af15_open_drain(self) -> $PXi<Alternate<AF15>>
+                    where
+                        Self: PinAf<15>,
+                    {
+                        self.into_alternate_af15().set_open_drain()
                     }
 
                     /// Configures the pin to operate as a floating
@@ -481,7 +1524,9 @@ macro_rules! gpio {
                     }
 
                     /// Configures the pin to operate as an open drain
-                    /// output pin
+                    /// output pin. The initial level is the previous
+                    /// content of the output data register, see
+                    /// `into_open_drain_output_in_state`
                     pub fn into_open_drain_output(self) -> $PXi<Output<OpenDrain>> {
                         let offset = 2 * $i;
                         unsafe {
@@ -499,8 +1544,27 @@ macro_rules! gpio {
                         $PXi { _mode: PhantomData }
                     }
 
+                    /// Configures the pin to operate as an open drain
+                    /// output pin, initially in `state`. The output
+                    /// level is set before the pin becomes an output, so
+                    /// there is no glitch.
+                    ///
+                    /// In the `High` state the pin is released, and its
+                    /// level is set by an external pull-up. The internal
+                    /// pull-up is disabled by this method; enable it
+                    /// afterwards with `internal_pull_up` if required
+                    pub fn into_open_drain_output_in_state(
+                        self,
+                        state: PinState,
+                    ) -> $PXi<Output<OpenDrain>> {
+                        _set_state($i, state);
+                        self.into_open_drain_output()
+                    }
+
                     /// Configures the pin to operate as an push pull
-                    /// output pin
+                    /// output pin. The initial level is the previous
+                    /// content of the output data register, see
+                    /// `into_push_pull_output_in_state`
                     pub fn into_push_pull_output(self) -> $PXi<Output<PushPull>> {
                         let offset = 2 * $i;
 
@@ -519,6 +1583,18 @@ macro_rules! gpio {
                         $PXi { _mode: PhantomData }
                     }
 
+                    /// Configures the pin to operate as an push pull
+                    /// output pin, initially in `state`. The output
+                    /// level is set before the pin becomes an output, so
+                    /// there is no glitch
+                    pub fn into_push_pull_output_in_state(
+                        self,
+                        state: PinState,
+                    ) -> $PXi<Output<PushPull>> {
+                        _set_state($i, state);
+                        self.into_push_pull_output()
+                    }
+
                     /// Configures the pin to operate as an analog
                     /// input pin
                     pub fn into_analog(self) -> $PXi<Analog> {
@@ -552,19 +1628,6 @@ macro_rules! gpio {
                     }
                 }
 
-                impl $PXi<Output<OpenDrain>> {
-                    /// Enables / disables the internal pull up
-                    pub fn internal_pull_up(&mut self, on: bool) {
-                        let offset = 2 * $i;
-                        let value = if on { 0b01 } else { 0b00 };
-                        unsafe {
-                            &(*$GPIOX::ptr()).pupdr.modify(|r, w| {
-                                w.bits((r.bits() & !(0b11 << offset)) | (value << offset))
-                            })
-                        };
-                    }
-                }
-
                 impl<MODE> $PXi<Alternate<MODE>> {
                     /// Set pin speed
                     pub fn set_speed(self, speed: Speed) -> Self {
@@ -578,19 +1641,6 @@ macro_rules! gpio {
 
                         self
                     }
-
-                    /// Enables / disables the internal pull up
-                    pub fn internal_pull_up(self, on: bool) -> Self {
-                        let offset = 2 * $i;
-                        let value = if on { 0b01 } else { 0b00 };
-                        unsafe {
-                            &(*$GPIOX::ptr()).pupdr.modify(|r, w| {
-                                w.bits((r.bits() & !(0b11 << offset)) | (value << offset))
-                            })
-                        };
-
-                        self
-                    }
                 }
 
                 impl<MODE> $PXi<Alternate<MODE>> {
@@ -620,6 +1670,127 @@ macro_rules! gpio {
                             _mode: self._mode,
                         }
                     }
+
+                    /// Erases both the port and the pin number from the
+                    /// type. See `ErasedPin`
+                    pub fn erase(self) -> ErasedPin<MODE> {
+                        self.downgrade().erase()
+                    }
+
+                    /// Locks the configuration of this pin. See
+                    /// `lock_all`
+                    pub fn lock(self) -> LockedPin<Self> {
+                        lock_all(self)
+                    }
+
+                    /// Temporarily configures the pin as a floating input while
+                    /// `f` runs. The previous configuration is restored
+                    /// afterwards, even if `f` panics
+                    pub fn with_input_floating<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<Floating>>) -> R,
+                    ) -> R {
+                        let _guard = ModeGuard::save($extigpionr, $i);
+                        let pin = $PXi::<MODE> { _mode: PhantomData };
+                        f(&mut pin.into_floating_input())
+                    }
+
+                    /// Temporarily configures the pin as an input with the
+                    /// internal pull up while `f` runs. The previous
+                    /// configuration is restored afterwards, even if `f` panics
+                    pub fn with_input_pull_up<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<PullUp>>) -> R,
+                    ) -> R {
+                        let _guard = ModeGuard::save($extigpionr, $i);
+                        let pin = $PXi::<MODE> { _mode: PhantomData };
+                        f(&mut pin.into_pull_up_input())
+                    }
+
+                    /// Temporarily configures the pin as an input with the
+                    /// internal pull down while `f` runs. The previous
+                    /// configuration is restored afterwards, even if `f` panics
+                    pub fn with_input_pull_down<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<PullDown>>) -> R,
+                    ) -> R {
+                        let _guard = ModeGuard::save($extigpionr, $i);
+                        let pin = $PXi::<MODE> { _mode: PhantomData };
+                        f(&mut pin.into_pull_down_input())
+                    }
+
+                    /// Temporarily configures the pin as a push pull output
+                    /// while `f` runs. The previous configuration is restored
+                    /// afterwards, even if `f` panics
+                    pub fn with_push_pull_output<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Output<PushPull>>) -> R,
+                    ) -> R {
+                        let _guard = ModeGuard::save($extigpionr, $i);
+                        let pin = $PXi::<MODE> { _mode: PhantomData };
+                        f(&mut pin.into_push_pull_output())
+                    }
+
+                    /// Temporarily configures the pin as an open drain output
+                    /// while `f` runs. The previous configuration is restored
+                    /// afterwards, even if `f` panics
+                    pub fn with_open_drain_output<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Output<OpenDrain>>) -> R,
+                    ) -> R {
+                        let _guard = ModeGuard::save($extigpionr, $i);
+                        let pin = $PXi::<MODE> { _mode: PhantomData };
+                        f(&mut pin.into_open_drain_output())
+                    }
+
+                    /// Configures the pin as a floating input, with a
+                    /// mode that can be changed at run time. See
+                    /// `DynamicPin`
+                    pub fn into_dynamic(self) -> DynamicPin<$PXi<Dynamic>> {
+                        let mut pin = DynamicPin {
+                            pin: $PXi { _mode: PhantomData },
+                            port: $extigpionr,
+                            i: $i,
+                            mode: DynamicMode::FloatingInput,
+                        };
+                        pin.make_floating_input();
+
+                        pin
+                    }
+
+                    /// Enables / disables the internal pull up. Disables
+                    /// the internal pull down when enabled, but leaves it
+                    /// in place when disabled
+                    pub fn internal_pull_up(&mut self, on: bool) {
+                        if on {
+                            _set_pull($i, 0b01);
+                        } else {
+                            _clear_pull($i, 0b01);
+                        }
+                    }
+
+                    /// Enables / disables the internal pull down. Disables
+                    /// the internal pull up when enabled, but leaves it in
+                    /// place when disabled
+                    pub fn internal_pull_down(&mut self, on: bool) {
+                        if on {
+                            _set_pull($i, 0b10);
+                        } else {
+                            _clear_pull($i, 0b10);
+                        }
+                    }
+                }
+
+                impl<MODE> LockPins for $PXi<MODE> {
+                    type Locked = LockedPin<Self>;
+
+                    fn mask(&self) -> u16 {
+                        1 << $i
+                    }
+
+                    fn into_locked(self) -> Self::Locked {
+                        LockedPin { pin: self }
+                    }
                 }
 
                 impl<MODE> OutputPin for $PXi<Output<MODE>> {
@@ -656,7 +1827,21 @@ macro_rules! gpio {
                     }
                 }
 
-                impl<MODE> toggleable::Default for $PXi<Output<MODE>> {}
+                impl<MODE> ToggleableOutputPin for $PXi<Output<MODE>> {
+                    type Error = Never;
+
+                    fn toggle(&mut self) -> Result<(), Never> {
+                        // NOTE(unsafe) atomic read, then atomic write to
+                        // a stateless register
+                        unsafe {
+                            let odr = (*$GPIOX::ptr()).odr.read().bits();
+                            (*$GPIOX::ptr()).bsrr
+                                .write(|w| w.bits(toggle_bits(odr, $i)))
+                        }
+
+                        Ok(())
+                    }
+                }
 
                 impl<MODE> InputPin for $PXi<Output<MODE>> {
                     type Error = Never;
@@ -686,71 +1871,43 @@ macro_rules! gpio {
                     }
                 }
 
+                impl<MODE> InputPin for $PXi<Alternate<MODE>> {
+                    type Error = Never;
+
+                    fn is_high(&self) -> Result<bool, Never> {
+                        self.is_low().map(|v| !v)
+                    }
+
+                    fn is_low(&self) -> Result<bool, Never> {
+                        // NOTE(unsafe) atomic read with no side effects
+                        Ok(unsafe { (*$GPIOX::ptr()).idr
+                                      .read().bits() & (1 << $i) } == 0)
+                    }
+                }
+
                 impl<MODE> ExtiPin for $PXi<Input<MODE>> {
-                    /// Configure EXTI Line $i to trigger from this pin.
                     fn make_interrupt_source(&mut self, syscfg: &mut SYSCFG) {
-                        let offset = 4 * ($i % 4);
-                        syscfg.$exticri.modify(|r, w| unsafe {
-                            let mut exticr = r.bits();
-                            exticr = (exticr & !(0xf << offset)) | ($extigpionr << offset);
-                            w.bits(exticr)
-                        });
+                        super::exti_make_interrupt_source(syscfg, $extigpionr, $i);
                     }
 
-                    /// Generate interrupt on rising edge, falling edge or both
                     fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
-                        match edge {
-                            Edge::RISING => {
-                                exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
-                                exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
-                            },
-                            Edge::FALLING => {
-                                exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
-                                exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
-                            },
-                            Edge::RISING_FALLING => {
-                                exti.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
-                                exti.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
-                            }
-                        }
+                        super::exti_trigger_on_edge(exti, $i, edge);
                     }
 
-                    /// Enable external interrupts from this pin.
                     fn enable_interrupt(&mut self, exti: &mut EXTI) {
-                        #[cfg(any(feature = "singlecore"))]
-                        let imr1 = &exti.cpuimr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                        let imr1 = &exti.c1imr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                        let imr1 = &exti.c2imr1;
-
-                        imr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
+                        super::exti_set_interrupt(exti, $i, true);
                     }
 
-                    /// Disable external interrupts from this pin
                     fn disable_interrupt(&mut self, exti: &mut EXTI) {
-                        #[cfg(any(feature = "singlecore"))]
-                        let imr1 = &exti.cpuimr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                        let imr1 = &exti.c1imr1;
-                        #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                        let imr1 = &exti.c2imr1;
-
-                        imr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
+                        super::exti_set_interrupt(exti, $i, false);
                     }
 
-                    /// Clear the interrupt pending bit for this pin
                     fn clear_interrupt_pending_bit(&mut self) {
-                        unsafe {
-                            #[cfg(any(feature = "singlecore"))]
-                            let pr1 = &(*(EXTI::ptr())).cpupr1;
-                            #[cfg(all(feature = "dualcore", feature = "cm7"))]
-                            let pr1 = &(*(EXTI::ptr())).c1pr1;
-                            #[cfg(all(feature = "dualcore", feature = "cm4"))]
-                            let pr1 = &(*(EXTI::ptr())).c2pr1;
-
-                            pr1.write(|w| w.bits(1 << $i));
-                        };
+                        super::exti_clear_pending($i);
+                    }
+
+                    fn check_interrupt(&self) -> bool {
+                        super::exti_is_pending($i)
                     }
                 }
             )+
@@ -759,210 +1916,394 @@ macro_rules! gpio {
 }
 
 gpio!(GPIOA, gpioa, gpioaen, gpioarst, PA, 0, [
-    PA0: (pa0, 0, Analog, exticr1),
-    PA1: (pa1, 1, Analog, exticr1),
-    PA2: (pa2, 2, Analog, exticr1),
-    PA3: (pa3, 3, Analog, exticr1),
-    PA4: (pa4, 4, Analog, exticr2),
-    PA5: (pa5, 5, Analog, exticr2),
-    PA6: (pa6, 6, Analog, exticr2),
-    PA7: (pa7, 7, Analog, exticr2),
-    PA8: (pa8, 8, Analog, exticr3),
-    PA9: (pa9, 9, Analog, exticr3),
-    PA10: (pa10, 10, Analog, exticr3),
-    PA11: (pa11, 11, Analog, exticr3),
-    PA12: (pa12, 12, Analog, exticr4),
-    PA13: (pa13, 13, Alternate<AF0>, exticr4), // JTMS/SWDIO
-    PA14: (pa14, 14, Alternate<AF0>, exticr4), // JTCK/SWCLK
-    PA15: (pa15, 15, Alternate<AF0>, exticr4), // JTDI
+    PA0: (pa0, 0, Analog),
+    PA1: (pa1, 1, Analog),
+    PA2: (pa2, 2, Analog),
+    PA3: (pa3, 3, Analog),
+    PA4: (pa4, 4, Analog),
+    PA5: (pa5, 5, Analog),
+    PA6: (pa6, 6, Analog),
+    PA7: (pa7, 7, Analog),
+    PA8: (pa8, 8, Analog),
+    PA9: (pa9, 9, Analog),
+    PA10: (pa10, 10, Analog),
+    PA11: (pa11, 11, Analog),
+    PA12: (pa12, 12, Analog),
+    PA13: (pa13, 13, Alternate<AF0>), // JTMS/SWDIO
+    PA14: (pa14, 14, Alternate<AF0>), // JTCK/SWCLK
+    PA15: (pa15, 15, Alternate<AF0>), // JTDI
 ]);
 
 gpio!(GPIOB, gpiob, gpioben, gpiobrst, PB, 1, [
-    PB0: (pb0, 0, Analog, exticr1),
-    PB1: (pb1, 1, Analog, exticr1),
-    PB2: (pb2, 2, Analog, exticr1),
-    PB3: (pb3, 3, Alternate<AF0>, exticr1), // JTDO/TRACESWO
-    PB4: (pb4, 4, Alternate<AF0>, exticr2), // !JTRST
-    PB5: (pb5, 5, Analog, exticr2),
-    PB6: (pb6, 6, Analog, exticr2),
-    PB7: (pb7, 7, Analog, exticr2),
-    PB8: (pb8, 8, Analog, exticr3),
-    PB9: (pb9, 9, Analog, exticr3),
-    PB10: (pb10, 10, Analog, exticr3),
-    PB11: (pb11, 11, Analog, exticr3),
-    PB12: (pb12, 12, Analog, exticr4),
-    PB13: (pb13, 13, Analog, exticr4),
-    PB14: (pb14, 14, Analog, exticr4),
-    PB15: (pb15, 15, Analog, exticr4),
+    PB0: (pb0, 0, Analog),
+    PB1: (pb1, 1, Analog),
+    PB2: (pb2, 2, Analog),
+    PB3: (pb3, 3, Alternate<AF0>), // JTDO/TRACESWO
+    PB4: (pb4, 4, Alternate<AF0>), // !JTRST
+    PB5: (pb5, 5, Analog),
+    PB6: (pb6, 6, Analog),
+    PB7: (pb7, 7, Analog),
+    PB8: (pb8, 8, Analog),
+    PB9: (pb9, 9, Analog),
+    PB10: (pb10, 10, Analog),
+    PB11: (pb11, 11, Analog),
+    PB12: (pb12, 12, Analog),
+    PB13: (pb13, 13, Analog),
+    PB14: (pb14, 14, Analog),
+    PB15: (pb15, 15, Analog),
 ]);
 
 gpio!(GPIOC, gpioc, gpiocen, gpiocrst, PC, 2, [
-    PC0: (pc0, 0, Analog, exticr1),
-    PC1: (pc1, 1, Analog, exticr1),
-    PC2: (pc2, 2, Analog, exticr1),
-    PC3: (pc3, 3, Analog, exticr1),
-    PC4: (pc4, 4, Analog, exticr2),
-    PC5: (pc5, 5, Analog, exticr2),
-    PC6: (pc6, 6, Analog, exticr2),
-    PC7: (pc7, 7, Analog, exticr2),
-    PC8: (pc8, 8, Analog, exticr3),
-    PC9: (pc9, 9, Analog, exticr3),
-    PC10: (pc10, 10, Analog, exticr3),
-    PC11: (pc11, 11, Analog, exticr3),
-    PC12: (pc12, 12, Analog, exticr4),
-    PC13: (pc13, 13, Analog, exticr4),
-    PC14: (pc14, 14, Analog, exticr4),
-    PC15: (pc15, 15, Analog, exticr4),
+    PC0: (pc0, 0, Analog),
+    PC1: (pc1, 1, Analog),
+    PC2: (pc2, 2, Analog),
+    PC3: (pc3, 3, Analog),
+    PC4: (pc4, 4, Analog),
+    PC5: (pc5, 5, Analog),
+    PC6: (pc6, 6, Analog),
+    PC7: (pc7, 7, Analog),
+    PC8: (pc8, 8, Analog),
+    PC9: (pc9, 9, Analog),
+    PC10: (pc10, 10, Analog),
+    PC11: (pc11, 11, Analog),
+    PC12: (pc12, 12, Analog),
+    PC13: (pc13, 13, Analog),
+    PC14: (pc14, 14, Analog),
+    PC15: (pc15, 15, Analog),
 ]);
 
 gpio!(GPIOD, gpiod, gpioden, gpiodrst, PD, 3, [
-    PD0: (pd0, 0, Analog, exticr1),
-    PD1: (pd1, 1, Analog, exticr1),
-    PD2: (pd2, 2, Analog, exticr1),
-    PD3: (pd3, 3, Analog, exticr1),
-    PD4: (pd4, 4, Analog, exticr2),
-    PD5: (pd5, 5, Analog, exticr2),
-    PD6: (pd6, 6, Analog, exticr2),
-    PD7: (pd7, 7, Analog, exticr2),
-    PD8: (pd8, 8, Analog, exticr3),
-    PD9: (pd9, 9, Analog, exticr3),
-    PD10: (pd10, 10, Analog, exticr3),
-    PD11: (pd11, 11, Analog, exticr3),
-    PD12: (pd12, 12, Analog, exticr4),
-    PD13: (pd13, 13, Analog, exticr4),
-    PD14: (pd14, 14, Analog, exticr4),
-    PD15: (pd15, 15, Analog, exticr4),
+    PD0: (pd0, 0, Analog),
+    PD1: (pd1, 1, Analog),
+    PD2: (pd2, 2, Analog),
+    PD3: (pd3, 3, Analog),
+    PD4: (pd4, 4, Analog),
+    PD5: (pd5, 5, Analog),
+    PD6: (pd6, 6, Analog),
+    PD7: (pd7, 7, Analog),
+    PD8: (pd8, 8, Analog),
+    PD9: (pd9, 9, Analog),
+    PD10: (pd10, 10, Analog),
+    PD11: (pd11, 11, Analog),
+    PD12: (pd12, 12, Analog),
+    PD13: (pd13, 13, Analog),
+    PD14: (pd14, 14, Analog),
+    PD15: (pd15, 15, Analog),
 ]);
 
 gpio!(GPIOE, gpioe, gpioeen, gpioerst, PE, 4, [
-    PE0: (pe0, 0, Analog, exticr1),
-    PE1: (pe1, 1, Analog, exticr1),
-    PE2: (pe2, 2, Analog, exticr1),
-    PE3: (pe3, 3, Analog, exticr1),
-    PE4: (pe4, 4, Analog, exticr2),
-    PE5: (pe5, 5, Analog, exticr2),
-    PE6: (pe6, 6, Analog, exticr2),
-    PE7: (pe7, 7, Analog, exticr2),
-    PE8: (pe8, 8, Analog, exticr3),
-    PE9: (pe9, 9, Analog, exticr3),
-    PE10: (pe10, 10, Analog, exticr3),
-    PE11: (pe11, 11, Analog, exticr3),
-    PE12: (pe12, 12, Analog, exticr4),
-    PE13: (pe13, 13, Analog, exticr4),
-    PE14: (pe14, 14, Analog, exticr4),
-    PE15: (pe15, 15, Analog, exticr4),
+    PE0: (pe0, 0, Analog),
+    PE1: (pe1, 1, Analog),
+    PE2: (pe2, 2, Analog),
+    PE3: (pe3, 3, Analog),
+    PE4: (pe4, 4, Analog),
+    PE5: (pe5, 5, Analog),
+    PE6: (pe6, 6, Analog),
+    PE7: (pe7, 7, Analog),
+    PE8: (pe8, 8, Analog),
+    PE9: (pe9, 9, Analog),
+    PE10: (pe10, 10, Analog),
+    PE11: (pe11, 11, Analog),
+    PE12: (pe12, 12, Analog),
+    PE13: (pe13, 13, Analog),
+    PE14: (pe14, 14, Analog),
+    PE15: (pe15, 15, Analog),
 ]);
 
 gpio!(GPIOF, gpiof, gpiofen, gpiofrst, PF, 5, [
-    PF0: (pf0, 0, Analog, exticr1),
-    PF1: (pf1, 1, Analog, exticr1),
-    PF2: (pf2, 2, Analog, exticr1),
-    PF3: (pf3, 3, Analog, exticr1),
-    PF4: (pf4, 4, Analog, exticr2),
-    PF5: (pf5, 5, Analog, exticr2),
-    PF6: (pf6, 6, Analog, exticr2),
-    PF7: (pf7, 7, Analog, exticr2),
-    PF8: (pf8, 8, Analog, exticr3),
-    PF9: (pf9, 9, Analog, exticr3),
-    PF10: (pf10, 10, Analog, exticr3),
-    PF11: (pf11, 11, Analog, exticr3),
-    PF12: (pf12, 12, Analog, exticr4),
-    PF13: (pf13, 13, Analog, exticr4),
-    PF14: (pf14, 14, Analog, exticr4),
-    PF15: (pf15, 15, Analog, exticr4),
+    PF0: (pf0, 0, Analog),
+    PF1: (pf1, 1, Analog),
+    PF2: (pf2, 2, Analog),
+    PF3: (pf3, 3, Analog),
+    PF4: (pf4, 4, Analog),
+    PF5: (pf5, 5, Analog),
+    PF6: (pf6, 6, Analog),
+    PF7: (pf7, 7, Analog),
+    PF8: (pf8, 8, Analog),
+    PF9: (pf9, 9, Analog),
+    PF10: (pf10, 10, Analog),
+    PF11: (pf11, 11, Analog),
+    PF12: (pf12, 12, Analog),
+    PF13: (pf13, 13, Analog),
+    PF14: (pf14, 14, Analog),
+    PF15: (pf15, 15, Analog),
 ]);
 
 gpio!(GPIOG, gpiog, gpiogen, gpiogrst, PG, 6, [
-    PG0: (pg0, 0, Analog, exticr1),
-    PG1: (pg1, 1, Analog, exticr1),
-    PG2: (pg2, 2, Analog, exticr1),
-    PG3: (pg3, 3, Analog, exticr1),
-    PG4: (pg4, 4, Analog, exticr2),
-    PG5: (pg5, 5, Analog, exticr2),
-    PG6: (pg6, 6, Analog, exticr2),
-    PG7: (pg7, 7, Analog, exticr2),
-    PG8: (pg8, 8, Analog, exticr3),
-    PG9: (pg9, 9, Analog, exticr3),
-    PG10: (pg10, 10, Analog, exticr3),
-    PG11: (pg11, 11, Analog, exticr3),
-    PG12: (pg12, 12, Analog, exticr4),
-    PG13: (pg13, 13, Analog, exticr4),
-    PG14: (pg14, 14, Analog, exticr4),
-    PG15: (pg15, 15, Analog, exticr4),
+    PG0: (pg0, 0, Analog),
+    PG1: (pg1, 1, Analog),
+    PG2: (pg2, 2, Analog),
+    PG3: (pg3, 3, Analog),
+    PG4: (pg4, 4, Analog),
+    PG5: (pg5, 5, Analog),
+    PG6: (pg6, 6, Analog),
+    PG7: (pg7, 7, Analog),
+    PG8: (pg8, 8, Analog),
+    PG9: (pg9, 9, Analog),
+    PG10: (pg10, 10, Analog),
+    PG11: (pg11, 11, Analog),
+    PG12: (pg12, 12, Analog),
+    PG13: (pg13, 13, Analog),
+    PG14: (pg14, 14, Analog),
+    PG15: (pg15, 15, Analog),
 ]);
 
 gpio!(GPIOH, gpioh, gpiohen, gpiohrst, PH, 7, [
-    PH0: (ph0, 0, Analog, exticr1),
-    PH1: (ph1, 1, Analog, exticr1),
-    PH2: (ph2, 2, Analog, exticr1),
-    PH3: (ph3, 3, Analog, exticr1),
-    PH4: (ph4, 4, Analog, exticr2),
-    PH5: (ph5, 5, Analog, exticr2),
-    PH6: (ph6, 6, Analog, exticr2),
-    PH7: (ph7, 7, Analog, exticr2),
-    PH8: (ph8, 8, Analog, exticr3),
-    PH9: (ph9, 9, Analog, exticr3),
-    PH10: (ph10, 10, Analog, exticr3),
-    PH11: (ph11, 11, Analog, exticr3),
-    PH12: (ph12, 12, Analog, exticr4),
-    PH13: (ph13, 13, Analog, exticr4),
-    PH14: (ph14, 14, Analog, exticr4),
-    PH15: (ph15, 15, Analog, exticr4),
+    PH0: (ph0, 0, Analog),
+    PH1: (ph1, 1, Analog),
+    PH2: (ph2, 2, Analog),
+    PH3: (ph3, 3, Analog),
+    PH4: (ph4, 4, Analog),
+    PH5: (ph5, 5, Analog),
+    PH6: (ph6, 6, Analog),
+    PH7: (ph7, 7, Analog),
+    PH8: (ph8, 8, Analog),
+    PH9: (ph9, 9, Analog),
+    PH10: (ph10, 10, Analog),
+    PH11: (ph11, 11, Analog),
+    PH12: (ph12, 12, Analog),
+    PH13: (ph13, 13, Analog),
+    PH14: (ph14, 14, Analog),
+    PH15: (ph15, 15, Analog),
 ]);
 
 gpio!(GPIOI, gpioi, gpioien, gpioirst, PI, 8, [
-    PI0: (pi0, 0, Analog, exticr1),
-    PI1: (pi1, 1, Analog, exticr1),
-    PI2: (pi2, 2, Analog, exticr1),
-    PI3: (pi3, 3, Analog, exticr1),
-    PI4: (pi4, 4, Analog, exticr2),
-    PI5: (pi5, 5, Analog, exticr2),
-    PI6: (pi6, 6, Analog, exticr2),
-    PI7: (pi7, 7, Analog, exticr2),
-    PI8: (pi8, 8, Analog, exticr3),
-    PI9: (pi9, 9, Analog, exticr3),
-    PI10: (pi10, 10, Analog, exticr3),
-    PI11: (pi11, 11, Analog, exticr3),
-    PI12: (pi12, 12, Analog, exticr4),
-    PI13: (pi13, 13, Analog, exticr4),
-    PI14: (pi14, 14, Analog, exticr4),
-    PI15: (pi15, 15, Analog, exticr4),
+    PI0: (pi0, 0, Analog),
+    PI1: (pi1, 1, Analog),
+    PI2: (pi2, 2, Analog),
+    PI3: (pi3, 3, Analog),
+    PI4: (pi4, 4, Analog),
+    PI5: (pi5, 5, Analog),
+    PI6: (pi6, 6, Analog),
+    PI7: (pi7, 7, Analog),
+    PI8: (pi8, 8, Analog),
+    PI9: (pi9, 9, Analog),
+    PI10: (pi10, 10, Analog),
+    PI11: (pi11, 11, Analog),
+    PI12: (pi12, 12, Analog),
+    PI13: (pi13, 13, Analog),
+    PI14: (pi14, 14, Analog),
+    PI15: (pi15, 15, Analog),
 ]);
 
 gpio!(GPIOJ, gpioj, gpiojen, gpiojrst, PJ, 9, [
-    PJ0: (pj0, 0, Analog, exticr1),
-    PJ1: (pj1, 1, Analog, exticr1),
-    PJ2: (pj2, 2, Analog, exticr1),
-    PJ3: (pj3, 3, Analog, exticr1),
-    PJ4: (pj4, 4, Analog, exticr2),
-    PJ5: (pj5, 5, Analog, exticr2),
-    PJ6: (pj6, 6, Analog, exticr2),
-    PJ7: (pj7, 7, Analog, exticr2),
-    PJ8: (pj8, 8, Analog, exticr3),
-    PJ9: (pj9, 9, Analog, exticr3),
-    PJ10: (pj10, 10, Analog, exticr3),
-    PJ11: (pj11, 11, Analog, exticr3),
-    PJ12: (pj12, 12, Analog, exticr4),
-    PJ13: (pj13, 13, Analog, exticr4),
-    PJ14: (pj14, 14, Analog, exticr4),
-    PJ15: (pj15, 15, Analog, exticr4),
+    PJ0: (pj0, 0, Analog),
+    PJ1: (pj1, 1, Analog),
+    PJ2: (pj2, 2, Analog),
+    PJ3: (pj3, 3, Analog),
+    PJ4: (pj4, 4, Analog),
+    PJ5: (pj5, 5, Analog),
+    PJ6: (pj6, 6, Analog),
+    PJ7: (pj7, 7, Analog),
+    PJ8: (pj8, 8, Analog),
+    PJ9: (pj9, 9, Analog),
+    PJ10: (pj10, 10, Analog),
+    PJ11: (pj11, 11, Analog),
+    PJ12: (pj12, 12, Analog),
+    PJ13: (pj13, 13, Analog),
+    PJ14: (pj14, 14, Analog),
+    PJ15: (pj15, 15, Analog),
 ]);
 
 gpio!(GPIOK, gpiok, gpioken, gpiokrst, PK, 10, [
-    PK0: (pk0, 0, Analog, exticr1),
-    PK1: (pk1, 1, Analog, exticr1),
-    PK2: (pk2, 2, Analog, exticr1),
-    PK3: (pk3, 3, Analog, exticr1),
-    PK4: (pk4, 4, Analog, exticr2),
-    PK5: (pk5, 5, Analog, exticr2),
-    PK6: (pk6, 6, Analog, exticr2),
-    PK7: (pk7, 7, Analog, exticr2),
-    PK8: (pk8, 8, Analog, exticr3),
-    PK9: (pk9, 9, Analog, exticr3),
-    PK10: (pk10, 10, Analog, exticr3),
-    PK11: (pk11, 11, Analog, exticr3),
-    PK12: (pk12, 12, Analog, exticr4),
-    PK13: (pk13, 13, Analog, exticr4),
-    PK14: (pk14, 14, Analog, exticr4),
-    PK15: (pk15, 15, Analog, exticr4),
+    PK0: (pk0, 0, Analog),
+    PK1: (pk1, 1, Analog),
+    PK2: (pk2, 2, Analog),
+    PK3: (pk3, 3, Analog),
+    PK4: (pk4, 4, Analog),
+    PK5: (pk5, 5, Analog),
+    PK6: (pk6, 6, Analog),
+    PK7: (pk7, 7, Analog),
+    PK8: (pk8, 8, Analog),
+    PK9: (pk9, 9, Analog),
+    PK10: (pk10, 10, Analog),
+    PK11: (pk11, 11, Analog),
+    PK12: (pk12, 12, Analog),
+    PK13: (pk13, 13, Analog),
+    PK14: (pk14, 14, Analog),
+    PK15: (pk15, 15, Analog),
 ]);
+
+/// Implements `PinAf` from the alternate function table
+macro_rules! pin_af {
+    ($($port:ident::$PXi:ident: [$($N:literal),+],)+) => {
+        $(
+            $(
+                impl<MODE> PinAf<$N> for $port::$PXi<MODE> {}
+            )+
+        )+
+    };
+}
+
+// Generated by `tools/pin_af.py tools/af_stm32h742_743_750_753.csv`
+#[cfg(feature = "singlecore")]
+pin_af! {
+    gpioa::PA0: [1, 2, 3, 4, 7, 8, 9, 10, 11, 15],
+    gpioa::PA1: [1, 2, 3, 4, 7, 8, 9, 10, 11, 14, 15],
+    gpioa::PA2: [1, 2, 3, 4, 7, 8, 11, 12, 14, 15],
+    gpioa::PA3: [1, 2, 3, 4, 7, 9, 10, 11, 14, 15],
+    gpioa::PA4: [2, 5, 6, 7, 8, 12, 13, 14, 15],
+    gpioa::PA5: [1, 3, 5, 8, 10, 14, 15],
+    gpioa::PA6: [1, 2, 3, 5, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpioa::PA7: [1, 2, 3, 5, 8, 9, 11, 12, 15],
+    gpioa::PA8: [0, 1, 2, 3, 4, 7, 10, 11, 12, 13, 14, 15],
+    gpioa::PA9: [1, 2, 3, 4, 5, 7, 9, 13, 14, 15],
+    gpioa::PA10: [1, 2, 3, 7, 9, 10, 11, 12, 13, 14, 15],
+    gpioa::PA11: [1, 2, 3, 5, 6, 7, 9, 14, 15],
+    gpioa::PA12: [1, 2, 3, 5, 6, 7, 8, 9, 14, 15],
+    gpioa::PA13: [0, 15],
+    gpioa::PA14: [0, 15],
+    gpioa::PA15: [0, 1, 2, 4, 5, 6, 7, 8, 11, 15],
+    gpiob::PB0: [1, 2, 3, 6, 8, 9, 10, 11, 14, 15],
+    gpiob::PB1: [1, 2, 3, 6, 9, 10, 11, 14, 15],
+    gpiob::PB2: [0, 2, 4, 6, 7, 8, 9, 10, 11, 15],
+    gpiob::PB3: [0, 1, 2, 5, 6, 8, 9, 11, 15],
+    gpiob::PB4: [0, 1, 2, 3, 5, 6, 7, 8, 9, 11, 15],
+    gpiob::PB5: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpiob::PB6: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpiob::PB7: [1, 2, 3, 4, 6, 7, 8, 9, 11, 12, 13, 15],
+    gpiob::PB8: [1, 2, 3, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpiob::PB9: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpiob::PB10: [1, 2, 3, 4, 5, 6, 7, 9, 10, 11, 14, 15],
+    gpiob::PB11: [1, 2, 3, 4, 6, 7, 10, 11, 14, 15],
+    gpiob::PB12: [1, 3, 4, 5, 6, 7, 9, 10, 11, 12, 13, 14, 15],
+    gpiob::PB13: [1, 3, 5, 6, 7, 9, 10, 11, 14, 15],
+    gpiob::PB14: [1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 15],
+    gpiob::PB15: [0, 1, 2, 3, 4, 5, 6, 8, 9, 12, 15],
+    gpioc::PC0: [3, 6, 8, 10, 12, 14, 15],
+    gpioc::PC1: [0, 2, 3, 4, 5, 6, 8, 9, 10, 11, 12, 15],
+    gpioc::PC2: [3, 5, 6, 10, 11, 12, 15],
+    gpioc::PC3: [3, 5, 10, 11, 12, 15],
+    gpioc::PC4: [3, 5, 9, 11, 12, 15],
+    gpioc::PC5: [2, 3, 9, 10, 11, 12, 13, 15],
+    gpioc::PC6: [1, 2, 3, 4, 5, 7, 8, 9, 10, 12, 13, 14, 15],
+    gpioc::PC7: [0, 1, 2, 3, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpioc::PC8: [0, 1, 2, 3, 7, 8, 9, 11, 12, 13, 15],
+    gpioc::PC9: [0, 2, 3, 4, 5, 8, 9, 11, 12, 13, 14, 15],
+    gpioc::PC10: [1, 3, 6, 7, 8, 9, 12, 13, 14, 15],
+    gpioc::PC11: [1, 3, 6, 7, 8, 9, 12, 13, 15],
+    gpioc::PC12: [0, 1, 6, 7, 8, 12, 13, 15],
+    gpioc::PC13: [15],
+    gpioc::PC14: [15],
+    gpioc::PC15: [15],
+    gpiod::PD0: [3, 6, 8, 9, 12, 15],
+    gpiod::PD1: [3, 6, 8, 9, 12, 15],
+    gpiod::PD2: [0, 2, 8, 12, 13, 15],
+    gpiod::PD3: [3, 5, 7, 12, 13, 14, 15],
+    gpiod::PD4: [2, 6, 7, 9, 12, 15],
+    gpiod::PD5: [2, 7, 9, 12, 15],
+    gpiod::PD6: [2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpiod::PD7: [3, 5, 6, 7, 9, 11, 12, 15],
+    gpiod::PD8: [3, 7, 9, 12, 15],
+    gpiod::PD9: [3, 7, 9, 12, 15],
+    gpiod::PD10: [3, 7, 9, 12, 14, 15],
+    gpiod::PD11: [3, 4, 7, 9, 10, 12, 15],
+    gpiod::PD12: [1, 2, 3, 4, 7, 9, 10, 12, 15],
+    gpiod::PD13: [1, 2, 4, 9, 10, 12, 15],
+    gpiod::PD14: [2, 8, 12, 15],
+    gpiod::PD15: [2, 8, 12, 15],
+    gpioe::PE0: [1, 2, 3, 4, 8, 9, 10, 12, 13, 15],
+    gpioe::PE1: [1, 3, 8, 9, 12, 13, 15],
+    gpioe::PE2: [0, 2, 5, 6, 8, 9, 10, 11, 12, 15],
+    gpioe::PE3: [0, 4, 6, 8, 12, 15],
+    gpioe::PE4: [0, 2, 3, 4, 5, 6, 8, 10, 12, 13, 14, 15],
+    gpioe::PE5: [0, 2, 3, 4, 5, 6, 8, 10, 12, 13, 14, 15],
+    gpioe::PE6: [0, 1, 2, 4, 5, 6, 8, 9, 10, 11, 12, 13, 14, 15],
+    gpioe::PE7: [1, 3, 7, 10, 12, 15],
+    gpioe::PE8: [1, 3, 7, 10, 12, 13, 15],
+    gpioe::PE9: [1, 3, 7, 10, 12, 15],
+    gpioe::PE10: [1, 3, 7, 10, 12, 15],
+    gpioe::PE11: [1, 3, 5, 10, 12, 14, 15],
+    gpioe::PE12: [1, 3, 5, 10, 12, 13, 14, 15],
+    gpioe::PE13: [1, 3, 5, 10, 12, 13, 14, 15],
+    gpioe::PE14: [1, 5, 10, 12, 14, 15],
+    gpioe::PE15: [1, 12, 13, 14, 15],
+    gpiof::PF0: [4, 12, 15],
+    gpiof::PF1: [4, 12, 15],
+    gpiof::PF2: [4, 12, 15],
+    gpiof::PF3: [12, 15],
+    gpiof::PF4: [12, 15],
+    gpiof::PF5: [12, 15],
+    gpiof::PF6: [1, 5, 6, 7, 8, 9, 15],
+    gpiof::PF7: [1, 5, 6, 7, 8, 9, 15],
+    gpiof::PF8: [1, 5, 6, 7, 8, 9, 10, 15],
+    gpiof::PF9: [1, 5, 6, 7, 8, 9, 10, 15],
+    gpiof::PF10: [1, 2, 9, 10, 13, 14, 15],
+    gpiof::PF11: [5, 10, 12, 13, 15],
+    gpiof::PF12: [12, 15],
+    gpiof::PF13: [3, 4, 12, 15],
+    gpiof::PF14: [3, 4, 12, 15],
+    gpiof::PF15: [4, 12, 15],
+    gpiog::PG0: [12, 15],
+    gpiog::PG1: [12, 15],
+    gpiog::PG2: [3, 11, 12, 15],
+    gpiog::PG3: [3, 11, 12, 15],
+    gpiog::PG4: [1, 11, 12, 15],
+    gpiog::PG5: [1, 12, 15],
+    gpiog::PG6: [1, 2, 10, 12, 13, 14, 15],
+    gpiog::PG7: [2, 6, 7, 12, 13, 14, 15],
+    gpiog::PG8: [3, 5, 7, 8, 11, 12, 14, 15],
+    gpiog::PG9: [5, 7, 8, 9, 10, 12, 13, 15],
+    gpiog::PG10: [2, 5, 9, 10, 12, 13, 14, 15],
+    gpiog::PG11: [1, 2, 5, 8, 11, 13, 14, 15],
+    gpiog::PG12: [1, 2, 5, 7, 8, 9, 11, 12, 14, 15],
+    gpiog::PG13: [0, 1, 2, 5, 7, 11, 12, 14, 15],
+    gpiog::PG14: [0, 1, 5, 7, 9, 11, 12, 14, 15],
+    gpiog::PG15: [7, 12, 13, 15],
+    gpioh::PH0: [15],
+    gpioh::PH1: [15],
+    gpioh::PH2: [1, 9, 10, 11, 12, 14, 15],
+    gpioh::PH3: [9, 10, 11, 12, 14, 15],
+    gpioh::PH4: [4, 9, 10, 14, 15],
+    gpioh::PH5: [4, 5, 12, 15],
+    gpioh::PH6: [2, 4, 5, 11, 12, 13, 15],
+    gpioh::PH7: [4, 5, 11, 12, 13, 15],
+    gpioh::PH8: [2, 4, 12, 13, 14, 15],
+    gpioh::PH9: [2, 4, 12, 13, 14, 15],
+    gpioh::PH10: [2, 4, 12, 13, 14, 15],
+    gpioh::PH11: [2, 4, 12, 13, 14, 15],
+    gpioh::PH12: [2, 4, 12, 13, 14, 15],
+    gpioh::PH13: [3, 8, 9, 12, 14, 15],
+    gpioh::PH14: [3, 8, 9, 12, 13, 14, 15],
+    gpioh::PH15: [3, 9, 12, 13, 14, 15],
+    gpioi::PI0: [2, 5, 12, 13, 14, 15],
+    gpioi::PI1: [3, 5, 11, 12, 13, 14, 15],
+    gpioi::PI2: [3, 5, 12, 13, 14, 15],
+    gpioi::PI3: [3, 5, 12, 13, 15],
+    gpioi::PI4: [3, 10, 11, 12, 13, 14, 15],
+    gpioi::PI5: [3, 10, 12, 13, 14, 15],
+    gpioi::PI6: [3, 10, 12, 13, 14, 15],
+    gpioi::PI7: [3, 10, 12, 13, 14, 15],
+    gpioi::PI8: [15],
+    gpioi::PI9: [8, 9, 12, 14, 15],
+    gpioi::PI10: [9, 11, 12, 14, 15],
+    gpioi::PI11: [9, 10, 15],
+    gpioi::PI12: [14, 15],
+    gpioi::PI13: [14, 15],
+    gpioi::PI14: [14, 15],
+    gpioi::PI15: [9, 14, 15],
+    gpioj::PJ0: [9, 14, 15],
+    gpioj::PJ1: [14, 15],
+    gpioj::PJ2: [14, 15],
+    gpioj::PJ3: [14, 15],
+    gpioj::PJ4: [14, 15],
+    gpioj::PJ5: [14, 15],
+    gpioj::PJ6: [3, 14, 15],
+    gpioj::PJ7: [0, 3, 14, 15],
+    gpioj::PJ8: [1, 3, 8, 14, 15],
+    gpioj::PJ9: [1, 3, 8, 14, 15],
+    gpioj::PJ10: [1, 3, 5, 14, 15],
+    gpioj::PJ11: [1, 3, 5, 14, 15],
+    gpioj::PJ12: [0, 9, 14, 15],
+    gpioj::PJ13: [9, 14, 15],
+    gpioj::PJ14: [14, 15],
+    gpioj::PJ15: [14, 15],
+    gpiok::PK0: [1, 3, 5, 14, 15],
+    gpiok::PK1: [1, 3, 5, 14, 15],
+    gpiok::PK2: [1, 3, 10, 11, 14, 15],
+    gpiok::PK3: [14, 15],
+    gpiok::PK4: [14, 15],
+    gpiok::PK5: [14, 15],
+    gpiok::PK6: [14, 15],
+    gpiok::PK7: [14, 15],
+}
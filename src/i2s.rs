@@ -0,0 +1,781 @@
+//! Inter-IC Sound (I2S) bus
+//!
+//! SPI1, SPI2 and SPI3 can be used as an I2S audio interface. The
+//! frame rate is derived from the SPI123 kernel clock, which can be the
+//! external clock on the I2S_CKIN pin.
+//!
+//! ```rust
+//! // 48 kHz stereo, 24-bit data, with a master clock of 256 * 48 kHz
+//! let pins = (ck, ws, sdo, i2s::NoSdi, mck);
+//! let config = i2s::Config::new(i2s::Mode::MasterTransmit, 48.khz())
+//!     .data_format(i2s::DataFormat::Data24Channel32)
+//!     .master_clock();
+//! let mut i2s =
+//!     dp.SPI1
+//!         .i2s(pins, config, ccdr.peripheral.SPI1, &ccdr.clocks);
+//!
+//! loop {
+//!     i2s.write_stereo(left, right)?;
+//! }
+//! ```
+
+use crate::stm32::{SPI1, SPI2, SPI3};
+use core::ptr;
+use nb;
+
+use crate::gpio::gpioa::{PA11, PA12, PA15, PA4, PA5, PA6, PA7, PA9};
+use crate::gpio::gpiob::{
+    PB10, PB12, PB13, PB14, PB15, PB2, PB3, PB4, PB5, PB9,
+};
+use crate::gpio::gpioc::{PC1, PC10, PC11, PC12, PC2, PC3, PC4, PC6, PC7};
+use crate::gpio::gpiod::{PD3, PD6, PD7};
+use crate::gpio::gpiog::{PG10, PG11, PG9};
+use crate::gpio::gpioi::{PI0, PI1, PI2, PI3};
+
+use crate::gpio::{Alternate, AF5, AF6, AF7};
+
+use crate::rcc::{rec, CoreClocks};
+use crate::spi::{Instance, SpiExt};
+use crate::time::Hertz;
+
+/// I2S error
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Overrun occurred: a sample was received while the receive FIFO
+    /// was full
+    Overrun,
+    /// Underrun occurred: a sample was due while the transmit FIFO
+    /// was empty
+    Underrun,
+    /// Frame error: in slave mode, the word select signal changed
+    /// unexpectedly
+    Frame,
+}
+
+/// Interrupt events
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A sample can be written (TXP)
+    Txp,
+    /// A sample has been received (RXP)
+    Rxp,
+    /// The end of the transfer (EOT), after the communication has
+    /// been suspended
+    Eot,
+    /// An error occurred: underrun, overrun or frame error
+    Error,
+}
+
+/// I2S communication standard
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Standard {
+    /// Philips I2S
+    Philips,
+    /// MSB justified (left justified)
+    MsbJustified,
+    /// LSB justified (right justified)
+    LsbJustified,
+    /// PCM with a short frame synchronisation
+    PcmShortSync,
+    /// PCM with a long frame synchronisation
+    PcmLongSync,
+}
+
+/// I2S mode: clock direction and data direction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Generate the clocks and transmit on SDO
+    MasterTransmit,
+    /// Generate the clocks and receive on SDI
+    MasterReceive,
+    /// Generate the clocks, transmit on SDO and receive on SDI
+    MasterFullDuplex,
+    /// Transmit on SDO, clocked by the master
+    SlaveTransmit,
+    /// Receive on SDI, clocked by the master
+    SlaveReceive,
+    /// Transmit on SDO and receive on SDI, clocked by the master
+    SlaveFullDuplex,
+}
+
+impl Mode {
+    fn is_master(self) -> bool {
+        matches!(
+            self,
+            Mode::MasterTransmit
+                | Mode::MasterReceive
+                | Mode::MasterFullDuplex
+        )
+    }
+
+    /// I2SCFG field
+    fn bits(self) -> u32 {
+        match self {
+            Mode::SlaveTransmit => 0b000,
+            Mode::SlaveReceive => 0b001,
+            Mode::MasterTransmit => 0b010,
+            Mode::MasterReceive => 0b011,
+            Mode::SlaveFullDuplex => 0b100,
+            Mode::MasterFullDuplex => 0b101,
+        }
+    }
+}
+
+/// Length of the data, and of each channel in the frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// 16-bit data in a 16-bit channel
+    Data16Channel16,
+    /// 16-bit data in a 32-bit channel
+    Data16Channel32,
+    /// 24-bit data in a 32-bit channel
+    Data24Channel32,
+    /// 32-bit data in a 32-bit channel
+    Data32Channel32,
+}
+
+impl DataFormat {
+    fn is_16bit(self) -> bool {
+        matches!(
+            self,
+            DataFormat::Data16Channel16 | DataFormat::Data16Channel32
+        )
+    }
+
+    /// DATLEN and CHLEN fields
+    fn bits(self) -> u32 {
+        match self {
+            DataFormat::Data16Channel16 => 0,
+            DataFormat::Data16Channel32 => I2SCFGR_CHLEN,
+            DataFormat::Data24Channel32 => (0b01 << 8) | I2SCFGR_CHLEN,
+            DataFormat::Data32Channel32 => (0b10 << 8) | I2SCFGR_CHLEN,
+        }
+    }
+
+    /// Number of bit clock cycles in a stereo frame. See
+    /// `Config::clock_ratio` for the PCM standards
+    fn frame_bits(self) -> u32 {
+        match self {
+            DataFormat::Data16Channel16 => 32,
+            _ => 64,
+        }
+    }
+}
+
+const I2SCFGR_I2SMOD: u32 = 1 << 0;
+const I2SCFGR_PCMSYNC: u32 = 1 << 7;
+const I2SCFGR_CHLEN: u32 = 1 << 10;
+const I2SCFGR_CKPOL: u32 = 1 << 11;
+const I2SCFGR_ODD: u32 = 1 << 24;
+const I2SCFGR_MCKOE: u32 = 1 << 25;
+
+/// A structure for specifying the I2S configuration.
+///
+/// This structure uses builder semantics to generate the configuration.
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    pub mode: Mode,
+    pub standard: Standard,
+    pub data_format: DataFormat,
+    pub frame_rate: Hertz,
+    pub master_clock: bool,
+    pub clock_idle_high: bool,
+    pub i2s_ckin: Option<Hertz>,
+}
+
+impl Config {
+    /// Create a default configuration: Philips standard, 16-bit data
+    /// in 16-bit channels, without master clock output.
+    ///
+    /// Arguments:
+    /// * `mode` - The clock and data direction.
+    /// * `frame_rate` - The audio sample rate. Only used in master
+    ///   mode.
+    pub fn new<T: Into<Hertz>>(mode: Mode, frame_rate: T) -> Self {
+        Config {
+            mode,
+            standard: Standard::Philips,
+            data_format: DataFormat::Data16Channel16,
+            frame_rate: frame_rate.into(),
+            master_clock: false,
+            clock_idle_high: false,
+            i2s_ckin: None,
+        }
+    }
+
+    /// Select the communication standard. The default is
+    /// `Standard::Philips`.
+    pub fn standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Select the data and channel lengths. The default is
+    /// `DataFormat::Data16Channel16`.
+    pub fn data_format(mut self, format: DataFormat) -> Self {
+        self.data_format = format;
+        self
+    }
+
+    /// Output the master clock on the MCK pin, at 256 times the frame
+    /// rate, or 128 times for the PCM standards. Only used in master
+    /// mode.
+    pub fn master_clock(mut self) -> Self {
+        self.master_clock = true;
+        self
+    }
+
+    /// Idle level of the bit clock CK. The default is low, with data
+    /// changing on the falling edge.
+    pub fn clock_idle_high(mut self) -> Self {
+        self.clock_idle_high = true;
+        self
+    }
+
+    /// Set the frequency of the external clock on the I2S_CKIN pin,
+    /// when it is selected as the kernel clock. See
+    /// `spi::Config::i2s_ckin`.
+    pub fn i2s_ckin<T: Into<Hertz>>(mut self, freq: T) -> Self {
+        self.i2s_ckin = Some(freq.into());
+        self
+    }
+}
+
+impl Config {
+    /// Ratio of the frequency the divider generates to the frame rate:
+    /// the master clock if it is output, otherwise the bit clock. A
+    /// PCM frame holds a single channel
+    fn clock_ratio(&self) -> u32 {
+        let pcm = matches!(
+            self.standard,
+            Standard::PcmShortSync | Standard::PcmLongSync
+        );
+
+        match (pcm, self.master_clock) {
+            (false, true) => 256,
+            (false, false) => self.data_format.frame_bits(),
+            (true, true) => 128,
+            (true, false) => self.data_format.frame_bits() / 2,
+        }
+    }
+}
+
+/// Returns the I2SDIV and ODD values that divide `ker_ck` down to
+/// `ratio` times `frame_rate`, or `None` if that is out of range
+fn divider(ker_ck: u32, frame_rate: u32, ratio: u32) -> Option<(u8, bool)> {
+    let den = frame_rate.checked_mul(ratio).filter(|&den| den != 0)?;
+    let div = (ker_ck + den / 2) / den;
+
+    match div {
+        // I2SDIV = 0 bypasses the divider
+        1 => Some((0, false)),
+        // I2SDIV = 1 with ODD = 1 is not allowed
+        3 => None,
+        2..=511 => Some(((div / 2) as u8, div & 1 == 1)),
+        _ => None,
+    }
+}
+
+pub trait Pins<SPI> {
+    /// `true` if the pins include an MCK pin
+    const MCK: bool = false;
+}
+pub trait PinCk<SPI> {}
+pub trait PinWs<SPI> {}
+pub trait PinSdo<SPI> {}
+pub trait PinSdi<SPI> {}
+pub trait PinMck<SPI> {
+    /// `false` for `NoMck`
+    const ENABLED: bool = true;
+}
+
+/// A filler type for when the SDO pin is unnecessary
+pub struct NoSdo;
+/// A filler type for when the SDI pin is unnecessary
+pub struct NoSdi;
+/// A filler type for when the MCK pin is unnecessary
+pub struct NoMck;
+
+impl<SPI> PinSdo<SPI> for NoSdo {}
+impl<SPI> PinSdi<SPI> for NoSdi {}
+impl<SPI> PinMck<SPI> for NoMck {
+    const ENABLED: bool = false;
+}
+
+impl<SPI, CK, WS, SDO, SDI, MCK> Pins<SPI> for (CK, WS, SDO, SDI, MCK)
+where
+    CK: PinCk<SPI>,
+    WS: PinWs<SPI>,
+    SDO: PinSdo<SPI>,
+    SDI: PinSdi<SPI>,
+    MCK: PinMck<SPI>,
+{
+    const MCK: bool = MCK::ENABLED;
+}
+
+macro_rules! pins {
+    ($($SPIX:ty:
+       CK: [$($CK:ident<$CK_AF:ident>),*]
+       WS: [$($WS:ident<$WS_AF:ident>),*]
+       SDO: [$($SDO:ident<$SDO_AF:ident>),*]
+       SDI: [$($SDI:ident<$SDI_AF:ident>),*]
+       MCK: [$($MCK:ident<$MCK_AF:ident>),*])+) => {
+        $(
+            $(
+                impl PinCk<$SPIX> for $CK<Alternate<$CK_AF>> {}
+            )*
+            $(
+                impl PinWs<$SPIX> for $WS<Alternate<$WS_AF>> {}
+            )*
+            $(
+                impl PinSdo<$SPIX> for $SDO<Alternate<$SDO_AF>> {}
+            )*
+            $(
+                impl PinSdi<$SPIX> for $SDI<Alternate<$SDI_AF>> {}
+            )*
+            $(
+                impl PinMck<$SPIX> for $MCK<Alternate<$MCK_AF>> {}
+            )*
+        )+
+    }
+}
+
+pins! {
+    SPI1:
+        CK: [
+            PA5<AF5>,
+            PB3<AF5>,
+            PG11<AF5>
+        ]
+        WS: [
+            PA4<AF5>,
+            PA15<AF5>,
+            PG10<AF5>
+        ]
+        SDO: [
+            PA7<AF5>,
+            PB5<AF5>,
+            PD7<AF5>
+        ]
+        SDI: [
+            PA6<AF5>,
+            PB4<AF5>,
+            PG9<AF5>
+        ]
+        MCK: [
+            PC4<AF5>
+        ]
+    SPI2:
+        CK: [
+            PA9<AF5>,
+            PA12<AF5>,
+            PB10<AF5>,
+            PB13<AF5>,
+            PD3<AF5>,
+            PI1<AF5>
+        ]
+        WS: [
+            PA11<AF5>,
+            PB4<AF7>,
+            PB9<AF5>,
+            PB12<AF5>,
+            PI0<AF5>
+        ]
+        SDO: [
+            PB15<AF5>,
+            PC1<AF5>,
+            PC3<AF5>,
+            PI3<AF5>
+        ]
+        SDI: [
+            PB14<AF5>,
+            PC2<AF5>,
+            PI2<AF5>
+        ]
+        MCK: [
+            PC6<AF5>
+        ]
+    SPI3:
+        CK: [
+            PB3<AF6>,
+            PC10<AF6>
+        ]
+        WS: [
+            PA4<AF6>,
+            PA15<AF6>
+        ]
+        SDO: [
+            PB2<AF7>,
+            PB5<AF7>,
+            PC12<AF6>,
+            PD6<AF5>
+        ]
+        SDI: [
+            PB4<AF6>,
+            PC11<AF6>
+        ]
+        MCK: [
+            PC7<AF6>
+        ]
+}
+
+/// An SPI peripheral in I2S mode
+#[derive(Debug)]
+pub struct I2s<SPI, PINS>
+where
+    SPI: Instance,
+{
+    spi: SPI,
+    pins: PINS,
+    rec: <SPI as SpiExt<SPI>>::Rec,
+    data_format: DataFormat,
+    frame_rate: Option<Hertz>,
+}
+
+pub trait I2sExt<SPI: Instance>: Sized {
+    /// Reset and enable control token for this instance, found in
+    /// `ccdr.peripheral`
+    type Rec;
+
+    /// Configure the SPI peripheral as an I2S interface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the kernel clock is not running, or if the frame
+    /// rate cannot be generated from it in master mode. Panics if
+    /// `config.master_clock` is set without an MCK pin.
+    fn i2s<PINS>(
+        self,
+        pins: PINS,
+        config: Config,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> I2s<SPI, PINS>
+    where
+        PINS: Pins<SPI>;
+}
+
+macro_rules! i2s {
+    ($($SPIX:ident: $Rec:ident,)+) => {
+        $(
+            impl I2sExt<$SPIX> for $SPIX {
+                type Rec = rec::$Rec;
+
+                fn i2s<PINS>(
+                    self,
+                    pins: PINS,
+                    config: Config,
+                    prec: rec::$Rec,
+                    clocks: &CoreClocks,
+                ) -> I2s<$SPIX, PINS>
+                where
+                    PINS: Pins<$SPIX>,
+                {
+                    assert!(
+                        !config.master_clock || PINS::MCK,
+                        "master_clock requires an MCK pin"
+                    );
+
+                    // Divider, in master mode
+                    let master = config.mode.is_master();
+                    let (div, frame_rate) = if master {
+                        let ker_ck =
+                            $SPIX::kernel_clk_ckin(clocks, config.i2s_ckin)
+                                .expect("I2S kernel clock not running")
+                                .0;
+                        let ratio = config.clock_ratio();
+                        let (i2sdiv, odd) =
+                            divider(ker_ck, config.frame_rate.0, ratio)
+                                .expect("I2S frame rate out of range");
+
+                        let div = if i2sdiv == 0 {
+                            1
+                        } else {
+                            2 * i2sdiv as u32 + odd as u32
+                        };
+                        let bits = ((i2sdiv as u32) << 16)
+                            | if odd { I2SCFGR_ODD } else { 0 };
+                        (bits, Some(Hertz(ker_ck / (ratio * div))))
+                    } else {
+                        (0, None)
+                    };
+
+                    // Enable and reset SPI
+                    let rec = prec.enable().reset();
+
+                    let (standard, pcmsync) = match config.standard {
+                        Standard::Philips => (0b00, 0),
+                        Standard::MsbJustified => (0b01, 0),
+                        Standard::LsbJustified => (0b10, 0),
+                        Standard::PcmShortSync => (0b11, 0),
+                        Standard::PcmLongSync => (0b11, I2SCFGR_PCMSYNC),
+                    };
+                    let i2scfgr = I2SCFGR_I2SMOD
+                        | (config.mode.bits() << 1)
+                        | (standard << 4)
+                        | pcmsync
+                        | config.data_format.bits()
+                        | if config.clock_idle_high { I2SCFGR_CKPOL } else { 0 }
+                        | div
+                        | if master && config.master_clock {
+                            I2SCFGR_MCKOE
+                        } else {
+                            0
+                        };
+
+                    // I2SCFGR can only be written while disabled
+                    self.cr1.modify(|_, w| w.spe().disabled());
+                    // NOTE(unsafe) all of the fields written are
+                    // valid, see above
+                    self.i2scfgr.write(|w| unsafe { w.bits(i2scfgr) });
+                    self.cr1.modify(|_, w| w.spe().enabled());
+
+                    I2s {
+                        spi: self,
+                        pins,
+                        rec,
+                        data_format: config.data_format,
+                        frame_rate,
+                    }
+                }
+            }
+
+            impl<PINS> I2s<$SPIX, PINS> {
+                /// Disable the I2S interface and its peripheral clock,
+                /// then release the peripheral, the pins and the reset
+                /// and enable control token.
+                pub fn release(self) -> ($SPIX, PINS, rec::$Rec) {
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    // NOTE(unsafe) return to SPI mode
+                    self.spi.i2scfgr.write(|w| unsafe { w.bits(0) });
+
+                    (self.spi, self.pins, self.rec.disable())
+                }
+            }
+        )+
+    }
+}
+
+i2s! {
+    SPI1: Spi1,
+    SPI2: Spi2,
+    SPI3: Spi3,
+}
+
+impl<SPI, PINS> I2s<SPI, PINS>
+where
+    SPI: Instance,
+{
+    /// Returns the frame rate actually generated in master mode, or
+    /// `None` in slave mode
+    pub fn frame_rate(&self) -> Option<Hertz> {
+        self.frame_rate
+    }
+
+    /// Start the communication, if not already started. In master
+    /// mode, the clocks are then generated.
+    fn start(&mut self) {
+        if self.spi.cr1.read().cstart().bit_is_clear() {
+            self.spi.cr1.modify(|_, w| w.cstart().started());
+        }
+    }
+
+    /// Returns the error flagged by the peripheral, if any. The flag
+    /// is cleared
+    fn check_errors(&mut self) -> Result<(), Error> {
+        let sr = self.spi.sr.read();
+
+        if sr.ovr().is_overrun() {
+            self.spi.ifcr.write(|w| w.ovrc().set_bit());
+            Err(Error::Overrun)
+        } else if sr.udr().bit_is_set() {
+            self.spi.ifcr.write(|w| w.udrc().set_bit());
+            Err(Error::Underrun)
+        } else if sr.tifre().bit_is_set() {
+            self.spi.ifcr.write(|w| w.tifrec().set_bit());
+            Err(Error::Frame)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a sample for the next channel to the transmit FIFO.
+    /// Samples alternate between the left and right channels,
+    /// starting with the left channel. Data is right aligned: for
+    /// 24-bit data, the upper 8 bits are ignored.
+    ///
+    /// The communication is started by the first sample, so that
+    /// the transmit FIFO is not empty when the clocks start.
+    pub fn send(&mut self, sample: u32) -> nb::Result<(), Error> {
+        self.check_errors()?;
+
+        if self.spi.sr.read().txp().is_not_full() {
+            if self.data_format.is_16bit() {
+                // NOTE(write_volatile) write exactly one half-word,
+                // so that exactly one sample is pushed into the FIFO
+                unsafe {
+                    ptr::write_volatile(
+                        ptr::addr_of!(self.spi.txdr) as *mut u16,
+                        sample as u16,
+                    )
+                }
+            } else {
+                // NOTE(write_volatile) write exactly one word
+                unsafe {
+                    ptr::write_volatile(
+                        ptr::addr_of!(self.spi.txdr) as *mut u32,
+                        sample,
+                    )
+                }
+            }
+            self.start();
+
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Read a sample of the next channel from the receive FIFO.
+    /// Samples alternate between the left and right channels,
+    /// starting with the left channel.
+    ///
+    /// The communication is started if it was not already.
+    pub fn read(&mut self) -> nb::Result<u32, Error> {
+        self.start();
+        self.check_errors()?;
+
+        if self.spi.sr.read().rxp().is_not_empty() {
+            Ok(if self.data_format.is_16bit() {
+                // NOTE(read_volatile) read exactly one half-word
+                unsafe {
+                    ptr::read_volatile(
+                        &self.spi.rxdr as *const _ as *const u16,
+                    ) as u32
+                }
+            } else {
+                // NOTE(read_volatile) read exactly one word
+                unsafe {
+                    ptr::read_volatile(
+                        &self.spi.rxdr as *const _ as *const u32,
+                    )
+                }
+            })
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Write a stereo sample, blocking until there is space in the
+    /// transmit FIFO
+    pub fn write_stereo(&mut self, left: u32, right: u32) -> Result<(), Error> {
+        nb::block!(self.send(left))?;
+        nb::block!(self.send(right))
+    }
+
+    /// Read a stereo sample `(left, right)`, blocking until it has
+    /// been received
+    pub fn read_stereo(&mut self) -> Result<(u32, u32), Error> {
+        let left = nb::block!(self.read())?;
+        let right = nb::block!(self.read())?;
+
+        Ok((left, right))
+    }
+
+    /// Enable interrupts for the given `event`
+    pub fn listen(&mut self, event: Event) {
+        self.set_interrupt(event, true);
+    }
+
+    /// Disable interrupts for the given `event`
+    pub fn unlisten(&mut self, event: Event) {
+        self.set_interrupt(event, false);
+    }
+
+    fn set_interrupt(&mut self, event: Event, enable: bool) {
+        self.spi.ier.modify(|_, w| match event {
+            Event::Txp => w.txpie().bit(enable),
+            Event::Rxp => w.rxpie().bit(enable),
+            Event::Eot => w.eotie().bit(enable),
+            Event::Error => w
+                .udrie()
+                .bit(enable)
+                .ovrie()
+                .bit(enable)
+                .tifreie()
+                .bit(enable),
+        });
+    }
+
+    /// Return `true` if the TXP flag is set, i.e. a sample can be
+    /// written
+    pub fn is_txp(&self) -> bool {
+        self.spi.sr.read().txp().is_not_full()
+    }
+
+    /// Return `true` if the RXP flag is set, i.e. a sample has been
+    /// received
+    pub fn is_rxp(&self) -> bool {
+        self.spi.sr.read().rxp().is_not_empty()
+    }
+
+    /// Return `true` if the EOT flag is set
+    pub fn is_eot(&self) -> bool {
+        self.spi.sr.read().eot().bit_is_set()
+    }
+
+    /// Clear the EOT flag
+    pub fn clear_eot(&mut self) {
+        self.spi.ifcr.write(|w| w.eotc().set_bit());
+    }
+
+    /// Stop the communication at the end of the current frame. In
+    /// master mode, the clocks are stopped. EOT is set once stopped
+    pub fn suspend(&mut self) {
+        if self.spi.cr1.read().cstart().bit_is_set() {
+            self.spi.cr1.modify(|_, w| w.csusp().set_bit());
+            while self.spi.sr.read().susp().bit_is_clear() {}
+            self.spi.ifcr.write(|w| w.suspc().set_bit());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{divider, Config, DataFormat, Mode, Standard};
+    use crate::time::Hertz;
+
+    fn config(standard: Standard, format: DataFormat) -> Config {
+        Config::new(Mode::MasterTransmit, Hertz(48_000))
+            .standard(standard)
+            .data_format(format)
+    }
+
+    #[test]
+    fn clock_ratio_by_standard() {
+        let table = [
+            (Standard::Philips, DataFormat::Data16Channel16, 32, 256),
+            (Standard::LsbJustified, DataFormat::Data24Channel32, 64, 256),
+            (Standard::PcmShortSync, DataFormat::Data16Channel16, 16, 128),
+            (Standard::PcmLongSync, DataFormat::Data16Channel32, 32, 128),
+        ];
+
+        for &(standard, format, ck, mck) in &table {
+            let config = config(standard, format);
+            assert_eq!(config.clock_ratio(), ck);
+            assert_eq!(config.master_clock().clock_ratio(), mck);
+        }
+    }
+
+    #[test]
+    fn pcm_frame_rate() {
+        // 48 kHz from a 12.288 MHz kernel clock
+        let pcm = config(Standard::PcmShortSync, DataFormat::Data16Channel16);
+        let ratio = pcm.clock_ratio();
+        assert_eq!(divider(12_288_000, 48_000, ratio), Some((8, false)));
+
+        let ratio = pcm.master_clock().clock_ratio();
+        assert_eq!(divider(12_288_000, 48_000, ratio), Some((1, false)));
+    }
+}
@@ -57,6 +57,13 @@ compile_error!("Cannot not select both singlecore and dualcore");
 #[cfg(all(feature = "cm7", feature = "cm4"))]
 compile_error!("Cannot not select both CM7 and CM4");
 
+#[cfg(any(
+    all(feature = "lqfp100", feature = "lqfp144"),
+    all(feature = "lqfp100", feature = "lqfp176"),
+    all(feature = "lqfp144", feature = "lqfp176"),
+))]
+compile_error!("Cannot select more than one package");
+
 #[cfg(feature = "device-selected")]
 pub use crate::stm32 as pac;
 #[cfg(feature = "device-selected")]
@@ -79,6 +86,8 @@ pub mod gpio;
 #[cfg(feature = "device-selected")]
 pub mod i2c;
 #[cfg(feature = "device-selected")]
+pub mod i2s;
+#[cfg(feature = "device-selected")]
 pub mod prelude;
 #[cfg(feature = "device-selected")]
 pub mod pwm;
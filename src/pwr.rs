@@ -47,6 +47,24 @@
 //! POR, and this is enforced by hardware. If you add or change the
 //! power supply method, `freeze` will panic until you power on reset
 //! your board.
+//!
+//! # Wakeup pins
+//!
+//! The WKUP pins are configured through a `Wakeup` token, which must be
+//! taken before `freeze`. See `gpio::WakeupPin`.
+//!
+//! ```rust
+//!     let mut pwr = dp.PWR.constrain();
+//!     let mut wakeup = pwr.take_wakeup().unwrap();
+//!     let vos = pwr.freeze();
+//!
+//!     // ...
+//!     let button = gpioc.pc13.into_wakeup_source(
+//!         &mut wakeup,
+//!         WakeupPolarity::Rising,
+//!         WakeupPull::Down,
+//!     );
+//! ```
 
 use crate::stm32::PWR;
 
@@ -59,6 +77,7 @@ impl PwrExt for PWR {
     fn constrain(self) -> Pwr {
         Pwr {
             rb: self,
+            wakeup_taken: false,
             #[cfg(any(feature = "dualcore"))]
             supply_configuration: SupplyConfiguration::Default,
         }
@@ -70,6 +89,7 @@ impl PwrExt for PWR {
 /// Generated by calling `constrain` on the PAC's PWR peripheral.
 pub struct Pwr {
     pub(crate) rb: PWR,
+    wakeup_taken: bool,
     #[cfg(any(feature = "dualcore"))]
     supply_configuration: SupplyConfiguration,
 }
@@ -86,6 +106,93 @@ pub enum VoltageScale {
     Scale3,
 }
 
+/// Edge of a WKUP pin that wakes the device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WakeupPolarity {
+    Rising,
+    Falling,
+}
+
+/// Pull resistor applied to a WKUP pin by the PWR unit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WakeupPull {
+    None = 0b00,
+    Up = 0b01,
+    Down = 0b10,
+}
+
+/// Wakeup pin control
+///
+/// Owns the wakeup pin registers of the PWR unit. Taken from `Pwr`
+/// with `take_wakeup`, and remains available after `freeze`.
+pub struct Wakeup {
+    _0: (),
+}
+
+impl Wakeup {
+    /// Enables WKUP `line` (1 - 6)
+    pub(crate) fn enable(
+        &mut self,
+        line: u8,
+        polarity: WakeupPolarity,
+        pull: WakeupPull,
+    ) {
+        let n = line - 1;
+        let falling = (polarity == WakeupPolarity::Falling) as u32;
+        let mask = (1 << n) | (1 << (8 + n)) | (0b11 << (16 + 2 * n));
+        let bits =
+            (1 << n) | (falling << (8 + n)) | ((pull as u32) << (16 + 2 * n));
+
+        // NOTE(unsafe) this token owns the wakeup pin registers
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.wkupepr
+            .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | bits) });
+    }
+
+    /// Disables WKUP `line` (1 - 6)
+    pub(crate) fn disable(&mut self, line: u8) {
+        // NOTE(unsafe) see above
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.wkupepr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << (line - 1))) });
+    }
+
+    /// Returns the lowest WKUP line (1 - 6) with its wakeup flag set.
+    /// After a wakeup from Standby, this is the pin that caused it
+    pub fn source(&self) -> Option<u8> {
+        // NOTE(unsafe) see above
+        let pwr = unsafe { &*PWR::ptr() };
+        let flags = pwr.wkupfr.read().bits() & 0x3F;
+
+        if flags == 0 {
+            None
+        } else {
+            Some(flags.trailing_zeros() as u8 + 1)
+        }
+    }
+
+    /// Returns true if the wakeup flag of WKUP `line` (1 - 6) is set
+    pub fn is_flag_set(&self, line: u8) -> bool {
+        // NOTE(unsafe) see above
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.wkupfr.read().bits() & (1 << (line - 1)) != 0
+    }
+
+    /// Clears the wakeup flag of WKUP `line` (1 - 6)
+    pub fn clear_flag(&mut self, line: u8) {
+        // NOTE(unsafe) see above
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.wkupcr.write(|w| unsafe { w.bits(1 << (line - 1)) });
+    }
+
+    /// Clears all the wakeup flags
+    pub fn clear_flags(&mut self) {
+        // NOTE(unsafe) see above
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.wkupcr.write(|w| unsafe { w.bits(0x3F) });
+    }
+}
+
 /// SMPS Supply Configuration - Dual Core parts
 ///
 /// Refer to RM0399 Rev 2 Table 31.
@@ -178,6 +285,17 @@ impl Pwr {
         }
     }
 
+    /// Takes the wakeup pin control token. Returns `None` if it has
+    /// already been taken
+    pub fn take_wakeup(&mut self) -> Option<Wakeup> {
+        if self.wakeup_taken {
+            None
+        } else {
+            self.wakeup_taken = true;
+            Some(Wakeup { _0: () })
+        }
+    }
+
     pub fn freeze(self) -> VoltageScale {
         // NB. The lower bytes of CR3 can only be written once after
         // POR, and must be written with a valid combination. Refer to
@@ -88,10 +88,17 @@ use crate::stm32::{rcc, RCC, SYSCFG};
 use crate::time::Hertz;
 
 mod core_clocks;
+mod peripheral_clocks;
 mod pll;
+pub mod rec;
 
 pub use core_clocks::CoreClocks;
+pub use peripheral_clocks::{
+    ClockNotRunning, PeripheralClocks, Spi123ClkSel, Spi45ClkSel,
+    Spi6ClkSel,
+};
 pub use pll::{PllConfig, PllConfigStrategy};
+pub use rec::PeripheralREC;
 
 /// Configuration of the core clocks
 pub struct Config {
@@ -180,6 +187,10 @@ pub struct Ccdr {
     pub apb4: APB4,
     /// RCC Domain 3 Kernel Clock Configuration Register
     pub d3ccipr: D3CCIPR,
+    /// Kernel clock selection for peripherals
+    pub peripheral_clocks: PeripheralClocks,
+    /// Peripheral reset and enable control tokens
+    pub peripheral: PeripheralREC,
     // Yes, it lives (locally)! We retain the right to switch most
     // PKSUs on the fly, to fine-tune PLL frequencies, and to enable /
     // reset peripherals.
@@ -802,6 +813,36 @@ impl Rcc {
         });
         while syscfg.cccsr.read().ready().bit_is_clear() {}
 
+        let clocks = CoreClocks {
+            hclk: Hertz(rcc_hclk),
+            pclk1: Hertz(rcc_pclk1),
+            pclk2: Hertz(rcc_pclk2),
+            pclk3: Hertz(rcc_pclk3),
+            pclk4: Hertz(rcc_pclk4),
+            ppre1,
+            ppre2,
+            ppre3,
+            ppre4,
+            csi_ck: Some(Hertz(csi)),
+            hsi_ck: Some(Hertz(hsi)),
+            hsi48_ck: Some(Hertz(hsi48)),
+            per_ck: Some(Hertz(per_ck)),
+            hse_ck,
+            pll1_p_ck,
+            pll1_q_ck,
+            pll1_r_ck,
+            pll2_p_ck,
+            pll2_q_ck,
+            pll2_r_ck,
+            pll3_p_ck,
+            pll3_q_ck,
+            pll3_r_ck,
+            timx_ker_ck: Hertz(rcc_timx_ker_ck),
+            timy_ker_ck: Hertz(rcc_timy_ker_ck),
+            sys_ck,
+            c_ck: Hertz(sys_d1cpre_ck),
+        };
+
         // Return frozen clock configuration
         Ccdr {
             ahb1: AHB1 { _0: () },
@@ -813,36 +854,10 @@ impl Rcc {
             apb2: APB2 { _0: () },
             apb3: APB3 { _0: () },
             apb4: APB4 { _0: () },
-            clocks: CoreClocks {
-                hclk: Hertz(rcc_hclk),
-                pclk1: Hertz(rcc_pclk1),
-                pclk2: Hertz(rcc_pclk2),
-                pclk3: Hertz(rcc_pclk3),
-                pclk4: Hertz(rcc_pclk4),
-                ppre1,
-                ppre2,
-                ppre3,
-                ppre4,
-                csi_ck: Some(Hertz(csi)),
-                hsi_ck: Some(Hertz(hsi)),
-                hsi48_ck: Some(Hertz(hsi48)),
-                per_ck: Some(Hertz(per_ck)),
-                hse_ck,
-                pll1_p_ck,
-                pll1_q_ck,
-                pll1_r_ck,
-                pll2_p_ck,
-                pll2_q_ck,
-                pll2_r_ck,
-                pll3_p_ck,
-                pll3_q_ck,
-                pll3_r_ck,
-                timx_ker_ck: Hertz(rcc_timx_ker_ck),
-                timy_ker_ck: Hertz(rcc_timy_ker_ck),
-                sys_ck,
-                c_ck: Hertz(sys_d1cpre_ck),
-            },
+            clocks,
             d3ccipr: D3CCIPR { _0: () },
+            peripheral_clocks: PeripheralClocks { clocks },
+            peripheral: PeripheralREC::new(),
             rb: self.rb,
         }
     }
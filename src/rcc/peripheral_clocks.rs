@@ -0,0 +1,189 @@
+//! Kernel clock selection for peripherals
+
+use super::CoreClocks;
+use crate::stm32::{rcc, RCC};
+
+/// The selected kernel clock is not running
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClockNotRunning;
+
+/// Kernel clock for SPI1, SPI2 and SPI3
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spi123ClkSel {
+    /// pll1_q_ck. This is the reset value
+    Pll1Q,
+    /// pll2_p_ck
+    Pll2P,
+    /// pll3_p_ck
+    Pll3P,
+    /// External clock on the I2S_CKIN pin. Its frequency is not known
+    /// to the RCC, see `spi::Config::i2s_ckin`
+    I2sCkin,
+    /// per_ck. It is derived from HSI by default, or from CSI or HSE
+    /// if selected with `Rcc::per_ck`, so the SPI can be used without
+    /// configuring any PLL
+    Per,
+}
+
+/// Kernel clock for SPI4 and SPI5
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spi45ClkSel {
+    /// rcc_pclk2. This is the reset value
+    Apb,
+    /// pll2_q_ck
+    Pll2Q,
+    /// pll3_q_ck
+    Pll3Q,
+    /// hsi_ker_ck
+    Hsi,
+    /// csi_ker_ck
+    Csi,
+    /// hse_ck
+    Hse,
+}
+
+/// Kernel clock for SPI6
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spi6ClkSel {
+    /// rcc_pclk4. This is the reset value
+    Apb,
+    /// pll2_q_ck
+    Pll2Q,
+    /// pll3_q_ck
+    Pll3Q,
+    /// hsi_ker_ck
+    Hsi,
+    /// csi_ker_ck
+    Csi,
+    /// hse_ck
+    Hse,
+}
+
+/// Kernel clock selection for peripherals
+///
+/// Only clocks that were configured by `freeze` can be selected. The
+/// selection is read back by the peripheral constructors, so it
+/// should be made before constructing the peripheral.
+///
+/// ```rust
+/// let mut ccdr = rcc.pll3_p_ck(100.mhz()).freeze(vos, &dp.SYSCFG);
+/// ccdr.peripheral_clocks
+///     .spi123_sel(Spi123ClkSel::Pll3P)
+///     .unwrap();
+///
+/// let spi = dp.SPI1.spi(
+///     pins,
+///     spi::MODE_0,
+///     10.mhz(),
+///     ccdr.peripheral.SPI1,
+///     &ccdr.clocks,
+/// );
+/// ```
+pub struct PeripheralClocks {
+    pub(super) clocks: CoreClocks,
+}
+
+impl PeripheralClocks {
+    fn d2ccip1r(&mut self) -> &rcc::D2CCIP1R {
+        // NOTE(unsafe) this proxy grants exclusive access to the
+        // kernel clock selection fields that it writes
+        unsafe { &(*RCC::ptr()).d2ccip1r }
+    }
+
+    fn d3ccipr(&mut self) -> &rcc::D3CCIPR {
+        // NOTE(unsafe) see above
+        unsafe { &(*RCC::ptr()).d3ccipr }
+    }
+
+    /// Select the kernel clock for SPI1, SPI2 and SPI3
+    ///
+    /// Returns `ClockNotRunning`, without changing the selection, if
+    /// the clock was not configured by `freeze`.
+    pub fn spi123_sel(
+        &mut self,
+        sel: Spi123ClkSel,
+    ) -> Result<(), ClockNotRunning> {
+        let running = match sel {
+            Spi123ClkSel::Pll1Q => self.clocks.pll1_q_ck().is_some(),
+            Spi123ClkSel::Pll2P => self.clocks.pll2_p_ck().is_some(),
+            Spi123ClkSel::Pll3P => self.clocks.pll3_p_ck().is_some(),
+            // Provided by the application
+            Spi123ClkSel::I2sCkin => true,
+            Spi123ClkSel::Per => self.clocks.per_ck().is_some(),
+        };
+        if !running {
+            return Err(ClockNotRunning);
+        }
+
+        self.d2ccip1r().modify(|_, w| match sel {
+            Spi123ClkSel::Pll1Q => w.spi123sel().pll1_q(),
+            Spi123ClkSel::Pll2P => w.spi123sel().pll2_p(),
+            Spi123ClkSel::Pll3P => w.spi123sel().pll3_p(),
+            Spi123ClkSel::I2sCkin => w.spi123sel().i2s_ckin(),
+            Spi123ClkSel::Per => w.spi123sel().per(),
+        });
+
+        Ok(())
+    }
+
+    /// Select the kernel clock for SPI4 and SPI5
+    ///
+    /// Returns `ClockNotRunning`, without changing the selection, if
+    /// the clock was not configured by `freeze`.
+    pub fn spi45_sel(
+        &mut self,
+        sel: Spi45ClkSel,
+    ) -> Result<(), ClockNotRunning> {
+        let running = match sel {
+            Spi45ClkSel::Apb => true,
+            Spi45ClkSel::Pll2Q => self.clocks.pll2_q_ck().is_some(),
+            Spi45ClkSel::Pll3Q => self.clocks.pll3_q_ck().is_some(),
+            Spi45ClkSel::Hsi => self.clocks.hsi_ck().is_some(),
+            Spi45ClkSel::Csi => self.clocks.csi_ck().is_some(),
+            Spi45ClkSel::Hse => self.clocks.hse_ck().is_some(),
+        };
+        if !running {
+            return Err(ClockNotRunning);
+        }
+
+        self.d2ccip1r().modify(|_, w| match sel {
+            Spi45ClkSel::Apb => w.spi45sel().apb(),
+            Spi45ClkSel::Pll2Q => w.spi45sel().pll2_q(),
+            Spi45ClkSel::Pll3Q => w.spi45sel().pll3_q(),
+            Spi45ClkSel::Hsi => w.spi45sel().hsi_ker(),
+            Spi45ClkSel::Csi => w.spi45sel().csi_ker(),
+            Spi45ClkSel::Hse => w.spi45sel().hse(),
+        });
+
+        Ok(())
+    }
+
+    /// Select the kernel clock for SPI6
+    ///
+    /// Returns `ClockNotRunning`, without changing the selection, if
+    /// the clock was not configured by `freeze`.
+    pub fn spi6_sel(&mut self, sel: Spi6ClkSel) -> Result<(), ClockNotRunning> {
+        let running = match sel {
+            Spi6ClkSel::Apb => true,
+            Spi6ClkSel::Pll2Q => self.clocks.pll2_q_ck().is_some(),
+            Spi6ClkSel::Pll3Q => self.clocks.pll3_q_ck().is_some(),
+            Spi6ClkSel::Hsi => self.clocks.hsi_ck().is_some(),
+            Spi6ClkSel::Csi => self.clocks.csi_ck().is_some(),
+            Spi6ClkSel::Hse => self.clocks.hse_ck().is_some(),
+        };
+        if !running {
+            return Err(ClockNotRunning);
+        }
+
+        self.d3ccipr().modify(|_, w| match sel {
+            Spi6ClkSel::Apb => w.spi6sel().rcc_pclk4(),
+            Spi6ClkSel::Pll2Q => w.spi6sel().pll2_q(),
+            Spi6ClkSel::Pll3Q => w.spi6sel().pll3_q(),
+            Spi6ClkSel::Hsi => w.spi6sel().hsi_ker(),
+            Spi6ClkSel::Csi => w.spi6sel().csi_ker(),
+            Spi6ClkSel::Hse => w.spi6sel().hse(),
+        });
+
+        Ok(())
+    }
+}
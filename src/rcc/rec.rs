@@ -0,0 +1,135 @@
+//! Peripheral Reset and Enable Control (REC)
+//!
+//! Each peripheral that supports it has a token in
+//! `ccdr.peripheral`. The token is consumed by the constructor of the
+//! peripheral, which enables and resets it, and is returned when the
+//! peripheral is freed. Holding the token is therefore proof that the
+//! peripheral is not in use, and that its clock may be disabled.
+//!
+//! ```rust
+//! let ccdr = rcc.freeze(vos, &dp.SYSCFG);
+//!
+//! let spi = dp.SPI1.spi(pins, spi::MODE_0, 1.mhz(),
+//!                       ccdr.peripheral.SPI1, &ccdr.clocks);
+//! let (spi1, pins, rec) = spi.free();
+//! ```
+#![allow(non_snake_case)]
+
+use cortex_m::interrupt;
+
+use crate::stm32::RCC;
+
+/// Reset and enable control, implemented by each token. This allows
+/// drivers that are generic over several instances of a peripheral to
+/// take the token of each instance
+pub trait ResetEnable {
+    /// Enable the clock for this peripheral
+    fn enable(self) -> Self;
+    /// Disable the clock for this peripheral
+    fn disable(self) -> Self;
+    /// Reset this peripheral
+    fn reset(self) -> Self;
+}
+
+macro_rules! peripheral_reset_and_enable_control {
+    ($($Name:ident, $NAME:ident: ($enr:ident, $rstr:ident, $en:ident,
+                                  $rst:ident, $doc:expr),)+) => {
+        /// Reset and enable control tokens for each peripheral
+        pub struct PeripheralREC {
+            $(
+                #[doc=$doc]
+                pub $NAME: $Name,
+            )+
+        }
+
+        impl PeripheralREC {
+            /// Create the tokens. Called once by `freeze`
+            pub(super) fn new() -> Self {
+                PeripheralREC {
+                    $(
+                        $NAME: $Name { _0: () },
+                    )+
+                }
+            }
+        }
+
+        $(
+            #[doc=$doc]
+            #[derive(Debug)]
+            pub struct $Name {
+                _0: (),
+            }
+
+            impl $Name {
+                /// Enable the clock for this peripheral
+                pub fn enable(self) -> Self {
+                    // The enable register is shared with other
+                    // peripherals, whose tokens may be used from an
+                    // interrupt
+                    interrupt::free(|_| {
+                        // NOTE(unsafe) this token grants exclusive
+                        // access to the enable bit of its peripheral
+                        let rcc = unsafe { &*RCC::ptr() };
+                        rcc.$enr.modify(|_, w| w.$en().enabled());
+                    });
+
+                    self
+                }
+
+                /// Disable the clock for this peripheral
+                pub fn disable(self) -> Self {
+                    interrupt::free(|_| {
+                        // NOTE(unsafe) see above
+                        let rcc = unsafe { &*RCC::ptr() };
+                        rcc.$enr.modify(|_, w| w.$en().disabled());
+                    });
+
+                    self
+                }
+
+                /// Reset this peripheral
+                pub fn reset(mut self) -> Self {
+                    self.reset_in_place();
+
+                    self
+                }
+
+                /// Reset this peripheral, for a driver that holds the
+                /// token
+                pub(crate) fn reset_in_place(&mut self) {
+                    // The reset register is shared, see `enable`
+                    interrupt::free(|_| {
+                        // NOTE(unsafe) this token grants exclusive
+                        // access to the reset bit of its peripheral
+                        let rcc = unsafe { &*RCC::ptr() };
+                        rcc.$rstr.modify(|_, w| w.$rst().set_bit());
+                        rcc.$rstr.modify(|_, w| w.$rst().clear_bit());
+                    });
+                }
+            }
+
+            impl ResetEnable for $Name {
+                fn enable(self) -> Self {
+                    $Name::enable(self)
+                }
+
+                fn disable(self) -> Self {
+                    $Name::disable(self)
+                }
+
+                fn reset(self) -> Self {
+                    $Name::reset(self)
+                }
+            }
+        )+
+    }
+}
+
+peripheral_reset_and_enable_control! {
+    Spi1, SPI1: (apb2enr, apb2rstr, spi1en, spi1rst, "SPI1"),
+    Spi2, SPI2: (apb1lenr, apb1lrstr, spi2en, spi2rst, "SPI2"),
+    Spi3, SPI3: (apb1lenr, apb1lrstr, spi3en, spi3rst, "SPI3"),
+    Spi4, SPI4: (apb2enr, apb2rstr, spi4en, spi4rst, "SPI4"),
+    Spi5, SPI5: (apb2enr, apb2rstr, spi5en, spi5rst, "SPI5"),
+    Spi6, SPI6: (apb4enr, apb4rstr, spi6en, spi6rst, "SPI6"),
+}
@@ -618,7 +618,7 @@ macro_rules! usart {
                         // possible through the svd2rust API
                         unsafe {
                             ptr::write_volatile(
-                                &(*$USARTX::ptr()).tdr as *const _ as *mut _, byte)
+                                ptr::addr_of!((*$USARTX::ptr()).tdr) as *mut _, byte)
                         }
                         Ok(())
                     } else {
@@ -1,36 +1,97 @@
 //! Serial Peripheral Interface (SPI) bus
+//!
+//! # Streaming
+//!
+//! A master transfer can be continued without a gap using the TSER
+//! reload mechanism. Each time the current TSIZE count runs out,
+//! the count in TSER is loaded into TSIZE and `Event::TserfReload`
+//! occurs. The next count can then be written with `reload`. When no
+//! count is pending, the transaction ends (EOT) after the current
+//! one.
+//!
+//! ```rust
+//! spi.start_streaming(256);
+//! spi.listen(spi::Event::TserfReload);
+//! nb::block!(spi.reload(256))?; // Second block
+//!
+//! // In the interrupt handler, once the second block has started
+//! if spi.is_tserf() {
+//!     if more_data {
+//!         nb::block!(spi.reload(256))?;
+//!     } else {
+//!         spi.clear_tserf();
+//!     }
+//! }
+//!
+//! // Once the last block has been fed
+//! spi.end_streaming();
+//! ```
+//!
+//! The data itself is written to the transmit FIFO as usual, for
+//! example by DMA.
 
 use crate::hal;
+use crate::hal::blocking::spi::Operation;
+use crate::hal::spi::FullDuplex;
 pub use crate::hal::spi::{
     Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3,
 };
-use crate::stm32::rcc::{d2ccip1r, d3ccipr};
+use crate::stm32::rcc::{self, d2ccip1r, d3ccipr};
 use crate::stm32::spi1::cfg1::MBR_A as MBR;
+use crate::stm32::spi1::cfg2;
+use crate::stm32::spi1;
+use core::any;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{BitAnd, BitOr, Deref};
 use core::ptr;
+use core::sync::atomic::{self, Ordering};
 use nb;
 use stm32h7::Variant::Val;
 
+use crate::stm32::{Interrupt, RCC};
+use crate::stm32::{dma1, DMA1, DMA2, DMAMUX1};
 use crate::stm32::{SPI1, SPI2, SPI3, SPI4, SPI5, SPI6};
 
-use crate::gpio::gpioa::{PA12, PA5, PA6, PA7, PA9};
-use crate::gpio::gpiob::{PB10, PB13, PB14, PB15, PB2, PB3, PB4, PB5};
+use crate::gpio::gpioa::{PA11, PA12, PA15, PA4, PA5, PA6, PA7, PA9};
+use crate::gpio::gpiob::{
+    PB10, PB12, PB13, PB14, PB15, PB2, PB3, PB4, PB5, PB9,
+};
 use crate::gpio::gpioc::{PC1, PC10, PC11, PC12, PC2, PC3};
 use crate::gpio::gpiod::{PD3, PD6, PD7};
-use crate::gpio::gpioe::{PE12, PE13, PE14, PE2, PE5, PE6};
-use crate::gpio::gpiof::{PF11, PF7, PF8, PF9};
-use crate::gpio::gpiog::{PG11, PG12, PG13, PG14, PG9};
-use crate::gpio::gpioh::{PH6, PH7};
-use crate::gpio::gpioi::{PI1, PI2, PI3};
+use crate::gpio::gpioe::{PE11, PE12, PE13, PE14, PE2, PE4, PE5, PE6};
+#[cfg(not(feature = "lqfp100"))]
+use crate::gpio::gpiof::{PF11, PF6, PF7, PF8, PF9};
+#[cfg(not(feature = "lqfp100"))]
+use crate::gpio::gpiog::{PG10, PG11, PG12, PG13, PG14, PG8, PG9};
+#[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+use crate::gpio::gpioh::{PH5, PH6, PH7};
+#[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+use crate::gpio::gpioi::{PI0, PI1, PI2, PI3};
+#[cfg(not(any(
+    feature = "lqfp100",
+    feature = "lqfp144",
+    feature = "lqfp176"
+)))]
 use crate::gpio::gpioj::{PJ10, PJ11};
-use crate::gpio::gpiok::PK0;
+#[cfg(not(any(
+    feature = "lqfp100",
+    feature = "lqfp144",
+    feature = "lqfp176"
+)))]
+use crate::gpio::gpiok::{PK0, PK1};
 
-use crate::gpio::{Alternate, AF5, AF6, AF7, AF8};
+use crate::gpio::{self, Alternate, ErasedPin, Speed, AF5, AF6, AF7, AF8};
 
-use crate::rcc::Ccdr;
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::{CoreClocks, AHB1};
 use crate::time::Hertz;
 
 /// SPI error
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Overrun occurred
     Overrun,
@@ -38,14 +99,991 @@ pub enum Error {
     ModeFault,
     /// CRC error
     Crc,
-    #[doc(hidden)]
-    _Extensible,
+    /// The hardware managed chip select did not toggle
+    ChipSelect,
+    /// Driving a chip select pin managed in software failed
+    CsPin,
+    /// Timed out waiting for the slave
+    Timeout,
+    /// The peripheral was disabled unexpectedly, for example by a
+    /// mode fault
+    Disabled,
+    /// Underrun occurred: in slave mode, the master started a frame
+    /// while the transmit FIFO was empty
+    Underrun,
+    /// A transfer is in progress
+    Busy,
+    /// The kernel clock selected for this instance is not running
+    KernelClockNotRunning,
+    /// A DMA stream reported a transfer error, for example because
+    /// the buffer is in memory that is not accessible to the DMA
+    /// controller
+    Dma,
+    /// The configuration is not supported by this instance
+    Config(ConfigError),
+}
+
+/// SPI configuration error
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The frame size is not supported by this instance. SPI1, SPI2
+    /// and SPI3 support 4 to 32 bits, SPI4, SPI5 and SPI6 support 4 to
+    /// 16 bits
+    FrameSize,
+    /// The requested frequency is zero
+    ZeroFrequency,
+    /// The requested frequency is higher than the kernel clock
+    FrequencyTooHigh,
+    /// The I2S_CKIN frequency was declared as zero
+    ZeroI2sCkin,
+    /// The TI frame format requires NSS to be managed by hardware,
+    /// see `Config::manage_cs`, and MSB first bit order
+    TiFrameFormat,
+    /// `CsMode::PulsedBetweenWords` requires a non-zero
+    /// `Config::inter_word_delay`
+    PulsedCs,
+    /// The CRC size is not supported by this instance. The range is
+    /// the same as for the frame size
+    CrcSize,
+    /// The FIFO threshold is zero, or a packet of this many frames does
+    /// not fit in the FIFO of this instance. SPI1, SPI2 and SPI3 have
+    /// a 16 byte FIFO, SPI4, SPI5 and SPI6 have an 8 byte FIFO
+    FifoThreshold,
+    /// The operation is not supported in the current communication
+    /// mode
+    CommunicationMode,
+    /// The operation requires a chip select managed by the SPI
+    /// peripheral, see `Config::manage_cs`
+    ManagedCs,
+    /// A transfer size (TSIZE) is programmed, but no transaction was
+    /// started to use it. `FullDuplex` requires TSIZE to be zero
+    TransferSize,
+    /// The selected kernel clock is stopped in STOP mode, see
+    /// `Config::low_power_compatible`
+    LowPowerKernelClock,
+    /// A pin passed to `Config::strict_pins` cannot be used for its
+    /// role on this instance, or is not in alternate function mode
+    /// with the alternate function listed in `PinTable`
+    Pins,
+    /// The frame size configured in hardware is not held by the word
+    /// type of the `Spi`, see `Spi::change_word_size`
+    WordSize,
+}
+
+/// The communication mode of the SPI peripheral
+///
+/// The mode selects which of the data lines are used, and in which
+/// direction. It does not select the number of bits transferred per
+/// clock: this SPI peripheral always transfers one bit per clock on
+/// a single data line in each direction. Dual, quad and octal modes
+/// are not supported, so there is no mode that selects them:
+///
+/// ```compile_fail
+/// use stm32h7xx_hal::spi::CommunicationMode;
+///
+/// let mode = CommunicationMode::Quad;
+/// ```
+///
+/// For flash memories and other devices with multiple data lines,
+/// use the QUADSPI peripheral (or OCTOSPI on parts that have it)
+/// instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CommunicationMode {
+    /// Data is sent on MOSI and received on MISO at the same time
+    FullDuplex,
+    /// Data is only sent, on MOSI
+    Transmitter,
+    /// Data is only received, on MISO
+    Receiver,
+    /// Data is sent or received on a single bidirectional data line
+    HalfDuplex,
+}
+
+/// Write the COMM field of CFG2
+fn comm(w: &mut cfg2::W, mode: CommunicationMode) -> &mut cfg2::W {
+    match mode {
+        CommunicationMode::FullDuplex => w.comm().full_duplex(),
+        CommunicationMode::Transmitter => w.comm().transmitter(),
+        CommunicationMode::Receiver => w.comm().receiver(),
+        CommunicationMode::HalfDuplex => w.comm().half_duplex(),
+    }
+}
+
+/// Master baud rate divider, the ratio of the kernel clock to SCK.
+/// See `Config::baud_divider`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Divider {
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+}
+
+impl From<Divider> for MBR {
+    fn from(divider: Divider) -> MBR {
+        match divider {
+            Divider::Div2 => MBR::DIV2,
+            Divider::Div4 => MBR::DIV4,
+            Divider::Div8 => MBR::DIV8,
+            Divider::Div16 => MBR::DIV16,
+            Divider::Div32 => MBR::DIV32,
+            Divider::Div64 => MBR::DIV64,
+            Divider::Div128 => MBR::DIV128,
+            Divider::Div256 => MBR::DIV256,
+        }
+    }
+}
+
+impl From<MBR> for Divider {
+    fn from(mbr: MBR) -> Divider {
+        match mbr {
+            MBR::DIV2 => Divider::Div2,
+            MBR::DIV4 => Divider::Div4,
+            MBR::DIV8 => Divider::Div8,
+            MBR::DIV16 => Divider::Div16,
+            MBR::DIV32 => Divider::Div32,
+            MBR::DIV64 => Divider::Div64,
+            MBR::DIV128 => Divider::Div128,
+            MBR::DIV256 => Divider::Div256,
+        }
+    }
+}
+
+/// The frame format of the SPI bus
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Motorola SPI format
+    Motorola,
+    /// TI synchronous serial frame format. The clock polarity and
+    /// phase are set by the protocol, and NSS is driven by hardware
+    /// as a frame pulse
+    TI,
+}
+
+/// Hardware CRC calculation. See `Config::hardware_crc`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CrcConfig {
+    /// The CRC polynomial, without its highest order term. For
+    /// example 0x1021 for CRC16-CCITT
+    pub polynomial: u32,
+    /// The CRC size in bits
+    pub size_bits: u8,
+}
+
+/// The active level of a chip select managed by the SPI peripheral
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsPolarity {
+    /// CS is driven low when active
+    ActiveLow,
+    /// CS is driven high when active
+    ActiveHigh,
+}
+
+/// When a chip select managed by the SPI peripheral is asserted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsMode {
+    /// CS is held active for the whole transaction
+    Continuous,
+    /// CS is deasserted between frames, during the inter-word delay
+    PulsedBetweenWords,
+}
+
+/// The order in which the bits of each frame are transmitted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Most significant bit first
+    MsbFirst,
+    /// Least significant bit first
+    LsbFirst,
+}
+
+/// The edge of SCK on which data is sampled. See `Spi::sampling_edge`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SamplingEdge {
+    /// Data is sampled on the rising edge of SCK
+    Rising,
+    /// Data is sampled on the falling edge of SCK
+    Falling,
+}
+
+/// Read the COMM field of CFG2
+fn read_comm(r: &cfg2::R) -> CommunicationMode {
+    match r.comm().bits() {
+        0 => CommunicationMode::FullDuplex,
+        1 => CommunicationMode::Transmitter,
+        2 => CommunicationMode::Receiver,
+        _ => CommunicationMode::HalfDuplex,
+    }
+}
+
+/// Data transmitted by a slave on underrun. See `Config::underrun`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnderrunData {
+    /// Transmit a constant pattern, stored in UDRDR
+    Pattern(u32),
+    /// Repeat the last frame received from the master
+    LastReceived,
+    /// Repeat the last frame transmitted
+    LastTransmitted,
+}
+
+/// When a slave detects an underrun. See `Config::underrun`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnderrunDetection {
+    /// At the beginning of a frame
+    BeginningOfFrame,
+    /// At the end of the last frame
+    EndOfFrame,
+    /// At the beginning of the active NSS signal
+    BeginningOfNss,
+}
+
+/// A structure for specifying SPI configuration.
+///
+/// This structure uses builder semantics to generate the configuration.
+///
+/// ```rust
+/// use embedded_hal::spi::MODE_0;
+///
+/// let config = Config::new(MODE_0).invert_mosi();
+/// ```
+#[derive(Copy, Clone)]
+pub struct Config {
+    pub mode: Mode,
+    pub invert_mosi: bool,
+    pub invert_miso: bool,
+    pub managed_cs: bool,
+    pub communication_mode: CommunicationMode,
+    pub low_power: bool,
+    pub cs_delay_ns: u32,
+    pub cs_hold_ns: u32,
+    pub mosi_idle_high: Option<bool>,
+    pub frame_size: u8,
+    pub slave: bool,
+    pub underrun_data: UnderrunData,
+    pub underrun_detection: UnderrunDetection,
+    pub i2s_ckin: Option<Hertz>,
+    pub bit_order: BitOrder,
+    pub frame_format: FrameFormat,
+    pub inter_word_delay_ns: u32,
+    pub cs_polarity: CsPolarity,
+    pub cs_mode: CsMode,
+    pub crc: Option<CrcConfig>,
+    pub fifo_threshold: u8,
+    pub keep_io_state: bool,
+    pub divider: Option<Divider>,
+    /// Swap the functions of the MOSI and MISO pins (IOSWP). See
+    /// `swap_io`
+    pub swap_mosi_miso: bool,
+    pub master_rx_auto_suspend: bool,
+    pub pin_speed: Option<Speed>,
+    pub nss_input: bool,
+    /// Port and pin number of the SCK, MISO, MOSI and NSS pins to
+    /// check at construction. See `strict_pins`
+    pub strict_pins: Option<[Option<(u8, u8)>; 4]>,
+}
+
+// embedded-hal's `Mode` does not implement `Debug`
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let polarity = match self.mode.polarity {
+            Polarity::IdleLow => "IdleLow",
+            Polarity::IdleHigh => "IdleHigh",
+        };
+        let phase = match self.mode.phase {
+            Phase::CaptureOnFirstTransition => "CaptureOnFirstTransition",
+            Phase::CaptureOnSecondTransition => "CaptureOnSecondTransition",
+        };
+
+        f.debug_struct("Config")
+            .field("polarity", &format_args!("{}", polarity))
+            .field("phase", &format_args!("{}", phase))
+            .field("invert_mosi", &self.invert_mosi)
+            .field("invert_miso", &self.invert_miso)
+            .field("managed_cs", &self.managed_cs)
+            .field("communication_mode", &self.communication_mode)
+            .field("low_power", &self.low_power)
+            .field("cs_delay_ns", &self.cs_delay_ns)
+            .field("cs_hold_ns", &self.cs_hold_ns)
+            .field("mosi_idle_high", &self.mosi_idle_high)
+            .field("frame_size", &self.frame_size)
+            .field("slave", &self.slave)
+            .field("underrun_data", &self.underrun_data)
+            .field("underrun_detection", &self.underrun_detection)
+            .field("i2s_ckin", &self.i2s_ckin)
+            .field("bit_order", &self.bit_order)
+            .field("frame_format", &self.frame_format)
+            .field("inter_word_delay_ns", &self.inter_word_delay_ns)
+            .field("cs_polarity", &self.cs_polarity)
+            .field("cs_mode", &self.cs_mode)
+            .field("crc", &self.crc)
+            .field("fifo_threshold", &self.fifo_threshold)
+            .field("keep_io_state", &self.keep_io_state)
+            .field("divider", &self.divider)
+            .field("swap_mosi_miso", &self.swap_mosi_miso)
+            .field("master_rx_auto_suspend", &self.master_rx_auto_suspend)
+            .field("pin_speed", &self.pin_speed)
+            .field("nss_input", &self.nss_input)
+            .field("strict_pins", &self.strict_pins)
+            .finish()
+    }
+}
+
+impl Config {
+    /// Create a default configuration for the SPI interface.
+    ///
+    /// Arguments:
+    /// * `mode` - The SPI mode to configure.
+    pub fn new(mode: Mode) -> Self {
+        Config {
+            mode,
+            invert_mosi: false,
+            invert_miso: false,
+            managed_cs: false,
+            communication_mode: CommunicationMode::FullDuplex,
+            low_power: false,
+            cs_delay_ns: 0,
+            cs_hold_ns: 0,
+            mosi_idle_high: None,
+            frame_size: 8,
+            slave: false,
+            underrun_data: UnderrunData::Pattern(0),
+            underrun_detection: UnderrunDetection::BeginningOfFrame,
+            i2s_ckin: None,
+            bit_order: BitOrder::MsbFirst,
+            frame_format: FrameFormat::Motorola,
+            inter_word_delay_ns: 0,
+            cs_polarity: CsPolarity::ActiveLow,
+            cs_mode: CsMode::Continuous,
+            crc: None,
+            fifo_threshold: 1,
+            keep_io_state: false,
+            divider: None,
+            swap_mosi_miso: false,
+            master_rx_auto_suspend: false,
+            pin_speed: None,
+            nss_input: false,
+            strict_pins: None,
+        }
+    }
+
+    /// Require a kernel clock source that survives STOP mode.
+    ///
+    /// The PLLs are stopped in STOP mode, and are not restarted on
+    /// wakeup. An SPI with a PLL derived kernel clock is therefore
+    /// unusable after wakeup until the PLL is restarted by software.
+    /// HSI and CSI are restarted automatically on wakeup.
+    ///
+    /// The kernel clock is selected with `ccdr.peripheral_clocks`
+    /// before constructing the SPI. When set, the SPI constructor
+    /// checks that one of the following kernel clock sources is
+    /// selected:
+    ///
+    /// * SPI1, SPI2, SPI3: `per_ck`, which must itself be derived
+    ///   from HSI or CSI
+    /// * SPI4, SPI5, SPI6: `hsi_ker_ck` or `csi_ker_ck`
+    ///
+    /// ```rust
+    /// let mut ccdr = rcc.per_ck(4.mhz()).freeze(vos, &dp.SYSCFG); // CSI
+    /// ccdr.peripheral_clocks.spi123_sel(Spi123ClkSel::Per).unwrap();
+    ///
+    /// let config = spi::Config::new(spi::MODE_0).low_power_compatible();
+    /// let spi = dp.SPI1.spi(
+    ///     pins,
+    ///     config,
+    ///     1.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// The SPI constructor panics if another kernel clock source is
+    /// selected. `try_spi` returns `ConfigError::LowPowerKernelClock`
+    /// instead.
+    pub fn low_power_compatible(mut self) -> Self {
+        self.low_power = true;
+        self
+    }
+
+    /// Set the frequency of the external clock on the I2S_CKIN
+    /// pin. This is required when I2S_CKIN is selected as the kernel
+    /// clock, which is only possible for SPI1, SPI2 and SPI3. The
+    /// master baud rate divider is then calculated from this
+    /// frequency.
+    ///
+    /// ```rust
+    /// // 12.288 MHz audio master clock on I2S_CKIN
+    /// let config = spi::Config::new(spi::MODE_0).i2s_ckin(12_288.khz());
+    /// let spi = dp.SPI1.spi(
+    ///     pins,
+    ///     config,
+    ///     3.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// ```
+    ///
+    /// Construction fails with `ConfigError::ZeroI2sCkin` if `freq`
+    /// is zero, and with `Error::KernelClockNotRunning` if I2S_CKIN
+    /// is selected but its frequency was not set.
+    pub fn i2s_ckin<T: Into<Hertz>>(mut self, freq: T) -> Self {
+        self.i2s_ckin = Some(freq.into());
+        self
+    }
+
+    /// Set the frame size in bits. The default is 8 bits.
+    ///
+    /// The word type used with `FullDuplex` and the blocking traits
+    /// must match the frame size:
+    ///
+    /// * 4 to 8 bits: `u8`
+    /// * 9 to 16 bits: `u16`
+    /// * 17 to 32 bits: `u32`, on SPI1, SPI2 and SPI3 only
+    ///
+    /// The word type is not selected by the frame size: the SPI is
+    /// always constructed with `u8` words. For larger frames, convert
+    /// it with `Spi::change_word_size`. Using `u8` words with larger
+    /// frames is only caught by a debug assertion.
+    ///
+    /// Frames of 4 to 7 bits are right aligned in each `u8`, one frame
+    /// per byte: the FIFOs are accessed a byte at a time, and do not
+    /// pack two frames into one byte. The high bits are ignored when
+    /// sending and are zero when receiving. `write_packed` and
+    /// `read_packed` only access the FIFOs 32 bits at a time with 8-bit
+    /// frames.
+    ///
+    /// # Panics
+    ///
+    /// The SPI constructor panics if the frame size is not supported
+    /// by the SPI instance. See `validate`.
+    pub fn frame_size(mut self, bits: u8) -> Self {
+        self.frame_size = bits;
+        self
+    }
+
+    /// Configure the SPI as a slave. The master drives SCK, and
+    /// `send` does not start a transaction.
+    ///
+    /// The slave is always selected, unless it is constructed with
+    /// `spi_slave` and an NSS pin, in which case it is selected by
+    /// the NSS input.
+    pub fn slave(mut self) -> Self {
+        self.slave = true;
+        self
+    }
+
+    /// Select what a slave transmits when the master starts a frame
+    /// while the transmit FIFO is empty, and when this underrun is
+    /// detected. The default is to transmit zero, detected at the
+    /// beginning of a frame. Only applies in slave mode.
+    pub fn underrun(
+        mut self,
+        data: UnderrunData,
+        detection: UnderrunDetection,
+    ) -> Self {
+        self.underrun_data = data;
+        self.underrun_detection = detection;
+        self
+    }
+
+    /// Select the order in which the bits of each frame are
+    /// transmitted. The default is `BitOrder::MsbFirst`.
+    ///
+    /// ```rust
+    /// let config = spi::Config::new(spi::MODE_0)
+    ///     .bit_order(spi::BitOrder::LsbFirst);
+    /// ```
+    pub fn bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    /// Select the frame format. The default is
+    /// `FrameFormat::Motorola`.
+    ///
+    /// In TI mode the clock polarity and phase are forced by the
+    /// hardware, so `mode` is ignored. NSS is always driven by
+    /// hardware, so `manage_cs` is required, along with the NSS pin.
+    /// Only MSB first bit order is supported.
+    ///
+    /// ```rust
+    /// let config = spi::Config::new(spi::MODE_0)
+    ///     .frame_format(spi::FrameFormat::TI)
+    ///     .manage_cs()
+    ///     .frame_size(16);
+    /// let pins = (sck, miso, mosi, nss);
+    /// let mut spi = dp.SPI1.spi(
+    ///     pins,
+    ///     config,
+    ///     1.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// spi.transfer(&mut [0x1234u16, 0x5678])?;
+    /// ```
+    pub fn frame_format(mut self, format: FrameFormat) -> Self {
+        self.frame_format = format;
+        self
+    }
+
+    /// Select the communication mode of the SPI bus. The default is
+    /// `CommunicationMode::FullDuplex`.
+    pub fn communication_mode(mut self, mode: CommunicationMode) -> Self {
+        self.communication_mode = mode;
+        self
+    }
+
+    /// CS pin is automatically managed by the SPI peripheral.
+    ///
+    /// The NSS pin must be passed to the SPI constructor as the
+    /// fourth pin, `(sck, miso, mosi, nss)`, so that its alternate
+    /// function is checked. It is driven active (low) by the
+    /// peripheral during transactions.
+    ///
+    /// # Panics
+    ///
+    /// The SPI constructor panics if no NSS pin is passed.
+    pub fn manage_cs(mut self) -> Self {
+        self.managed_cs = true;
+        self
+    }
+
+    /// Sense the NSS pin as an input in master mode, for a bus with
+    /// multiple masters. The default is software slave management
+    /// (SSM), where the NSS pin is not used.
+    ///
+    /// When another master asserts NSS, the peripheral flags a mode
+    /// fault: it is disabled and switches to slave mode, releasing the
+    /// bus. Transfers then return `Error::ModeFault`, and
+    /// `Event::ModeFault` can be listened for. Once the other master
+    /// has released NSS, call `Spi::rearm_after_mode_fault` to retry.
+    ///
+    /// The NSS pin must be passed to the SPI constructor as the fourth
+    /// pin, `(sck, miso, mosi, nss)`. Takes precedence over
+    /// `manage_cs`. The active level is set by `cs_polarity`.
+    ///
+    /// # Panics
+    ///
+    /// The SPI constructor panics if no NSS pin is passed.
+    pub fn nss_input_mode(mut self) -> Self {
+        self.nss_input = true;
+        self
+    }
+
+    /// Minimum time in seconds between the assertion of CS and the
+    /// first clock of a transaction. The default is zero.
+    ///
+    /// When CS is managed by the SPI peripheral, see `manage_cs`,
+    /// this is converted to cycles of the actual SCK, rounding up,
+    /// and limited to 15 cycles (MSSI). See `Spi::cs_delay_cycles`.
+    /// For a software managed CS, with `transfer_cs` or `with_cs`,
+    /// it is a software delay of at least this time.
+    pub fn cs_delay(mut self, seconds: f32) -> Self {
+        self.cs_delay_ns = seconds_to_ns(seconds);
+        self
+    }
+
+    /// As `cs_delay`, in nanoseconds. This avoids floating point
+    /// arithmetic entirely.
+    pub fn cs_delay_ns(mut self, ns: u32) -> Self {
+        self.cs_delay_ns = ns;
+        self
+    }
+
+    /// Minimum idle time in seconds between consecutive frames
+    /// (MIDI). The default is zero.
+    ///
+    /// This is converted to cycles of the actual SCK, rounding up,
+    /// and limited to 15 cycles. See `Spi::inter_word_delay_cycles`.
+    pub fn inter_word_delay(mut self, seconds: f32) -> Self {
+        self.inter_word_delay_ns = seconds_to_ns(seconds);
+        self
+    }
+
+    /// As `inter_word_delay`, in nanoseconds. This avoids floating
+    /// point arithmetic entirely.
+    pub fn inter_word_delay_ns(mut self, ns: u32) -> Self {
+        self.inter_word_delay_ns = ns;
+        self
+    }
+
+    /// Set the FIFO threshold (FTHLV) in frames. The default is 1. TXP
+    /// and RXP are only set once a whole packet of this many frames can
+    /// be written or read.
+    ///
+    /// The packet must fit in the FIFO: SPI1, SPI2 and SPI3 have a 16
+    /// byte FIFO, SPI4, SPI5 and SPI6 have an 8 byte FIFO. Frames of up
+    /// to 8 bits take one byte, up to 16 bits two bytes, and larger
+    /// frames four bytes. See `ConfigError::FifoThreshold`.
+    ///
+    /// With 8-bit frames and a threshold of 4, 8 or 16, `write_packed`
+    /// and `read_packed` access the FIFOs 32 bits at a time, and DMA
+    /// transfers use peripheral bursts of the same size.
+    pub fn fifo_threshold(mut self, words: u8) -> Self {
+        self.fifo_threshold = words;
+        self
+    }
+
+    /// Enable hardware CRC calculation. The default is disabled.
+    ///
+    /// The CRC is calculated over the frames of a transaction with a
+    /// fixed size, see `prepare_transaction`. The transmitted CRC is
+    /// appended after the last frame, and the received CRC is
+    /// checked against it, setting `Error::Crc` on a mismatch. Both
+    /// CRCs are initialised to zero.
+    ///
+    /// ```rust
+    /// // CRC16-CCITT
+    /// let config = spi::Config::new(spi::MODE_0).hardware_crc(
+    ///     spi::CrcConfig {
+    ///         polynomial: 0x1021,
+    ///         size_bits: 16,
+    ///     },
+    /// );
+    /// ```
+    pub fn hardware_crc(mut self, crc: CrcConfig) -> Self {
+        self.crc = Some(crc);
+        self
+    }
+
+    /// Set the active level of CS. The default is
+    /// `CsPolarity::ActiveLow`. Only applies when CS is managed by
+    /// the SPI peripheral, see `manage_cs`.
+    pub fn cs_polarity(mut self, polarity: CsPolarity) -> Self {
+        self.cs_polarity = polarity;
+        self
+    }
+
+    /// Select whether CS is held active for the whole transaction,
+    /// or pulsed between frames. The default is
+    /// `CsMode::Continuous`. Only applies when CS is managed by the
+    /// SPI peripheral, see `manage_cs`.
+    ///
+    /// `CsMode::PulsedBetweenWords` requires a non-zero
+    /// `inter_word_delay`, otherwise construction fails with
+    /// `ConfigError::PulsedCs`.
+    pub fn cs_assertion(mut self, mode: CsMode) -> Self {
+        self.cs_mode = mode;
+        self
+    }
+
+    /// Minimum time in seconds between the end of the last clock of
+    /// a transaction and the deassertion of CS. The default is
+    /// zero. Only applies to transactions ended with
+    /// `end_transaction` when CS is managed by the SPI peripheral, or
+    /// to transactions run with `transfer_cs` or `with_cs`.
+    ///
+    /// Note: The SPI peripheral has no hardware setting for the CS
+    /// hold time, so this is always a software delay. The actual hold
+    /// time is at least `seconds`, plus the latency of the driver.
+    pub fn cs_hold(mut self, seconds: f32) -> Self {
+        self.cs_hold_ns = seconds_to_ns(seconds);
+        self
+    }
+
+    /// As `cs_hold`, in nanoseconds. This avoids floating point
+    /// arithmetic entirely.
+    pub fn cs_hold_ns(mut self, ns: u32) -> Self {
+        self.cs_hold_ns = ns;
+        self
+    }
+
+    /// Define the levels of SCK and MOSI when the bus is idle.
+    ///
+    /// The idle level of SCK is set by the clock polarity, so this
+    /// overrides the polarity of `mode`. The peripheral has no
+    /// setting for the idle level of MOSI, so the SPI constructor
+    /// enables the internal pull-up (`mosi_high`) or pull-down
+    /// resistor on the MOSI pin passed to it instead. The pull is
+    /// not changed by `spi_unchecked` or `Spi::reconfigure`.
+    ///
+    /// This also enables `keep_io_state`.
+    pub fn idle_levels(mut self, sck_high: bool, mosi_high: bool) -> Self {
+        self.mode.polarity = if sck_high {
+            Polarity::IdleHigh
+        } else {
+            Polarity::IdleLow
+        };
+        self.mosi_idle_high = Some(mosi_high);
+        self.keep_io_state = true;
+        self
+    }
+
+    /// Use a fixed master baud rate divider, instead of calculating
+    /// it from the requested SCK frequency. The `freq` argument of
+    /// the SPI constructor is then ignored, and SCK is always this
+    /// ratio of the kernel clock, see `Spi::clock`.
+    ///
+    /// ```rust
+    /// let config = spi::Config::new(spi::MODE_0)
+    ///     .baud_divider(spi::Divider::Div32);
+    /// ```
+    pub fn baud_divider(mut self, divider: Divider) -> Self {
+        self.divider = Some(divider);
+        self
+    }
+
+    /// Keep control of the SPI pins while the peripheral is disabled
+    /// (AFCNTR). The default is off, and the pins then float while
+    /// disabled.
+    ///
+    /// The driver briefly disables the peripheral to change its
+    /// configuration, for example in `set_frequency`, `set_mode`,
+    /// `half_duplex_write` and `half_duplex_read`, and to deassert a
+    /// hardware managed CS. With this option SCK is held at the idle
+    /// level set by the clock polarity of `mode` throughout, so the
+    /// slave sees no spurious edge. When CS is managed by the
+    /// peripheral it is likewise held inactive.
+    pub fn keep_io_state(mut self) -> Self {
+        self.keep_io_state = true;
+        self
+    }
+
+    /// Suspend the master clock when the receive FIFO is full
+    /// (MASRX), rather than overrunning. The default is off.
+    ///
+    /// The transaction is resumed once the FIFO has been drained, so a
+    /// slow consumer loses no data. `read_frames` and
+    /// `RxTransfer::wait` resume it while waiting. A circular DMA
+    /// capture is only resumed by `CircularTransfer::poll` and
+    /// `CircularTransfer::readable_half`, and stalls until one of them
+    /// is called. The SCK clock then has gaps, which the slave must
+    /// tolerate. Has no effect in slave mode.
+    pub fn master_rx_auto_suspend(mut self) -> Self {
+        self.master_rx_auto_suspend = true;
+        self
+    }
+
+    /// Set the output speed (OSPEEDR) of the SCK, MISO and MOSI
+    /// pins passed to the SPI constructor. By default the speed set
+    /// when the pins were configured is kept, which is `Speed::Low`
+    /// unless changed. The speed is not changed by `spi_unchecked` or
+    /// `Spi::reconfigure`.
+    ///
+    /// Fast SCK frequencies need a higher speed for clean edges, at
+    /// the cost of more electromagnetic emissions.
+    ///
+    /// ```rust
+    /// // 50 MHz SCK
+    /// let config = spi::Config::new(spi::MODE_0).pin_speed(Speed::VeryHigh);
+    /// let spi = dp.SPI1.spi(
+    ///     pins,
+    ///     config,
+    ///     50.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// ```
+    pub fn pin_speed(mut self, speed: Speed) -> Self {
+        self.pin_speed = Some(speed);
+        self
+    }
+
+    /// Check at construction that `pins` are configured for the SPI
+    /// instance. Intended for use with `spi_unchecked`, where the pins
+    /// are not checked at compile time.
+    ///
+    /// In debug builds the constructor reads the mode and alternate
+    /// function registers of each pin, and fails with
+    /// `ConfigError::Pins` unless every pin is listed for its role in
+    /// `PinTable`, and is in alternate function mode with the
+    /// alternate function listed there. Has no effect in release
+    /// builds.
+    ///
+    /// ```rust
+    /// let pins = (
+    ///     gpioa.pa5.into_alternate_af5().erase(),
+    ///     spi::NoMiso,
+    ///     gpioa.pa7.into_alternate_af5().erase(),
+    /// );
+    /// let config = spi::Config::new(spi::MODE_0).strict_pins(&pins);
+    /// let spi = dp.SPI1.spi_unchecked(
+    ///     pins,
+    ///     config,
+    ///     1.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// ```
+    pub fn strict_pins<P: PinLocations>(mut self, pins: &P) -> Self {
+        self.strict_pins = Some(pins.locations());
+        self
+    }
+
+    /// Swap the functions of the MOSI and MISO pins (IOSWP), for boards
+    /// where they are crossed. The pins are still passed to the
+    /// constructor as `(sck, miso, mosi)`, and must be valid for those
+    /// roles. In master mode the MISO pin then drives the data out, and
+    /// the MOSI pin receives.
+    ///
+    /// Applies to the idle level set by `mosi_idle_high` too, which is
+    /// then set on the MISO pin.
+    pub fn swap_io(mut self) -> Self {
+        self.swap_mosi_miso = true;
+        self
+    }
+
+    /// Complement each word before it is written to the transmit
+    /// FIFO. For use with an inverting level shifter on MOSI.
+    ///
+    /// Note: This is a software transform applied by the driver,
+    /// the SPI peripheral has no data inversion feature. It is not
+    /// applied to data moved by other means, for example DMA.
+    pub fn invert_mosi(mut self) -> Self {
+        self.invert_mosi = true;
+        self
+    }
+
+    /// Complement each word after it is read from the receive
+    /// FIFO. For use with an inverting level shifter on MISO.
+    ///
+    /// Note: This is a software transform applied by the driver,
+    /// the SPI peripheral has no data inversion feature. It is not
+    /// applied to data moved by other means, for example DMA.
+    pub fn invert_miso(mut self) -> Self {
+        self.invert_miso = true;
+        self
+    }
+}
+
+/// Maximum SCK frequency permitted during SD card initialisation
+pub const SD_CARD_INIT_FREQ: Hertz = Hertz(400_000);
+
+impl Config {
+    /// Configuration for initialising an SD card in SPI mode.
+    ///
+    /// SD cards start in SD mode, and must be switched to SPI mode
+    /// with 8-bit frames in `MODE_0` at no more than 400kHz
+    /// (`SD_CARD_INIT_FREQ`). Once the card is initialised, the bus
+    /// can be rebuilt at full speed.
+    ///
+    /// ```rust
+    /// let mut spi = dp.SPI1.spi(
+    ///     pins,
+    ///     Config::sd_card_init(),
+    ///     spi::SD_CARD_INIT_FREQ,
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// sd_cs.set_high()?; // CS must be deasserted
+    /// spi.sd_card_power_up()?;
+    /// ```
+    pub fn sd_card_init() -> Self {
+        Config::new(MODE_0)
+    }
+}
+
+impl Config {
+    /// Check that this configuration is supported by the SPI instance
+    /// `SPI`. The SPI constructors panic if it is not.
+    pub fn validate<SPI: Instance>(&self) -> Result<(), ConfigError> {
+        if !(4..=SPI::MAX_FRAME_SIZE).contains(&self.frame_size) {
+            return Err(ConfigError::FrameSize);
+        }
+        if let Some(Hertz(0)) = self.i2s_ckin {
+            return Err(ConfigError::ZeroI2sCkin);
+        }
+        if self.frame_format == FrameFormat::TI
+            && (!self.managed_cs || self.bit_order != BitOrder::MsbFirst)
+        {
+            return Err(ConfigError::TiFrameFormat);
+        }
+        if self.cs_mode == CsMode::PulsedBetweenWords
+            && self.inter_word_delay_ns == 0
+        {
+            return Err(ConfigError::PulsedCs);
+        }
+        if let Some(crc) = self.crc {
+            if !(4..=SPI::MAX_FRAME_SIZE).contains(&crc.size_bits) {
+                return Err(ConfigError::CrcSize);
+            }
+        }
+        // A packet of frames must fit in the FIFO. Frames are stored
+        // in 1, 2 or 4 bytes, depending on the frame size
+        let frame_bytes = match self.frame_size {
+            0..=8 => 1,
+            9..=16 => 2,
+            _ => 4,
+        };
+        let max_threshold = SPI::FIFO_SIZE / frame_bytes;
+        if self.fifo_threshold == 0
+            || usize::from(self.fifo_threshold) > max_threshold
+        {
+            return Err(ConfigError::FifoThreshold);
+        }
+        if self.low_power && !SPI::kernel_clk_survives_stop() {
+            return Err(ConfigError::LowPowerKernelClock);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Mode> for Config {
+    fn from(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
+pub trait Pins<SPI> {
+    /// `true` if the pins include an NSS pin, which is required for
+    /// hardware managed CS
+    const NSS: bool = false;
+
+    /// Enable the internal pull-up (`high`) or pull-down resistor on
+    /// the pin that drives data out: MOSI, or MISO if `swapped`. See
+    /// `Config::idle_levels`
+    fn set_idle_pull(&mut self, _swapped: bool, _high: bool) {}
+
+    /// Set the output speed of the SCK, MISO and MOSI pins. See
+    /// `Config::pin_speed`
+    fn set_speed(self, _speed: Speed) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+pub trait PinSck<SPI> {
+    /// Set the output speed of the pin
+    fn set_speed(self, _speed: Speed) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+pub trait PinMiso<SPI> {
+    /// Enable the internal pull-up (`high`) or pull-down resistor
+    fn set_idle_pull(&mut self, _high: bool) {}
+
+    /// Set the output speed of the pin
+    fn set_speed(self, _speed: Speed) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
 }
+pub trait PinMosi<SPI> {
+    /// Enable the internal pull-up (`high`) or pull-down resistor
+    fn set_idle_pull(&mut self, _high: bool) {}
 
-pub trait Pins<SPI> {}
-pub trait PinSck<SPI> {}
-pub trait PinMiso<SPI> {}
-pub trait PinMosi<SPI> {}
+    /// Set the output speed of the pin
+    fn set_speed(self, _speed: Speed) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+pub trait PinNss<SPI> {}
 
 impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
 where
@@ -53,424 +1091,5585 @@ where
     MISO: PinMiso<SPI>,
     MOSI: PinMosi<SPI>,
 {
+    fn set_idle_pull(&mut self, swapped: bool, high: bool) {
+        if swapped {
+            self.1.set_idle_pull(high);
+        } else {
+            self.2.set_idle_pull(high);
+        }
+    }
+
+    fn set_speed(self, speed: Speed) -> Self {
+        let (sck, miso, mosi) = self;
+        (sck.set_speed(speed), miso.set_speed(speed), mosi.set_speed(speed))
+    }
+}
+
+impl<SPI, SCK, MISO, MOSI, NSS> Pins<SPI> for (SCK, MISO, MOSI, NSS)
+where
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+    NSS: PinNss<SPI>,
+{
+    const NSS: bool = true;
+
+    fn set_idle_pull(&mut self, swapped: bool, high: bool) {
+        if swapped {
+            self.1.set_idle_pull(high);
+        } else {
+            self.2.set_idle_pull(high);
+        }
+    }
+
+    fn set_speed(self, speed: Speed) -> Self {
+        let (sck, miso, mosi, nss) = self;
+        let (sck, miso, mosi) = (sck, miso, mosi).set_speed(speed);
+        (sck, miso, mosi, nss)
+    }
+}
+
+/// Apply the pin settings requested by `config` to `pins`
+fn configure_pins<SPI, PINS: Pins<SPI>>(mut pins: PINS, config: &Config) -> PINS {
+    if let Some(high) = config.mosi_idle_high {
+        pins.set_idle_pull(config.swap_mosi_miso, high);
+    }
+    if let Some(speed) = config.pin_speed {
+        pins = pins.set_speed(speed);
+    }
+    pins
+}
+
+/// Frame size in bits, lifted to the type level
+///
+/// Used to check at compile time that a frame size is valid for the
+/// data word that carries it. See `transfer_framed`.
+pub struct FrameSize<const BITS: u8>;
+
+/// Marker trait for frame sizes that can be carried in a `u16` data word
+pub trait HalfWordFrame {}
+
+macro_rules! halfword_frames {
+    ($($BITS:literal),+) => {
+        $(
+            impl HalfWordFrame for FrameSize<$BITS> {}
+        )+
+    }
 }
 
+// The minimum data size supported by the hardware is 4 bits
+halfword_frames!(4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16);
+
 /// A filler type for when the SCK pin is unnecessary
 pub struct NoSck;
 /// A filler type for when the Miso pin is unnecessary
+///
+/// This is also used for 3-wire half duplex wiring, where the MOSI
+/// pin carries data in both directions: `(sck, NoMiso, mosi)`. See
+/// `Spi::half_duplex_write`.
 pub struct NoMiso;
 /// A filler type for when the Mosi pin is unnecessary
 pub struct NoMosi;
 
+impl<SPI> PinSck<SPI> for NoSck {}
+impl<SPI> PinMiso<SPI> for NoMiso {}
+impl<SPI> PinMosi<SPI> for NoMosi {}
+
+/// Describes a pin that is valid for a particular role on an SPI
+/// instance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ValidPin {
+    /// Name of the pin, for example "PA5"
+    pub pin: &'static str,
+    /// Alternate function required, for example "AF5"
+    pub af: &'static str,
+}
+
+/// Table of the pins that are valid for each role on an SPI instance.
+///
+/// This contains the same information as the `PinSck`, `PinMiso`
+/// and `PinMosi` implementations, but as data that can be queried at
+/// runtime. For example, to print diagnostics when checking a board
+/// configuration.
+///
+/// ```rust
+/// let valid = SPI1::SCK.iter().any(|p| p.pin == "PA5" && p.af == "AF5");
+/// ```
+pub trait PinTable {
+    /// Valid SCK pins
+    const SCK: &'static [ValidPin];
+    /// Valid MISO pins
+    const MISO: &'static [ValidPin];
+    /// Valid MOSI pins
+    const MOSI: &'static [ValidPin];
+    /// Valid NSS pins
+    const NSS: &'static [ValidPin];
+}
+
+/// A pin whose port and pin number are known at runtime. See
+/// `Config::strict_pins`
+pub trait PinLocation {
+    /// Port index, where 0 is GPIOA, and pin number. `None` for the
+    /// filler types
+    fn location(&self) -> Option<(u8, u8)>;
+}
+
+impl<MODE> PinLocation for ErasedPin<MODE> {
+    fn location(&self) -> Option<(u8, u8)> {
+        Some((self.get_port(), self.get_id()))
+    }
+}
+impl PinLocation for NoSck {
+    fn location(&self) -> Option<(u8, u8)> {
+        None
+    }
+}
+impl PinLocation for NoMiso {
+    fn location(&self) -> Option<(u8, u8)> {
+        None
+    }
+}
+impl PinLocation for NoMosi {
+    fn location(&self) -> Option<(u8, u8)> {
+        None
+    }
+}
+
+/// A set of pins whose ports and pin numbers are known at runtime.
+/// See `Config::strict_pins`
+pub trait PinLocations {
+    /// Locations of the SCK, MISO, MOSI and NSS pins, see
+    /// `PinLocation`
+    fn locations(&self) -> [Option<(u8, u8)>; 4];
+}
+
+impl<SCK, MISO, MOSI> PinLocations for (SCK, MISO, MOSI)
+where
+    SCK: PinLocation,
+    MISO: PinLocation,
+    MOSI: PinLocation,
+{
+    fn locations(&self) -> [Option<(u8, u8)>; 4] {
+        [self.0.location(), self.1.location(), self.2.location(), None]
+    }
+}
+
+impl<SCK, MISO, MOSI, NSS> PinLocations for (SCK, MISO, MOSI, NSS)
+where
+    SCK: PinLocation,
+    MISO: PinLocation,
+    MOSI: PinLocation,
+    NSS: PinLocation,
+{
+    fn locations(&self) -> [Option<(u8, u8)>; 4] {
+        [
+            self.0.location(),
+            self.1.location(),
+            self.2.location(),
+            self.3.location(),
+        ]
+    }
+}
+
+/// Returns `true` if pin `id` of `port` is listed in `table`, and its
+/// mode (MODER) and alternate function (AFR) fields are `mode` and
+/// `af`. Alternate function mode is 0b10
+fn pin_af_valid(
+    table: &[ValidPin],
+    port: u8,
+    id: u8,
+    mode: u8,
+    af: u8,
+) -> bool {
+    table.iter().any(|valid| {
+        let name = valid.pin.as_bytes();
+        name[1] - b'A' == port
+            && valid.pin[2..].parse() == Ok(id)
+            && valid.af[2..].parse() == Ok(af)
+            && mode == 0b10
+    })
+}
+
+/// Returns the mode (MODER) and alternate function (AFR) fields of
+/// pin `id` of `port`
+fn pin_mode_af(port: u8, id: u8) -> (u8, u8) {
+    let gpio = gpio::port_block(port);
+    let mode = (gpio.moder.read().bits() >> (2 * id)) & 0b11;
+    let af = if id < 8 {
+        gpio.afrl.read().bits() >> (4 * id)
+    } else {
+        gpio.afrh.read().bits() >> (4 * (id - 8))
+    } & 0b1111;
+
+    (mode as u8, af as u8)
+}
+
+/// Returns the location of the first pin in `SPI::NSS` that is
+/// currently configured as the NSS pin of `SPI`
+fn nss_location<SPI: PinTable>() -> Option<(u8, u8)> {
+    SPI::NSS.iter().find_map(|valid| {
+        let port = valid.pin.as_bytes()[1] - b'A';
+        let id = valid.pin[2..].parse().ok()?;
+        let (mode, af) = pin_mode_af(port, id);
+
+        if pin_af_valid(SPI::NSS, port, id, mode, af) {
+            Some((port, id))
+        } else {
+            None
+        }
+    })
+}
+
+/// Check that the pins at `locations` are configured for the SPI
+/// instance `SPI`. See `Config::strict_pins`
+fn check_pins<SPI: PinTable>(
+    locations: &[Option<(u8, u8)>; 4],
+) -> Result<(), ConfigError> {
+    let tables = [SPI::SCK, SPI::MISO, SPI::MOSI, SPI::NSS];
+
+    for (table, location) in tables.iter().zip(locations.iter()) {
+        if let Some((port, id)) = *location {
+            let (mode, af) = pin_mode_af(port, id);
+
+            if !pin_af_valid(table, port, id, mode, af) {
+                return Err(ConfigError::Pins);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 macro_rules! pins {
-    ($($SPIX:ty: SCK: [$($SCK:ty),*] MISO: [$($MISO:ty),*] MOSI: [$($MOSI:ty),*])+) => {
+    ($($SPIX:ty:
+       SCK: [$($(#[$SCK_M:meta])* $SCK:ident<$SCK_AF:ident>),*]
+       MISO: [$($(#[$MISO_M:meta])* $MISO:ident<$MISO_AF:ident>),*]
+       MOSI: [$($(#[$MOSI_M:meta])* $MOSI:ident<$MOSI_AF:ident>),*]
+       NSS: [$($(#[$NSS_M:meta])* $NSS:ident<$NSS_AF:ident>),*])+) => {
         $(
             $(
-                impl PinSck<$SPIX> for $SCK {}
+                $(#[$SCK_M])*
+                impl PinSck<$SPIX> for $SCK<Alternate<$SCK_AF>> {
+                    fn set_speed(self, speed: Speed) -> Self {
+                        $SCK::<Alternate<$SCK_AF>>::set_speed(self, speed)
+                    }
+                }
+            )*
+            $(
+                $(#[$MISO_M])*
+                impl PinMiso<$SPIX> for $MISO<Alternate<$MISO_AF>> {
+                    fn set_idle_pull(&mut self, high: bool) {
+                        if high {
+                            self.internal_pull_up(true);
+                        } else {
+                            self.internal_pull_down(true);
+                        }
+                    }
+
+                    fn set_speed(self, speed: Speed) -> Self {
+                        $MISO::<Alternate<$MISO_AF>>::set_speed(self, speed)
+                    }
+                }
             )*
             $(
-                impl PinMiso<$SPIX> for $MISO {}
+                $(#[$MOSI_M])*
+                impl PinMosi<$SPIX> for $MOSI<Alternate<$MOSI_AF>> {
+                    fn set_idle_pull(&mut self, high: bool) {
+                        if high {
+                            self.internal_pull_up(true);
+                        } else {
+                            self.internal_pull_down(true);
+                        }
+                    }
+
+                    fn set_speed(self, speed: Speed) -> Self {
+                        $MOSI::<Alternate<$MOSI_AF>>::set_speed(self, speed)
+                    }
+                }
             )*
             $(
-                impl PinMosi<$SPIX> for $MOSI {}
+                $(#[$NSS_M])*
+                impl PinNss<$SPIX> for $NSS<Alternate<$NSS_AF>> {}
             )*
+
+            impl PinTable for $SPIX {
+                const SCK: &'static [ValidPin] = &[$(
+                    $(#[$SCK_M])*
+                    ValidPin { pin: stringify!($SCK), af: stringify!($SCK_AF) }
+                ),*];
+                const MISO: &'static [ValidPin] = &[$(
+                    $(#[$MISO_M])*
+                    ValidPin { pin: stringify!($MISO), af: stringify!($MISO_AF) }
+                ),*];
+                const MOSI: &'static [ValidPin] = &[$(
+                    $(#[$MOSI_M])*
+                    ValidPin { pin: stringify!($MOSI), af: stringify!($MOSI_AF) }
+                ),*];
+                const NSS: &'static [ValidPin] = &[$(
+                    $(#[$NSS_M])*
+                    ValidPin { pin: stringify!($NSS), af: stringify!($NSS_AF) }
+                ),*];
+            }
         )+
     }
 }
 
+// Alternate functions from the STM32H742xI/G, STM32H743xI/G,
+// STM32H750xB and STM32H753xI datasheets, which share the same
+// mapping for SPI1 - SPI6. The package features exclude the pins
+// that are not bonded out: ports F and G are absent from LQFP100,
+// ports H (beyond PH1) and I from LQFP144, and ports J and K from
+// LQFP176. Without a package feature, all the pins are implemented
 pins! {
     SPI1:
         SCK: [
-            NoSck,
-            PA5<Alternate<AF5>>,
-            PB3<Alternate<AF5>>,
-            PG11<Alternate<AF5>>
+            PA5<AF5>,
+            PB3<AF5>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG11<AF5>
         ]
         MISO: [
-            NoMiso,
-            PA6<Alternate<AF5>>,
-            PB4<Alternate<AF5>>,
-            PG9<Alternate<AF5>>
+            PA6<AF5>,
+            PB4<AF5>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG9<AF5>
         ]
         MOSI: [
-            NoMosi,
-            PA7<Alternate<AF5>>,
-            PB5<Alternate<AF5>>,
-            PD7<Alternate<AF5>>
+            PA7<AF5>,
+            PB5<AF5>,
+            PD7<AF5>
+        ]
+        NSS: [
+            PA4<AF5>,
+            PA15<AF5>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG10<AF5>
         ]
     SPI2:
         SCK: [
-            NoSck,
-            PA9<Alternate<AF5>>,
-            PA12<Alternate<AF5>>,
-            PB10<Alternate<AF5>>,
-            PB13<Alternate<AF5>>,
-            PD3<Alternate<AF5>>,
-            PI1<Alternate<AF5>>
+            PA9<AF5>,
+            PA12<AF5>,
+            PB10<AF5>,
+            PB13<AF5>,
+            PD3<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PI1<AF5>
         ]
         MISO: [
-            NoMiso,
-            PB14<Alternate<AF5>>,
-            PC2<Alternate<AF5>>,
-            PI2<Alternate<AF5>>
+            PB14<AF5>,
+            PC2<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PI2<AF5>
         ]
         MOSI: [
-            NoMosi,
-            PB15<Alternate<AF5>>,
-            PC1<Alternate<AF5>>,
-            PC3<Alternate<AF5>>,
-            PI3<Alternate<AF5>>
+            PB15<AF5>,
+            PC1<AF5>,
+            PC3<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PI3<AF5>
+        ]
+        NSS: [
+            PA11<AF5>,
+            PB4<AF7>,
+            PB9<AF5>,
+            PB12<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PI0<AF5>
         ]
     SPI3:
         SCK: [
-            NoSck,
-            PB3<Alternate<AF6>>,
-            PC10<Alternate<AF6>>
+            PB3<AF6>,
+            PC10<AF6>
         ]
         MISO: [
-            NoMiso,
-            PB4<Alternate<AF6>>,
-            PC11<Alternate<AF6>>
+            PB4<AF6>,
+            PC11<AF6>
         ]
         MOSI: [
-            NoMosi,
-            PB2<Alternate<AF7>>,
-            PB5<Alternate<AF7>>,
-            PC12<Alternate<AF6>>,
-            PD6<Alternate<AF5>>
+            PB2<AF7>,
+            PB5<AF7>,
+            PC12<AF6>,
+            PD6<AF5>
+        ]
+        NSS: [
+            PA4<AF6>,
+            PA15<AF6>
         ]
     SPI4:
         SCK: [
-            NoSck,
-            PE2<Alternate<AF5>>,
-            PE12<Alternate<AF5>>
+            PE2<AF5>,
+            PE12<AF5>
         ]
         MISO: [
-            NoMiso,
-            PE5<Alternate<AF5>>,
-            PE13<Alternate<AF5>>
+            PE5<AF5>,
+            PE13<AF5>
         ]
         MOSI: [
-            NoMosi,
-            PE6<Alternate<AF5>>,
-            PE14<Alternate<AF5>>
+            PE6<AF5>,
+            PE14<AF5>
+        ]
+        NSS: [
+            PE4<AF5>,
+            PE11<AF5>
         ]
     SPI5:
         SCK: [
-            NoSck,
-            PF7<Alternate<AF5>>,
-            PH6<Alternate<AF5>>,
-            PK0<Alternate<AF5>>
+            #[cfg(not(feature = "lqfp100"))]
+            PF7<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PH6<AF5>,
+            #[cfg(not(any(
+                feature = "lqfp100",
+                feature = "lqfp144",
+                feature = "lqfp176"
+            )))]
+            PK0<AF5>
         ]
         MISO: [
-            NoMiso,
-            PF8<Alternate<AF5>>,
-            PH7<Alternate<AF5>>,
-            PJ11<Alternate<AF5>>
+            #[cfg(not(feature = "lqfp100"))]
+            PF8<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PH7<AF5>,
+            #[cfg(not(any(
+                feature = "lqfp100",
+                feature = "lqfp144",
+                feature = "lqfp176"
+            )))]
+            PJ11<AF5>
         ]
         MOSI: [
-            NoMosi,
-            PF9<Alternate<AF5>>,
-            PF11<Alternate<AF5>>,
-            PJ10<Alternate<AF5>>
+            #[cfg(not(feature = "lqfp100"))]
+            PF9<AF5>,
+            #[cfg(not(feature = "lqfp100"))]
+            PF11<AF5>,
+            #[cfg(not(any(
+                feature = "lqfp100",
+                feature = "lqfp144",
+                feature = "lqfp176"
+            )))]
+            PJ10<AF5>
+        ]
+        NSS: [
+            #[cfg(not(feature = "lqfp100"))]
+            PF6<AF5>,
+            #[cfg(not(any(feature = "lqfp100", feature = "lqfp144")))]
+            PH5<AF5>,
+            #[cfg(not(any(
+                feature = "lqfp100",
+                feature = "lqfp144",
+                feature = "lqfp176"
+            )))]
+            PK1<AF5>
         ]
     SPI6:
         SCK: [
-            NoSck,
-            PA5<Alternate<AF8>>,
-            PB3<Alternate<AF8>>,
-            PG13<Alternate<AF5>>
+            PA5<AF8>,
+            PB3<AF8>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG13<AF5>
         ]
         MISO: [
-            NoMiso,
-            PA6<Alternate<AF8>>,
-            PB4<Alternate<AF8>>,
-            PG12<Alternate<AF5>>
+            PA6<AF8>,
+            PB4<AF8>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG12<AF5>
         ]
         MOSI: [
-            NoMosi,
-            PA7<Alternate<AF8>>,
-            PB5<Alternate<AF8>>,
-            PG14<Alternate<AF5>>
+            PA7<AF8>,
+            PB5<AF8>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG14<AF5>
         ]
+        NSS: [
+            PA4<AF8>,
+            PA15<AF7>,
+            #[cfg(not(feature = "lqfp100"))]
+            PG8<AF5>
+        ]
+}
+
+/// Interrupt events
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// New data has been received
+    Rxp,
+    /// Data can be sent
+    Txp,
+    /// Data can be sent and new data has been received (DXP)
+    Dxp,
+    /// All the data of the transaction has been written to the
+    /// transmit FIFO (TXTF)
+    Txtf,
+    /// The end of a transaction (EOT), a suspended master transaction
+    /// (SUSP) or the last frame transmitted (TXC). These flags share a
+    /// single interrupt enable, so check `is_eot`, `is_suspended` and
+    /// `is_txc` in the interrupt handler. Use TXC to deassert a chip
+    /// select driven by software
+    Eot,
+    /// A master transaction has been suspended (SUSP). SUSP shares its
+    /// interrupt enable (EOTIE) with EOT and TXC, so this is the same
+    /// as `Eot`: listening for it also enables the EOT and TXC
+    /// interrupts. Check `is_suspended` in the interrupt handler
+    Suspend,
+    /// The last frame has been transmitted (TXC), for example to
+    /// deassert a chip select driven by software. TXC shares its
+    /// interrupt enable (EOTIE) with EOT and SUSP, so this is the same
+    /// as `Eot`: listening for it also enables the EOT and SUSP
+    /// interrupts. Check `is_txc` in the interrupt handler
+    Txc,
+    /// TSER has been loaded into TSIZE (TSERF)
+    TserfReload,
+    /// An error occurred: underrun, overrun, CRC error or mode
+    /// fault
+    Error,
+    /// Underrun occurred, in slave mode. Also enabled by `Error`
+    Underrun,
+    /// Overrun occurred. Also enabled by `Error`
+    Overrun,
+    /// CRC error. Also enabled by `Error`
+    CrcError,
+    /// Mode fault. Also enabled by `Error`
+    ModeFault,
+    /// TI frame format error
+    FrameError,
+}
+
+/// A snapshot of the status register (SR). Returned by
+/// `Spi::read_status`.
+///
+/// ```rust
+/// let status = spi.read_status();
+/// if status.contains(StatusFlags::OVR | StatusFlags::MODF) {
+///     spi.clear_flags(status);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StatusFlags(u32);
+
+impl StatusFlags {
+    /// Data has been received
+    pub const RXP: StatusFlags = StatusFlags(1 << 0);
+    /// Data can be sent
+    pub const TXP: StatusFlags = StatusFlags(1 << 1);
+    /// Data can be sent and new data has been received
+    pub const DXP: StatusFlags = StatusFlags(1 << 2);
+    /// End of transfer
+    pub const EOT: StatusFlags = StatusFlags(1 << 3);
+    /// All the data of the transaction has been written to the
+    /// transmit FIFO
+    pub const TXTF: StatusFlags = StatusFlags(1 << 4);
+    /// Underrun, in slave mode
+    pub const UDR: StatusFlags = StatusFlags(1 << 5);
+    /// Overrun
+    pub const OVR: StatusFlags = StatusFlags(1 << 6);
+    /// CRC error
+    pub const CRCE: StatusFlags = StatusFlags(1 << 7);
+    /// TI frame format error
+    pub const TIFRE: StatusFlags = StatusFlags(1 << 8);
+    /// Mode fault
+    pub const MODF: StatusFlags = StatusFlags(1 << 9);
+    /// TSER has been loaded into TSIZE
+    pub const TSERF: StatusFlags = StatusFlags(1 << 10);
+    /// The master transaction was suspended
+    pub const SUSP: StatusFlags = StatusFlags(1 << 11);
+    /// The last frame has been transmitted
+    pub const TXC: StatusFlags = StatusFlags(1 << 12);
+
+    /// Flags that can be cleared with `Spi::clear_flags`. IFCR has a
+    /// clear bit in the same position as each of these flags
+    const CLEARABLE: u32 = 0x0FF8;
+
+    /// No flags
+    pub const fn empty() -> Self {
+        StatusFlags(0)
+    }
+
+    /// Returns the raw value of the flags, as in SR
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if no flags are set
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all of the flags in `other` are set
+    pub const fn contains(&self, other: StatusFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if any of the flags in `other` are set
+    pub const fn intersects(&self, other: StatusFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns the error flagged when receiving, if any. An overrun
+    /// takes precedence over a mode fault, which takes precedence over
+    /// a CRC error
+    fn error(self) -> Option<Error> {
+        if self.contains(StatusFlags::OVR) {
+            Some(Error::Overrun)
+        } else if self.contains(StatusFlags::MODF) {
+            Some(Error::ModeFault)
+        } else if self.contains(StatusFlags::CRCE) {
+            Some(Error::Crc)
+        } else {
+            None
+        }
+    }
+}
+
+impl BitOr for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitor(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 | other.0)
+    }
+}
+
+impl BitAnd for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitand(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 & other.0)
+    }
+}
+
+/// Number of events held by a `TraceBuffer`
+#[cfg(feature = "trace")]
+pub const TRACE_LEN: usize = 32;
+
+/// An event recorded in a `TraceBuffer`
+#[cfg(feature = "trace")]
+#[derive(Copy, Clone, Debug)]
+pub enum TraceEvent {
+    /// A byte was written to the transmit FIFO
+    Send(u8),
+    /// A byte was read from the receive FIFO
+    Read(u8),
+    /// An error was returned
+    Error(Error),
+}
+
+/// An event with its sequence number
+#[cfg(feature = "trace")]
+#[derive(Copy, Clone, Debug)]
+pub struct TraceRecord {
+    pub seq: u32,
+    pub event: TraceEvent,
+}
+
+/// Ring buffer holding the most recent `TRACE_LEN` bus operations.
+/// See `Spi::enable_trace`.
+///
+/// ```rust
+/// static mut TRACE: spi::TraceBuffer = spi::TraceBuffer::new();
+///
+/// spi.enable_trace(unsafe { &mut TRACE });
+/// ```
+#[cfg(feature = "trace")]
+#[derive(Debug)]
+pub struct TraceBuffer {
+    records: [Option<TraceRecord>; TRACE_LEN],
+    next: usize,
+    seq: u32,
+}
+
+#[cfg(feature = "trace")]
+impl TraceBuffer {
+    /// Create an empty trace buffer
+    pub const fn new() -> Self {
+        TraceBuffer {
+            records: [None; TRACE_LEN],
+            next: 0,
+            seq: 0,
+        }
+    }
+
+    fn record(&mut self, event: TraceEvent) {
+        self.records[self.next] = Some(TraceRecord {
+            seq: self.seq,
+            event,
+        });
+        self.next = (self.next + 1) % TRACE_LEN;
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    /// Iterate over the recorded events, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &TraceRecord> {
+        let (newer, older) = self.records.split_at(self.next);
+        older.iter().chain(newer.iter()).filter_map(Option::as_ref)
+    }
+
+    /// Remove all recorded events. Sequence numbers continue from
+    /// their current value.
+    pub fn clear(&mut self) {
+        self.records = [None; TRACE_LEN];
+        self.next = 0;
+    }
+}
+
+/// An SPI bus exchanging words of type `WORD`: `u8`, `u16` or
+/// `u32`. The constructors return `u8` words, whatever the frame size.
+/// See `Spi::change_word_size`
+pub struct Spi<SPI, PINS, WORD = u8>
+where
+    SPI: Instance,
+{
+    spi: SPI,
+    pins: PINS,
+    rec: <SPI as SpiExt<SPI>>::Rec,
+    invert_mosi: bool,
+    invert_miso: bool,
+    managed_cs: bool,
+    cs_delay: u32,
+    cs_hold: u32,
+    sck: Hertz,
+    slave: bool,
+    i2s_ckin: Option<Hertz>,
+    #[cfg(feature = "trace")]
+    trace: Option<&'static mut TraceBuffer>,
+    _word: PhantomData<WORD>,
+}
+
+/// A word type exchanged by the SPI, holding frames of `MIN_BITS` to
+/// `BITS` bits
+pub trait Word: Copy {
+    /// Largest frame size held by this word type
+    const BITS: u8;
+    /// Smallest frame size that needs this word type
+    const MIN_BITS: u8;
+}
+
+impl Word for u8 {
+    const BITS: u8 = 8;
+    const MIN_BITS: u8 = 4;
+}
+
+impl Word for u16 {
+    const BITS: u8 = 16;
+    const MIN_BITS: u8 = 9;
+}
+
+impl Word for u32 {
+    const BITS: u8 = 32;
+    const MIN_BITS: u8 = 17;
+}
+
+/// Shows the effective configuration read back from hardware,
+/// rather than the register block
+impl<SPI, PINS, WORD> fmt::Debug for Spi<SPI, PINS, WORD>
+where
+    SPI: Instance,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cfg1 = self.spi.cfg1.read();
+        let cfg2 = self.spi.cfg2.read();
+
+        f.debug_struct("Spi")
+            .field("frame_size", &(cfg1.dsize().bits() + 1))
+            .field("cpol", &cfg2.cpol().bit_is_set())
+            .field("cpha", &cfg2.cpha().bit_is_set())
+            .field("communication_mode", &read_comm(&cfg2))
+            .field("managed_cs", &self.managed_cs)
+            .field("frequency", &self.sck.0)
+            .finish()
+    }
+}
+
+impl<SPI, PINS, WORD> Spi<SPI, PINS, WORD>
+where
+    SPI: Instance,
+{
+    /// Return `true` if the peripheral is enabled (SPE is
+    /// set).
+    ///
+    /// The driver keeps the peripheral enabled, but it is
+    /// disabled by hardware on a mode fault. While it is
+    /// disabled, sending returns `Error::Disabled`.
+    pub fn is_enabled(&self) -> bool {
+        self.spi.cr1.read().spe().is_enabled()
+    }
+
+    /// Enable the peripheral (set SPE), for example after
+    /// `disable`.
+    pub fn enable(&mut self) {
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+    }
+
+    /// Disable the peripheral (clear SPE), after any
+    /// ongoing transmission has completed. Disabling the
+    /// peripheral flushes both FIFOs, and CFG1, CFG2 and
+    /// TSIZE can only be written while it is disabled.
+    pub fn disable(&mut self) {
+        while self.spi.sr.read().txc().is_ongoing() {}
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+    }
+
+    /// Returns a snapshot of the status register
+    pub fn read_status(&self) -> StatusFlags {
+        self.spi.status()
+    }
+
+    /// Clear each of the `flags` that can be cleared
+    /// through IFCR. The other flags are ignored.
+    pub fn clear_flags(&mut self, flags: StatusFlags) {
+        self.spi.clear(flags);
+    }
+
+    /// Returns a reference to the peripheral.
+    pub fn inner(&self) -> &SPI {
+        &self.spi
+    }
+
+    /// Returns a mutable reference to the peripheral.
+    ///
+    /// Writing the registers directly may desynchronise
+    /// the driver from the peripheral. Prefer the methods
+    /// of `Spi`, such as `enable`, `disable` and
+    /// `clear_flags`, where possible.
+    pub fn inner_mut(&mut self) -> &mut SPI {
+        &mut self.spi
+    }
+
+    /// Returns a mutable reference to the pins.
+    ///
+    /// This allows a pin to be reconfigured temporarily between
+    /// transactions, for example with `with_input_pull_down` to
+    /// sample MISO.
+    pub fn pins_mut(&mut self) -> &mut PINS {
+        &mut self.pins
+    }
+
+    /// Return `true` if a transaction has been started and
+    /// has not yet ended, i.e. CSTART is set.
+    ///
+    /// CSTART is set by the first `send` and is cleared by
+    /// hardware at the end of the transaction (EOT) or when
+    /// the transaction is suspended. Subsequent words sent
+    /// while this is `true` continue the same transaction.
+    /// When TSIZE is zero, the transaction does not end.
+    pub fn transaction_active(&self) -> bool {
+        self.spi.cr1.read().cstart().bit_is_set()
+    }
+
+    /// Return `true` if the TXP flag is set, i.e. new
+    /// data to transmit can be written to the SPI.
+    pub fn is_txp(&self) -> bool {
+        self.spi.sr.read().txp().is_not_full()
+    }
+
+    /// Return `true` if the RXP flag is set, i.e. new
+    /// data has been received and can be read from the
+    /// SPI.
+    pub fn is_rxp(&self) -> bool {
+        self.spi.sr.read().rxp().is_not_empty()
+    }
+
+    /// Return `true` if the MODF flag is set, i.e. the
+    /// SPI has experienced a mode fault
+    pub fn is_modf(&self) -> bool {
+        self.spi.sr.read().modf().is_fault()
+    }
+
+    /// Return `true` if the OVR flag is set, i.e. new
+    /// data has been received while the receive data
+    /// register was already filled.
+    pub fn is_ovr(&self) -> bool {
+        self.spi.sr.read().ovr().is_overrun()
+    }
+
+    /// Return `true` if the DXP flag is set, i.e. data can
+    /// be sent and new data has been received
+    pub fn is_dxp(&self) -> bool {
+        self.spi.sr.read().dxp().bit_is_set()
+    }
+
+    /// Return `true` if the TXTF flag is set, i.e. all the
+    /// data of the transaction has been written to the
+    /// transmit FIFO
+    pub fn is_txtf(&self) -> bool {
+        self.spi.sr.read().txtf().bit_is_set()
+    }
+
+    /// Return `true` if the SUSP flag is set, i.e. the
+    /// master transaction was suspended
+    pub fn is_suspended(&self) -> bool {
+        self.spi.sr.read().susp().bit_is_set()
+    }
+
+    /// Return `true` if the TSERF flag is set, i.e. TSER
+    /// has been loaded into TSIZE
+    pub fn is_tserf(&self) -> bool {
+        self.spi.sr.read().tserf().bit_is_set()
+    }
+
+    /// Return `true` if the UDR flag is set, i.e. the
+    /// master started a frame while the transmit FIFO was
+    /// empty, in slave mode
+    pub fn is_udr(&self) -> bool {
+        self.spi.sr.read().udr().bit_is_set()
+    }
+
+    /// Return `true` if the CRCE flag is set, i.e. the
+    /// received CRC did not match
+    pub fn is_crce(&self) -> bool {
+        self.spi.sr.read().crce().is_error()
+    }
+
+    /// Return `true` if the TIFRE flag is set, i.e. a TI
+    /// frame format error occurred
+    pub fn is_tifre(&self) -> bool {
+        self.spi.sr.read().tifre().bit_is_set()
+    }
+
+    /// Return `true` if the EOT flag is set, i.e. the
+    /// transaction has ended
+    pub fn is_eot(&self) -> bool {
+        self.spi.sr.read().eot().bit_is_set()
+    }
+
+    /// Return `true` if the TXC flag is set, i.e. the
+    /// transmit FIFO is empty and the last frame has left
+    /// the shift register
+    pub fn is_txc(&self) -> bool {
+        self.spi.sr.read().txc().is_completed()
+    }
+
+    /// Wait until all the data written to the transmit FIFO
+    /// has been transmitted. Once this returns, a chip
+    /// select driven by software can be deasserted.
+    ///
+    /// If TSIZE is set, this also waits for the end of the
+    /// transaction (CSTART cleared). Returns any error
+    /// flagged by the peripheral, without clearing it.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        loop {
+            let sr = self.spi.sr.read();
+
+            if let Some(e) = StatusFlags(sr.bits()).error() {
+                return Err(e);
+            } else if sr.udr().bit_is_set() {
+                return Err(Error::Underrun);
+            }
+
+            let ended = self.spi.cr2.read().tsize().bits() == 0
+                || !self.transaction_active();
+            if sr.txc().is_completed() && ended {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Clear the TXTF flag
+    pub fn clear_txtf(&mut self) {
+        self.spi.ifcr.write(|w| w.txtfc().set_bit());
+    }
+
+    /// Clear the SUSP flag
+    pub fn clear_suspend(&mut self) {
+        self.spi.ifcr.write(|w| w.suspc().set_bit());
+    }
+
+    /// Clear the TSERF flag
+    pub fn clear_tserf(&mut self) {
+        self.spi.ifcr.write(|w| w.tserfc().set_bit());
+    }
+
+    /// Clear the UDR flag
+    pub fn clear_udr(&mut self) {
+        self.spi.ifcr.write(|w| w.udrc().set_bit());
+    }
+
+    /// Clear the OVR flag
+    pub fn clear_ovr(&mut self) {
+        self.spi.ifcr.write(|w| w.ovrc().set_bit());
+    }
+
+    /// Clear the MODF flag. The peripheral remains disabled,
+    /// see `recover`
+    pub fn clear_modf(&mut self) {
+        self.spi.ifcr.write(|w| w.modfc().set_bit());
+    }
+
+    /// Clear the TIFRE flag
+    pub fn clear_tifre(&mut self) {
+        self.spi.ifcr.write(|w| w.tifrec().set_bit());
+    }
+}
+
+pub trait SpiExt<SPI: Instance>: Sized {
+    /// Reset and enable control token for this instance, found in
+    /// `ccdr.peripheral`. It is consumed by the constructor and
+    /// returned by `Spi::free`
+    type Rec: fmt::Debug + ResetEnable;
+
+    fn spi<PINS, T, CONFIG>(
+        self,
+        pins: PINS,
+        config: CONFIG,
+        freq: T,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Spi<SPI, PINS>
+    where
+        PINS: Pins<SPI>,
+        T: Into<Hertz>,
+        CONFIG: Into<Config>;
+
+    /// Construct an SPI, returning an error instead of panicking if
+    /// the kernel clock is not running or the configuration is not
+    /// supported by this instance. On error, the peripheral and the
+    /// pins are returned with the error.
+    ///
+    /// ```rust
+    /// let spi = match dp.SPI1.try_spi(
+    ///     pins,
+    ///     spi::MODE_0,
+    ///     1.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// ) {
+    ///     Ok(spi) => spi,
+    ///     Err((spi::Error::KernelClockNotRunning, spi1, pins)) => {
+    ///         /* fall back */
+    ///     }
+    ///     Err((e, _, _)) => panic!("{:?}", e),
+    /// };
+    /// ```
+    fn try_spi<PINS, T, CONFIG>(
+        self,
+        pins: PINS,
+        config: CONFIG,
+        freq: T,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Result<Spi<SPI, PINS>, (Error, SPI, PINS)>
+    where
+        PINS: Pins<SPI>,
+        T: Into<Hertz>,
+        CONFIG: Into<Config>;
+
+    /// Construct a transmit only SPI. No MISO pin is taken, so that
+    /// it remains available for other purposes. The communication
+    /// mode is always `CommunicationMode::Transmitter`.
+    fn spi_tx<SCK, MOSI, T, CONFIG>(
+        self,
+        pins: (SCK, MOSI),
+        config: CONFIG,
+        freq: T,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Spi<SPI, (SCK, MOSI)>
+    where
+        SCK: PinSck<SPI>,
+        MOSI: PinMosi<SPI>,
+        T: Into<Hertz>,
+        CONFIG: Into<Config>;
+
+    /// Construct an SPI slave, selected by the NSS input `pins.3`.
+    /// See `Config::slave`.
+    fn spi_slave<SCK, MISO, MOSI, NSS, CONFIG>(
+        self,
+        pins: (SCK, MISO, MOSI, NSS),
+        config: CONFIG,
+        prec: Self::Rec,
+    ) -> Spi<SPI, (SCK, MISO, MOSI, NSS)>
+    where
+        SCK: PinSck<SPI>,
+        MISO: PinMiso<SPI>,
+        MOSI: PinMosi<SPI>,
+        NSS: PinNss<SPI>,
+        CONFIG: Into<Config>;
+
+    /// Construct an SPI without checking the pins at compile
+    /// time. Any value may be passed as `pins`, and is returned by
+    /// `free`.
+    ///
+    /// The pins must be configured for the correct alternate
+    /// function by the caller, otherwise the bus is silently
+    /// dead. Use `Config::strict_pins` to check the pins at
+    /// construction in debug builds.
+    fn spi_unchecked<PINS, T, CONFIG>(
+        self,
+        pins: PINS,
+        config: CONFIG,
+        freq: T,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Spi<SPI, PINS>
+    where
+        T: Into<Hertz>,
+        CONFIG: Into<Config>;
+
+    /// Construct an SPI with the master baud rate divider chosen at
+    /// compile time, for a kernel clock of `KERNEL_HZ` and a target
+    /// SCK frequency of `TARGET_HZ`.
+    ///
+    /// As with `spi`, the divider is the power of two nearest to
+    /// `KERNEL_HZ / TARGET_HZ`, so SCK can be up to 1.5 times faster
+    /// than `TARGET_HZ`. Fails to compile if `TARGET_HZ` is zero, is
+    /// faster than `KERNEL_HZ`, or cannot be reached by dividing
+    /// `KERNEL_HZ` by 256 or less.
+    ///
+    /// The delays are converted to cycles with integer arithmetic. To
+    /// avoid floating point arithmetic entirely, set them with
+    /// `Config::cs_delay_ns`, `cs_hold_ns` and `inter_word_delay_ns`.
+    ///
+    /// ```rust
+    /// let spi = dp.SPI1.spi_const::<_, _, 100_000_000, 10_000_000>(
+    ///     pins,
+    ///     spi::MODE_0,
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if `KERNEL_HZ` is not the frequency of
+    /// the kernel clock that is actually selected for this instance.
+    /// Release builds do not look up the kernel clock.
+    fn spi_const<PINS, CONFIG, const KERNEL_HZ: u32, const TARGET_HZ: u32>(
+        self,
+        pins: PINS,
+        config: CONFIG,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Spi<SPI, PINS>
+    where
+        PINS: Pins<SPI>,
+        CONFIG: Into<Config>;
+}
+
+/// Description of an SPI bus on a board: the SPI instance, its pins,
+/// frequency, configuration and pin speed. Use `build` to construct
+/// the `Spi`.
+///
+/// The alternate function of each pin is set by converting it to
+/// the alternate function mode before it is placed in `pins`. For
+/// example, with one description per board revision:
+///
+/// ```rust
+/// let board = spi::SpiBoardConfig {
+///     spi: dp.SPI1,
+///     pins: (
+///         gpioa.pa5.into_alternate_af5(),
+///         gpioa.pa6.into_alternate_af5(),
+///         gpioa.pa7.into_alternate_af5(),
+///     ),
+///     freq: 10.mhz().into(),
+///     config: spi::Config::new(spi::MODE_0).idle_levels(false, true),
+///     speed: Speed::High,
+/// };
+/// let spi = board.build(ccdr.peripheral.SPI1, &ccdr.clocks)?;
+/// ```
+pub struct SpiBoardConfig<SPI, PINS> {
+    pub spi: SPI,
+    pub pins: PINS,
+    pub freq: Hertz,
+    pub config: Config,
+    /// Output speed for the SPI pins
+    pub speed: Speed,
+}
+
+impl<SPI, PINS> SpiBoardConfig<SPI, PINS>
+where
+    SPI: Instance,
+    PINS: Pins<SPI>,
+{
+    /// Construct the `Spi`, setting the speed of the SPI pins as
+    /// with `Config::pin_speed`. See `SpiExt::try_spi` for the
+    /// errors returned.
+    pub fn build(
+        self,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        clocks: &CoreClocks,
+    ) -> Result<Spi<SPI, PINS>, Error> {
+        let config = self.config.pin_speed(self.speed);
+
+        self.spi
+            .try_spi(self.pins, config, self.freq, prec, clocks)
+            .map_err(|(e, _, _)| e)
+    }
+}
+
+/// A transaction with a write phase followed by a read phase, where
+/// each phase uses 8-bit or 16-bit frames. Created by
+/// `Spi::mixed_transaction`.
+///
+/// Between the phases, the transfer is suspended at a frame boundary
+/// and the frame size is changed while the peripheral stays enabled,
+/// so a CS managed by the peripheral stays asserted for the whole
+/// transaction.
+///
+/// ```rust
+/// spi.mixed_transaction()
+///     .write8(&command)
+///     .read16(&mut response)
+///     .run()?;
+/// ```
+pub struct MixedTransaction<'a, SPI, PINS>
+where
+    SPI: Instance,
+{
+    spi: &'a mut Spi<SPI, PINS>,
+    write: WritePhase<'a>,
+    read: ReadPhase<'a>,
+}
+
+/// Words sent in the write phase of a `MixedTransaction`
+enum WritePhase<'a> {
+    Bits8(&'a [u8]),
+    Bits16(&'a [u16]),
+}
+
+/// Buffer filled in the read phase of a `MixedTransaction`
+enum ReadPhase<'a> {
+    Bits8(&'a mut [u8]),
+    Bits16(&'a mut [u16]),
+}
+
+impl WritePhase<'_> {
+    /// DSIZE for the frames of this phase
+    fn dsize(&self) -> u8 {
+        match self {
+            WritePhase::Bits8(_) => 8 - 1,
+            WritePhase::Bits16(_) => 16 - 1,
+        }
+    }
+}
+
+impl ReadPhase<'_> {
+    /// DSIZE for the frames of this phase
+    fn dsize(&self) -> u8 {
+        match self {
+            ReadPhase::Bits8(_) => 8 - 1,
+            ReadPhase::Bits16(_) => 16 - 1,
+        }
+    }
+}
+
+impl<'a, SPI, PINS> MixedTransaction<'a, SPI, PINS>
+where
+    SPI: Instance,
+{
+    /// Words to send in the write phase, using 8-bit frames
+    pub fn write8(mut self, words: &'a [u8]) -> Self {
+        self.write = WritePhase::Bits8(words);
+        self
+    }
+
+    /// Words to send in the write phase, using 16-bit frames
+    pub fn write16(mut self, words: &'a [u16]) -> Self {
+        self.write = WritePhase::Bits16(words);
+        self
+    }
+
+    /// Buffer to fill in the read phase, using 8-bit frames
+    pub fn read8(mut self, words: &'a mut [u8]) -> Self {
+        self.read = ReadPhase::Bits8(words);
+        self
+    }
+
+    /// Buffer to fill in the read phase, using 16-bit frames
+    pub fn read16(mut self, words: &'a mut [u16]) -> Self {
+        self.read = ReadPhase::Bits16(words);
+        self
+    }
+}
+
+/// Delay in `seconds`, rounded to the nearest nanosecond. Negative
+/// delays are zero, and delays longer than `u32::MAX` nanoseconds
+/// (about 4.3 seconds) saturate
+fn seconds_to_ns(seconds: f32) -> u32 {
+    (seconds * 1e9 + 0.5) as u32
+}
+
+/// Number of cycles, at a frequency of `hz` Hz, covering a delay of
+/// `ns` nanoseconds. Rounded up
+const fn ns_to_cycles(ns: u32, hz: u32) -> u64 {
+    let product = ns as u64 * hz as u64;
+    let whole = product / 1_000_000_000;
+    if whole * 1_000_000_000 < product {
+        whole + 1
+    } else {
+        whole
+    }
+}
+
+/// Number of SCK cycles, at a frequency of `sck` Hz, covering a
+/// delay of `ns` nanoseconds. Rounded up, and limited to the 15 cycles
+/// that fit the MIDI and MSSI fields
+const fn delay_cycles(ns: u32, sck: u32) -> u8 {
+    let cycles = ns_to_cycles(ns, sck);
+    if cycles >= 15 {
+        15
+    } else {
+        cycles as u8
+    }
+}
+
+/// Number of core clock cycles, at a frequency of `c_ck` Hz, covering
+/// a delay of `ns` nanoseconds. Rounded up, for the software CS delays.
+/// Cannot overflow, as `u32::MAX` nanoseconds at 480 MHz is about 2^31
+/// cycles
+const fn core_delay_cycles(ns: u32, c_ck: u32) -> u32 {
+    ns_to_cycles(ns, c_ck) as u32
+}
+
+/// Master baud rate divider for a kernel clock of `spi_ker_ck` and
+/// SCK frequency of `spi_freq`
+fn mbr_for(spi_ker_ck: u32, spi_freq: u32) -> Result<MBR, ConfigError> {
+    match spi_ker_ck.checked_div(spi_freq) {
+        None => Err(ConfigError::ZeroFrequency),
+        Some(0) => Err(ConfigError::FrequencyTooHigh),
+        Some(ratio) => Ok(mbr_for_ratio(ratio)),
+    }
+}
+
+/// Returns the kernel clock frequency and the master baud rate divider
+/// for a kernel clock of `spi_ker_ck`, if running, and an SCK frequency
+/// of `spi_freq`. A fixed `divider` takes precedence over `spi_freq`
+fn baud_for(
+    spi_ker_ck: Option<Hertz>,
+    spi_freq: u32,
+    divider: Option<Divider>,
+) -> Result<(u32, MBR), Error> {
+    let spi_ker_ck = spi_ker_ck.ok_or(Error::KernelClockNotRunning)?.0;
+    let mbr = match divider {
+        Some(divider) => divider.into(),
+        None => mbr_for(spi_ker_ck, spi_freq).map_err(Error::Config)?,
+    };
+
+    Ok((spi_ker_ck, mbr))
+}
+
+/// Mask of the low `bits` bits of a byte
+const fn frame_mask(bits: u8) -> u8 {
+    if bits >= 8 {
+        0xFF
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// Master baud rate divider for a ratio of kernel clock to SCK
+/// frequency. The ratio is truncated, then mapped to the nearest power
+/// of two, so SCK can be up to 1.5 times faster than requested
+const fn mbr_for_ratio(ratio: u32) -> MBR {
+    match ratio {
+        0..=2 => MBR::DIV2,
+        3..=5 => MBR::DIV4,
+        6..=11 => MBR::DIV8,
+        12..=23 => MBR::DIV16,
+        24..=47 => MBR::DIV32,
+        48..=95 => MBR::DIV64,
+        96..=191 => MBR::DIV128,
+        _ => MBR::DIV256,
+    }
+}
+
+/// Master baud rate divider for a kernel clock of `KERNEL_HZ` and SCK
+/// frequency of `TARGET_HZ`, evaluated at compile time.
+///
+/// Evaluating `MBR` fails to compile if `TARGET_HZ` is zero, faster
+/// than the kernel clock, or slower than the kernel clock divided by
+/// 256.
+struct ConstMbr<const KERNEL_HZ: u32, const TARGET_HZ: u32>;
+
+impl<const KERNEL_HZ: u32, const TARGET_HZ: u32>
+    ConstMbr<KERNEL_HZ, TARGET_HZ>
+{
+    const MBR: MBR = {
+        let valid = TARGET_HZ != 0
+            && TARGET_HZ <= KERNEL_HZ
+            && KERNEL_HZ / 256 <= TARGET_HZ;
+
+        // A compile error here, "attempt to compute `0_usize - 1_usize`,
+        // which would overflow", means that TARGET_HZ cannot be reached
+        // from KERNEL_HZ: it is zero, faster than KERNEL_HZ, or slower
+        // than KERNEL_HZ / 256
+        let _target_hz_out_of_range: u8 = [0; 1][valid as usize - 1];
+
+        mbr_for_ratio(KERNEL_HZ / TARGET_HZ)
+    };
+}
+
+/// Tracks a full duplex transfer that writes ahead of the reads, so
+/// that the SPI clock runs without gaps between words.
+///
+/// At most `depth` words are in flight: written to the transmit FIFO
+/// but not yet read from the receive FIFO. As each of these words
+/// ends up in the receive FIFO, the receive FIFO cannot overrun as
+/// long as `depth` words fit in it.
+struct Pipeline {
+    depth: usize,
+    written: usize,
+    read: usize,
+}
+
+impl Pipeline {
+    fn new(depth: usize) -> Self {
+        Pipeline {
+            depth: depth.max(1),
+            written: 0,
+            read: 0,
+        }
+    }
+
+    /// Return `true` if another word can be written
+    fn can_write(&self) -> bool {
+        self.written - self.read < self.depth
+    }
+
+    /// Return `true` if a word has been written but not read
+    fn in_flight(&self) -> bool {
+        self.written > self.read
+    }
+}
+
+/// Write the words produced by `words` to `bus`, keeping the transmit
+/// FIFO topped up while the receive FIFO is drained, and pass each
+/// received word to `received` in order. At most `depth` words are in
+/// flight, see `Pipeline`
+fn stream_pipelined<W, B, I, F>(
+    bus: &mut B,
+    words: I,
+    mut received: F,
+    depth: usize,
+) -> Result<(), B::Error>
+where
+    W: Copy,
+    B: FullDuplex<W>,
+    I: IntoIterator<Item = W>,
+    F: FnMut(W),
+{
+    let mut pipeline = Pipeline::new(depth);
+    let mut words = words.into_iter();
+    let mut next = words.next();
+
+    let mut step = || -> nb::Result<(), B::Error> {
+        if let Some(word) = next.filter(|_| pipeline.can_write()) {
+            match bus.send(word) {
+                Ok(()) => {
+                    pipeline.written += 1;
+                    next = words.next();
+                }
+                Err(nb::Error::WouldBlock) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if pipeline.in_flight() {
+            received(bus.read()?);
+            pipeline.read += 1;
+        }
+        if next.is_none() && !pipeline.in_flight() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    };
+    nb::block!(step())
+}
+
+/// Transfer `words` in place through `bus`. See `stream_pipelined`
+fn transfer_pipelined<W, B>(
+    bus: &mut B,
+    words: &mut [W],
+    depth: usize,
+) -> Result<(), B::Error>
+where
+    W: Copy,
+    B: FullDuplex<W>,
+{
+    // Each word is read back after it has been written, so it can be
+    // overwritten in place
+    let cells = Cell::from_mut(words).as_slice_of_cells();
+    let mut read = 0;
+
+    stream_pipelined(
+        bus,
+        cells.iter().map(Cell::get),
+        |word| {
+            cells[read].set(word);
+            read += 1;
+        },
+        depth,
+    )
+}
+
+/// Register accesses of the FIFO data path. Implemented by the
+/// peripheral's register block, and by a model of the peripheral in
+/// the host tests
+trait SpiRegs {
+    /// Read SR
+    fn status(&self) -> StatusFlags;
+    /// Clear `flags` through IFCR
+    fn clear(&self, flags: StatusFlags);
+    /// Read one byte from RXDR
+    fn read_rxdr_u8(&self) -> u8;
+    /// Write one byte to TXDR
+    fn write_txdr_u8(&self, byte: u8);
+}
+
+impl SpiRegs for spi1::RegisterBlock {
+    fn status(&self) -> StatusFlags {
+        StatusFlags(self.sr.read().bits() & 0x1FFF)
+    }
+
+    fn clear(&self, flags: StatusFlags) {
+        let bits = flags.0 & StatusFlags::CLEARABLE;
+        // NOTE(unsafe) only the clear bits of IFCR are set
+        self.ifcr.write(|w| unsafe { w.bits(bits) });
+    }
+
+    fn read_rxdr_u8(&self) -> u8 {
+        // NOTE(read_volatile) read only 1 byte (the svd2rust API only
+        // allows reading a half-word)
+        unsafe { ptr::read_volatile(&self.rxdr as *const _ as *const u8) }
+    }
+
+    fn write_txdr_u8(&self, byte: u8) {
+        // NOTE(write_volatile) see note above
+        unsafe {
+            ptr::write_volatile(ptr::addr_of!(self.txdr) as *mut u8, byte)
+        }
+    }
+}
+
+/// Read a byte from the receive FIFO. It is inverted if `invert`, then
+/// masked with `mask`
+fn fifo_read_u8<R: SpiRegs + ?Sized>(
+    regs: &R,
+    invert: bool,
+    mask: u8,
+) -> nb::Result<u8, Error> {
+    let status = regs.status();
+
+    if let Some(e) = status.error() {
+        Err(nb::Error::Other(e))
+    } else if status.contains(StatusFlags::RXP) {
+        let byte = regs.read_rxdr_u8();
+        let byte = if invert { !byte } else { byte };
+        Ok(byte & mask)
+    } else {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+/// Write a byte to the transmit FIFO. It is inverted if `invert`, then
+/// masked with `mask`
+fn fifo_write_u8<R: SpiRegs + ?Sized>(
+    regs: &R,
+    byte: u8,
+    invert: bool,
+    mask: u8,
+) -> nb::Result<(), Error> {
+    let status = regs.status();
+
+    if status.contains(StatusFlags::UDR) {
+        // Clear the flag, so that the slave can continue
+        regs.clear(StatusFlags::UDR);
+        Err(nb::Error::Other(Error::Underrun))
+    } else if let Some(e) = status.error() {
+        Err(nb::Error::Other(e))
+    } else if status.contains(StatusFlags::TXP) {
+        let byte = if invert { !byte } else { byte };
+        regs.write_txdr_u8(byte & mask);
+        Ok(())
+    } else {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+/// Resume a master transaction that was suspended by hardware because
+/// the receive FIFO was full. See `Config::master_rx_auto_suspend`
+fn resume_rx(spi: &spi1::RegisterBlock) {
+    if spi.cr1.read().masrx().bit_is_set()
+        && spi.sr.read().susp().bit_is_set()
+    {
+        spi.ifcr.write(|w| w.suspc().set_bit());
+        spi.cr1.modify(|_, w| w.cstart().started());
+    }
+}
+
+/// Write TSIZE. The peripheral is briefly disabled, after any ongoing
+/// transmission has completed
+fn set_tsize(spi: &spi1::RegisterBlock, tsize: u16) {
+    while spi.sr.read().txc().is_ongoing() {}
+
+    spi.cr1.modify(|_, w| w.spe().disabled());
+    spi.cr2.modify(|_, w| w.tsize().bits(tsize));
+    spi.cr1.modify(|_, w| w.spe().enabled());
+}
+
+/// Write the TSER field of CR2, the number of words to be loaded into
+/// TSIZE when it runs out. The PAC only exposes TSER for reading
+fn set_tser(spi: &spi1::RegisterBlock, tser: u16) {
+    // NOTE(unsafe) TSER is CR2[31:16], TSIZE is preserved
+    spi.cr2.modify(|r, w| unsafe {
+        w.bits((r.bits() & 0x0000_FFFF) | ((tser as u32) << 16))
+    });
+}
+
+/// A transaction of a fixed length that can be run repeatedly.
+/// Created by `Spi::prepare_transaction`.
+///
+/// TSIZE is written once when the transaction is prepared, so that
+/// each run only has to feed the FIFOs. TSIZE is cleared again when
+/// the `Transaction` is dropped.
+///
+/// ```rust
+/// let mut transaction = spi.prepare_transaction(4);
+/// loop {
+///     transaction.run(&command, &mut response)?;
+/// }
+/// ```
+pub struct Transaction<'a, SPI, PINS>
+where
+    SPI: Instance,
+{
+    spi: &'a mut Spi<SPI, PINS>,
+    len: usize,
+}
+
+impl<'a, SPI, PINS> Transaction<'a, SPI, PINS>
+where
+    SPI: Instance,
+    Spi<SPI, PINS>: FullDuplex<u8, Error = Error>,
+{
+    /// Run the transaction, sending `tx` and receiving into `rx`.
+    /// Returns once the end of the transaction (EOT) is reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `tx` or `rx` differs from the length
+    /// of the transaction.
+    pub fn run(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<(), Error> {
+        assert!(tx.len() == self.len && rx.len() == self.len);
+
+        // CSTART is cleared by hardware at the end of each run
+        if !self.spi.slave {
+            self.spi.spi.cr1.modify(|_, w| w.cstart().started());
+        }
+        for (out, received) in tx.iter().zip(rx.iter_mut()) {
+            nb::block!(self.spi.send(*out))?;
+            *received = nb::block!(self.spi.read())?;
+        }
+
+        let regs = &self.spi.spi;
+        while regs.sr.read().eot().bit_is_clear() {}
+        regs.ifcr.write(|w| w.eotc().set_bit().txtfc().set_bit());
+
+        Ok(())
+    }
+}
+
+impl<'a, SPI, PINS> Drop for Transaction<'a, SPI, PINS>
+where
+    SPI: Instance,
+{
+    fn drop(&mut self) {
+        set_tsize(&self.spi.spi, 0);
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// An SPI peripheral instance
+///
+/// Allows code that is generic over the SPI instance to also
+/// construct the SPI, using the `SpiExt` trait. This trait is sealed,
+/// and is implemented for SPI1 - SPI6 only.
+///
+/// ```rust
+/// fn new_bus<S, P>(
+///     spi: S,
+///     pins: P,
+///     prec: <S as SpiExt<S>>::Rec,
+///     clocks: &CoreClocks,
+/// ) -> Spi<S, P>
+/// where
+///     S: spi::Instance,
+///     P: spi::Pins<S>,
+/// {
+///     spi.spi(pins, spi::MODE_0, 1.mhz(), prec, clocks)
+/// }
+/// ```
+pub trait Instance:
+    Deref<Target = spi1::RegisterBlock>
+    + SpiExt<Self>
+    + PinTable
+    + sealed::Sealed
+{
+    /// Interrupt line for this instance
+    const INTERRUPT: Interrupt;
+
+    /// Maximum frame size in bits supported by this instance
+    const MAX_FRAME_SIZE: u8;
+
+    /// Size of each of the transmit and receive FIFOs in bytes
+    const FIFO_SIZE: usize;
+
+    /// Returns the frequency of the current kernel clock for this
+    /// instance
+    fn kernel_clk(clocks: &CoreClocks) -> Option<Hertz>;
+
+    /// Returns the frequency of the current kernel clock for this
+    /// instance, where `i2s_ckin` is the frequency of the external
+    /// clock on the I2S_CKIN pin, if any
+    fn kernel_clk_ckin(
+        clocks: &CoreClocks,
+        _i2s_ckin: Option<Hertz>,
+    ) -> Option<Hertz> {
+        Self::kernel_clk(clocks)
+    }
+
+    /// Returns `true` if the kernel clock currently selected for this
+    /// instance keeps running across STOP mode. See
+    /// `Config::low_power_compatible`.
+    fn kernel_clk_survives_stop() -> bool;
+}
+
+/// DMAMUX request lines for an SPI instance
+///
+/// Used to select the correct request line when configuring a DMA
+/// stream for this SPI. See RM0433 Rev 6 Section 17.3.2 "DMAMUX1
+/// mapping" and Section 17.3.3 "DMAMUX2 mapping".
+///
+/// Note: SPI1 - SPI5 are connected to DMAMUX1 (DMA1 / DMA2),
+/// whereas SPI6 is connected to DMAMUX2 (BDMA).
+pub trait DmaRequests {
+    /// DMAMUX request line for received data
+    const DMA_RX_REQUEST: u8;
+    /// DMAMUX request line for data to transmit
+    const DMA_TX_REQUEST: u8;
+}
+
+macro_rules! dma_requests {
+    ($($SPIX:ident: ($rx:expr, $tx:expr),)+) => {
+        $(
+            impl DmaRequests for $SPIX {
+                const DMA_RX_REQUEST: u8 = $rx;
+                const DMA_TX_REQUEST: u8 = $tx;
+            }
+        )+
+    }
+}
+
+dma_requests! {
+    SPI1: (37, 38),
+    SPI2: (39, 40),
+    SPI3: (61, 62),
+    SPI4: (83, 84),
+    SPI5: (85, 86),
+    SPI6: (11, 12),
+}
+
+/// A DMA controller that can be split into streams by `DmaStreams`
+pub trait DmaInstance {
+    /// The first DMAMUX1 channel connected to this controller
+    const DMAMUX_CHANNEL: usize;
+
+    /// Returns a pointer to the register block of this controller
+    fn ptr() -> *const dma1::RegisterBlock;
+
+    /// Enable the clock for this controller
+    fn enable(ahb1: &mut AHB1);
+}
+
+impl DmaInstance for DMA1 {
+    const DMAMUX_CHANNEL: usize = 0;
+
+    fn ptr() -> *const dma1::RegisterBlock {
+        DMA1::ptr()
+    }
+
+    fn enable(ahb1: &mut AHB1) {
+        ahb1.enr().modify(|_, w| w.dma1en().enabled());
+    }
+}
+
+impl DmaInstance for DMA2 {
+    const DMAMUX_CHANNEL: usize = 8;
+
+    fn ptr() -> *const dma1::RegisterBlock {
+        DMA2::ptr()
+    }
+
+    fn enable(ahb1: &mut AHB1) {
+        ahb1.enr().modify(|_, w| w.dma2en().enabled());
+    }
+}
+
+// DMA stream interrupt flags, relative to the stream's offset
+const DMA_HTIF: u32 = 1 << 4;
+const DMA_TEIF: u32 = 1 << 3;
+const DMA_TCIF: u32 = 1 << 5;
+const DMA_FLAGS: u32 = 0b11_1101;
+
+/// The eight streams of DMA1 or DMA2
+///
+/// ```rust
+/// let streams = DmaStreams::new(dp.DMA1, &mut ccdr.ahb1);
+/// let tx = spi.dma_tx(streams.s0);
+/// ```
+pub struct DmaStreams<DMA> {
+    pub s0: DmaStream<DMA>,
+    pub s1: DmaStream<DMA>,
+    pub s2: DmaStream<DMA>,
+    pub s3: DmaStream<DMA>,
+    pub s4: DmaStream<DMA>,
+    pub s5: DmaStream<DMA>,
+    pub s6: DmaStream<DMA>,
+    pub s7: DmaStream<DMA>,
+}
+
+impl<DMA: DmaInstance> DmaStreams<DMA> {
+    /// Split the DMA controller `dma` into its streams, and enable
+    /// the controller's clock
+    pub fn new(_dma: DMA, ahb1: &mut AHB1) -> Self {
+        DMA::enable(ahb1);
+
+        DmaStreams {
+            s0: DmaStream::new(0),
+            s1: DmaStream::new(1),
+            s2: DmaStream::new(2),
+            s3: DmaStream::new(3),
+            s4: DmaStream::new(4),
+            s5: DmaStream::new(5),
+            s6: DmaStream::new(6),
+            s7: DmaStream::new(7),
+        }
+    }
+}
+
+/// A stream of DMA1 or DMA2, for use by `Spi::dma_tx` and
+/// `Spi::dma_rx`. Created by `DmaStreams::new`.
+///
+/// The stream owns its registers, its flags in LISR / HISR, and the
+/// DMAMUX1 channel connected to it. Other streams of the same
+/// controller can be used independently.
+pub struct DmaStream<DMA> {
+    stream: usize,
+    _dma: PhantomData<DMA>,
+}
+
+/// Size of each access of a DMA stream to memory and to the
+/// peripheral
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum DmaSize {
+    Bits8,
+    Bits16,
+}
+
+/// Configuration of a DMA stream transfer
+#[derive(Copy, Clone)]
+struct DmaConfig {
+    /// Transfer from memory to the peripheral, rather than from the
+    /// peripheral to memory
+    to_peripheral: bool,
+    /// Restart from the beginning of the buffer once it is full
+    circular: bool,
+    /// Enable the half transfer and transfer complete interrupts
+    interrupts: bool,
+    size: DmaSize,
+    /// Peripheral burst length in beats, or 1 for single transfers
+    burst: u8,
+}
+
+impl<DMA: DmaInstance> DmaStream<DMA> {
+    fn new(stream: usize) -> Self {
+        DmaStream {
+            stream,
+            _dma: PhantomData,
+        }
+    }
+
+    /// Registers of the DMA controller
+    fn dma(&self) -> &dma1::RegisterBlock {
+        // NOTE(unsafe) this stream only writes its own registers, and
+        // clears its own flags through the write-only LIFCR / HIFCR
+        unsafe { &*DMA::ptr() }
+    }
+
+    /// Select DMAMUX request line `request` for this stream
+    fn select_request(&mut self, request: u8) {
+        // NOTE(unsafe) This stream owns its DMAMUX1 channel
+        let dmamux = unsafe { &*DMAMUX1::ptr() };
+        dmamux.ccr[DMA::DMAMUX_CHANNEL + self.stream]
+            .write(|w| unsafe { w.bits(u32::from(request)) });
+    }
+
+    /// Bit offset of the flags for this stream in LISR / HISR
+    fn flag_offset(&self) -> u32 {
+        [0, 6, 16, 22][self.stream % 4]
+    }
+
+    /// Read the flags for this stream: TCIF, HTIF, TEIF, DMEIF, FEIF
+    fn flags(&self) -> u32 {
+        let isr = if self.stream < 4 {
+            self.dma().lisr.read().bits()
+        } else {
+            self.dma().hisr.read().bits()
+        };
+        (isr >> self.flag_offset()) & DMA_FLAGS
+    }
+
+    /// Clear all flags for this stream
+    fn clear_flags(&mut self) {
+        self.clear(DMA_FLAGS);
+    }
+
+    /// Clear the given flags for this stream
+    fn clear(&mut self, flags: u32) {
+        let mask = flags << self.flag_offset();
+        if self.stream < 4 {
+            self.dma().lifcr.write(|w| unsafe { w.bits(mask) });
+        } else {
+            self.dma().hifcr.write(|w| unsafe { w.bits(mask) });
+        }
+    }
+
+    /// Start a transfer of `ndtr` accesses between `memory` and
+    /// `peripheral`. The stream FIFO is used if `config` selects a
+    /// peripheral burst
+    fn start(
+        &mut self,
+        memory: u32,
+        peripheral: u32,
+        ndtr: u16,
+        config: DmaConfig,
+    ) {
+        self.stop();
+        self.clear_flags();
+
+        let st = &self.dma().st[self.stream];
+        st.par.write(|w| unsafe { w.bits(peripheral) });
+        st.m0ar.write(|w| unsafe { w.bits(memory) });
+        st.ndtr.write(|w| unsafe { w.bits(u32::from(ndtr)) });
+        if config.burst > 1 {
+            // Bursts require the FIFO
+            st.fcr.write(|w| w.dmdis().disabled().fth().full());
+        } else {
+            st.fcr.write(|w| w.dmdis().enabled());
+        }
+
+        // Writes to the buffer must complete before the stream reads
+        // it, and reads of the buffer must not be moved before it
+        atomic::compiler_fence(Ordering::Release);
+
+        st.cr.write(|w| {
+            let w = if config.to_peripheral {
+                w.dir().memory_to_peripheral()
+            } else {
+                w.dir().peripheral_to_memory()
+            };
+            let w = match config.size {
+                DmaSize::Bits8 => w.psize().bits8().msize().bits8(),
+                DmaSize::Bits16 => w.psize().bits16().msize().bits16(),
+            };
+            let w = match config.burst {
+                4 => w.pburst().incr4(),
+                8 => w.pburst().incr8(),
+                16 => w.pburst().incr16(),
+                _ => w.pburst().single(),
+            };
+            w.circ()
+                .bit(config.circular)
+                .htie()
+                .bit(config.interrupts)
+                .tcie()
+                .bit(config.interrupts)
+                .minc()
+                .incremented()
+                .en()
+                .enabled()
+        });
+    }
+
+    /// Disable the stream, and wait for it to stop
+    fn stop(&mut self) {
+        let st = &self.dma().st[self.stream];
+        st.cr.modify(|_, w| w.en().disabled());
+        while st.cr.read().en().bit_is_set() {}
+
+        // The stream has stopped writing the buffer
+        atomic::compiler_fence(Ordering::Acquire);
+    }
+
+    /// Returns `Error::Dma` if the stream reports a transfer error
+    fn check_error(&self) -> Result<(), Error> {
+        if self.flags() & DMA_TEIF != 0 {
+            Err(Error::Dma)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// DMA peripheral burst matching the FIFO threshold of `spi`, so that
+/// each DMA request moves a whole packet
+fn dma_burst(spi: &spi1::RegisterBlock) -> u8 {
+    match spi.cfg1.read().fthlv().bits() + 1 {
+        burst @ 4 | burst @ 8 | burst @ 16 => burst,
+        _ => 1,
+    }
+}
+
+/// An SPI paired with a DMA stream for transmitting. Created by
+/// `Spi::dma_tx`.
+///
+/// `Transfer::wait` hands back the SPI, the buffer and the DMA
+/// stream, so that two buffers can be ping-ponged: one is filled
+/// while the other is transmitted.
+///
+/// ```rust
+/// let streams = DmaStreams::new(dp.DMA1, &mut ccdr.ahb1);
+/// let mut transfer = spi.dma_tx(streams.s0).start(ping);
+/// loop {
+///     fill(pong);
+///     let (spi, sent, stream) = transfer.wait().map_err(|(e, ..)| e)?;
+///     transfer = spi.dma_tx(stream).start(pong);
+///     pong = sent;
+/// }
+/// ```
+pub struct TxDma<SPI, PINS, DMA>
+where
+    SPI: Instance,
+{
+    spi: Spi<SPI, PINS>,
+    stream: DmaStream<DMA>,
+    /// Communication mode to restore after each transfer
+    comm: CommunicationMode,
+}
+
+/// A DMA transmit in progress. Created by `TxDma::start`
+pub struct Transfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+{
+    tx: TxDma<SPI, PINS, DMA>,
+    buf: &'static mut [u8],
+}
+
+impl<SPI, PINS, DMA> TxDma<SPI, PINS, DMA>
+where
+    SPI: Instance,
+    DMA: DmaInstance,
+{
+    /// Start transmitting `buf`.
+    ///
+    /// The peripheral is switched to
+    /// `CommunicationMode::Transmitter` for the duration of the
+    /// transfer, so that no received data accumulates.
+    ///
+    /// The buffer must be in memory that is accessible to DMA1 and
+    /// DMA2, for example AXI SRAM. DTCM is not accessible. If the
+    /// data cache is enabled, it must be cleaned for `buf` first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty or longer than 65535 bytes. TSIZE = 0
+    /// would start an endless transfer.
+    pub fn start(
+        mut self,
+        buf: &'static mut [u8],
+    ) -> Transfer<SPI, PINS, DMA> {
+        assert!(!buf.is_empty() && buf.len() <= u16::MAX as usize);
+        let len = buf.len() as u16;
+        let spi = &self.spi.spi;
+
+        // CFG1, CFG2 and TSIZE can only be written while disabled
+        while spi.sr.read().txc().is_ongoing() {}
+        spi.cr1.modify(|_, w| w.spe().disabled());
+        spi.cr2.modify(|_, w| w.tsize().bits(len));
+        spi.cfg2.modify(|_, w| w.comm().transmitter());
+
+        self.stream.start(
+            buf.as_ptr() as u32,
+            ptr::addr_of!(spi.txdr) as u32,
+            len,
+            DmaConfig {
+                to_peripheral: true,
+                circular: false,
+                interrupts: false,
+                size: DmaSize::Bits8,
+                burst: dma_burst(spi),
+            },
+        );
+
+        let spi = &self.spi.spi;
+        spi.cfg1.modify(|_, w| w.txdmaen().set_bit());
+        spi.cr1.modify(|_, w| w.spe().enabled());
+        spi.cr1.modify(|_, w| w.cstart().started());
+
+        Transfer { tx: self, buf }
+    }
+
+    /// Release the SPI and the DMA stream
+    pub fn free(self) -> (Spi<SPI, PINS>, DmaStream<DMA>) {
+        (self.spi, self.stream)
+    }
+}
+
+impl<SPI, PINS, DMA> Transfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+    DMA: DmaInstance,
+{
+    /// Returns `Ok` once the transfer is complete: the DMA stream has
+    /// transferred the whole buffer (TC), and the SPI has transmitted
+    /// it (EOT). The SPI's DMA requests are then disabled, which
+    /// disables the peripheral until `wait`. Returns `WouldBlock`
+    /// until then.
+    ///
+    /// Returns `Error::Dma` if the DMA stream reports a transfer
+    /// error, which occurs if the buffer is in memory that is not
+    /// accessible to the DMA controller.
+    pub fn poll(&self) -> nb::Result<(), Error> {
+        self.tx.stream.check_error()?;
+        // Completed by a previous call
+        if self.tx.spi.spi.cfg1.read().txdmaen().bit_is_clear() {
+            return Ok(());
+        }
+
+        let tc = self.tx.stream.flags() & DMA_TCIF != 0;
+        let eot = self.tx.spi.spi.sr.read().eot().bit_is_set();
+
+        if tc && eot {
+            disable_dma_requests(&self.tx.spi.spi);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Wait for the transfer to complete (EOT), then return the SPI,
+    /// the buffer and the DMA stream. The SPI's DMA requests are
+    /// disabled and its communication mode is restored, so it can be
+    /// used for blocking operations or paired with the stream again.
+    ///
+    /// On a DMA transfer error, see `poll`, the transfer is stopped
+    /// and the error is returned together with the SPI, the buffer
+    /// and the DMA stream.
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        self,
+    ) -> Result<
+        (Spi<SPI, PINS>, &'static mut [u8], DmaStream<DMA>),
+        (Error, Spi<SPI, PINS>, &'static mut [u8], DmaStream<DMA>),
+    > {
+        let result = nb::block!(self.poll());
+
+        let Transfer { mut tx, buf } = self;
+        tx.stream.stop();
+        tx.stream.clear_flags();
+        end_dma(&tx.spi.spi, tx.comm);
+
+        let (spi, stream) = tx.free();
+        match result {
+            Ok(()) => Ok((spi, buf, stream)),
+            Err(e) => Err((e, spi, buf, stream)),
+        }
+    }
+}
+
+/// Disable the DMA requests of `spi`. CFG1 can only be written while
+/// the peripheral is disabled, so it is left disabled
+fn disable_dma_requests(spi: &spi1::RegisterBlock) {
+    spi.cr1.modify(|_, w| w.spe().disabled());
+    spi.cfg1
+        .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+}
+
+/// Restore the SPI after a DMA transfer: disable the DMA requests,
+/// clear TSIZE and restore the communication mode `mode`
+fn end_dma(spi: &spi1::RegisterBlock, mode: CommunicationMode) {
+    spi.ifcr.write(|w| w.eotc().set_bit().txtfc().set_bit());
+    disable_dma_requests(spi);
+    spi.cr2.modify(|_, w| w.tsize().bits(0));
+    spi.cfg2.modify(|_, w| comm(w, mode));
+    spi.cr1.modify(|_, w| w.spe().enabled());
+}
+
+/// DMA access size for receiving 8-bit frames into `buf`. With a FIFO
+/// threshold of two frames, a packet is read from RXDR as a halfword
+/// holding both frames, if whole halfwords fit `buf` (and each half of
+/// it, if `circular`)
+fn dma_rx_size(
+    spi: &spi1::RegisterBlock,
+    buf: &[u8],
+    circular: bool,
+) -> DmaSize {
+    let multiple = if circular { 4 } else { 2 };
+
+    if spi.cfg1.read().fthlv().bits() + 1 == 2
+        && buf.len() & (multiple - 1) == 0
+        && buf.as_ptr() as usize & 1 == 0
+    {
+        DmaSize::Bits16
+    } else {
+        DmaSize::Bits8
+    }
+}
+
+/// An SPI paired with a DMA stream for receiving. Created by
+/// `Spi::dma_rx`.
+///
+/// The peripheral is switched to `CommunicationMode::Receiver` for
+/// the duration of each transfer, so that the master generates the
+/// clock without any data to transmit.
+///
+/// Frames must be 8 bits or less. The DMA stream reads RXDR one frame
+/// per byte, or with a FIFO threshold of 4, 8 or 16 frames, see
+/// `Config::fifo_threshold`, in bursts of a whole packet.
+///
+/// With a FIFO threshold of 2 frames, the receive FIFO packs each
+/// packet of two frames into a halfword of RXDR. If the buffer is
+/// aligned to two bytes, and its length is a multiple of 2 (or of 4
+/// for `start_circular`), the DMA stream then reads RXDR a halfword
+/// at a time, halving the number of DMA requests. Otherwise it reads
+/// one frame per byte.
+pub struct RxDma<SPI, PINS, DMA>
+where
+    SPI: Instance,
+{
+    spi: Spi<SPI, PINS>,
+    stream: DmaStream<DMA>,
+    /// Communication mode to restore after each transfer
+    comm: CommunicationMode,
+}
+
+/// A DMA receive of a fixed number of frames in progress. Created by
+/// `RxDma::start`
+pub struct RxTransfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+{
+    rx: RxDma<SPI, PINS, DMA>,
+    buf: &'static mut [u8],
+}
+
+/// Half of the buffer of a `CircularTransfer`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Half {
+    First,
+    Second,
+}
+
+/// A continuous DMA receive into a circular buffer. Created by
+/// `RxDma::start_circular`
+///
+/// ```rust
+/// let mut capture = rx.start_circular(buffer);
+/// loop {
+///     capture.poll()?;
+///     if let Some(half) = capture.readable_half()? {
+///         process(capture.half(half));
+///     }
+/// }
+/// ```
+pub struct CircularTransfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+{
+    rx: RxDma<SPI, PINS, DMA>,
+    buf: &'static mut [u8],
+}
+
+impl<SPI, PINS, DMA> RxDma<SPI, PINS, DMA>
+where
+    SPI: Instance,
+    DMA: DmaInstance,
+{
+    /// Enable receiving into `buf`, and start the master clock
+    fn begin(&mut self, buf: &mut [u8], tsize: u16, circular: bool) {
+        // An empty transfer would program TSIZE or NDTR to zero, and
+        // never end
+        assert!(!buf.is_empty() && buf.len() <= u16::MAX as usize);
+        let spi = &self.spi.spi;
+        assert!(
+            spi.cfg1.read().dsize().bits() < 8,
+            "DMA receive requires frames of 8 bits or less"
+        );
+        let size = dma_rx_size(spi, buf, circular);
+        let ndtr = match size {
+            DmaSize::Bits8 => buf.len(),
+            DmaSize::Bits16 => buf.len() / 2,
+        };
+
+        // CFG1, CFG2 and TSIZE can only be written while disabled
+        while spi.sr.read().txc().is_ongoing() {}
+        spi.cr1.modify(|_, w| w.spe().disabled());
+        spi.cr2.modify(|_, w| w.tsize().bits(tsize));
+        spi.cfg2.modify(|_, w| w.comm().receiver());
+        spi.cfg1.modify(|_, w| w.rxdmaen().set_bit());
+
+        self.stream.start(
+            buf.as_mut_ptr() as u32,
+            ptr::addr_of!(spi.rxdr) as u32,
+            ndtr as u16,
+            DmaConfig {
+                to_peripheral: false,
+                circular,
+                interrupts: circular,
+                size,
+                burst: dma_burst(spi),
+            },
+        );
+
+        let spi = &self.spi.spi;
+        spi.cr1.modify(|_, w| w.spe().enabled());
+        spi.cr1.modify(|_, w| w.cstart().started());
+    }
+
+    /// Receive `buf.len()` frames into `buf`. TSIZE is set to the
+    /// length of the buffer, so the master stops generating the
+    /// clock once the buffer is full.
+    ///
+    /// See `TxDma::start` for the requirements on `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty or longer than 65535 bytes, or if the
+    /// frame size is larger than 8 bits.
+    pub fn start(
+        mut self,
+        buf: &'static mut [u8],
+    ) -> RxTransfer<SPI, PINS, DMA> {
+        let len = buf.len() as u16;
+        self.begin(buf, len, false);
+
+        RxTransfer { rx: self, buf }
+    }
+
+    /// Receive continuously into `buf`, which is used as a circular
+    /// buffer. The master generates the clock until the transfer is
+    /// stopped.
+    ///
+    /// The DMA stream's half transfer and transfer complete
+    /// interrupts are enabled. Unmask the stream's interrupt in the
+    /// NVIC to be notified when a half of the buffer is ready, then
+    /// call `readable_half`. With `Config::master_rx_auto_suspend`,
+    /// `CircularTransfer::poll` must also be called periodically.
+    ///
+    /// See `TxDma::start` for the requirements on `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty or longer than 65535 bytes, or if the
+    /// frame size is larger than 8 bits.
+    pub fn start_circular(
+        mut self,
+        buf: &'static mut [u8],
+    ) -> CircularTransfer<SPI, PINS, DMA> {
+        self.begin(buf, 0, true);
+
+        CircularTransfer { rx: self, buf }
+    }
+
+    /// Release the SPI and the DMA stream
+    pub fn free(self) -> (Spi<SPI, PINS>, DmaStream<DMA>) {
+        (self.spi, self.stream)
+    }
+}
+
+impl<SPI, PINS, DMA> RxTransfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+    DMA: DmaInstance,
+{
+    /// Returns `Ok` once the transfer is complete: the SPI has
+    /// received all of the frames (EOT), and the DMA stream has
+    /// transferred them to the buffer (TC). The SPI's DMA requests are
+    /// then disabled. Returns `WouldBlock` until then.
+    ///
+    /// Returns `Error::Dma` on a DMA transfer error, see
+    /// `Transfer::poll`.
+    pub fn poll(&self) -> nb::Result<(), Error> {
+        self.rx.stream.check_error()?;
+        // Completed by a previous call
+        if self.rx.spi.spi.cfg1.read().rxdmaen().bit_is_clear() {
+            return Ok(());
+        }
+
+        // The DMA stream drains the receive FIFO
+        resume_rx(&self.rx.spi.spi);
+        let tc = self.rx.stream.flags() & DMA_TCIF != 0;
+        let eot = self.rx.spi.spi.sr.read().eot().bit_is_set();
+
+        if tc && eot {
+            disable_dma_requests(&self.rx.spi.spi);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Wait for the transfer to complete (EOT), then return the SPI,
+    /// the filled buffer and the DMA stream. The SPI's DMA requests
+    /// are disabled and its communication mode is restored.
+    ///
+    /// On a DMA transfer error the transfer is stopped, and the error
+    /// is returned together with the SPI, the buffer and the DMA
+    /// stream, see `Transfer::wait`.
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        self,
+    ) -> Result<
+        (Spi<SPI, PINS>, &'static mut [u8], DmaStream<DMA>),
+        (Error, Spi<SPI, PINS>, &'static mut [u8], DmaStream<DMA>),
+    > {
+        let result = nb::block!(self.poll());
+
+        let RxTransfer { mut rx, buf } = self;
+        rx.stream.stop();
+        rx.stream.clear_flags();
+        end_dma(&rx.spi.spi, rx.comm);
+
+        let (spi, stream) = rx.free();
+        match result {
+            Ok(()) => Ok((spi, buf, stream)),
+            Err(e) => Err((e, spi, buf, stream)),
+        }
+    }
+}
+
+impl<SPI, PINS, DMA> CircularTransfer<SPI, PINS, DMA>
+where
+    SPI: Instance,
+    DMA: DmaInstance,
+{
+    /// Returns the half of the buffer that has just been filled, and
+    /// is safe to read until the DMA stream wraps around to it again.
+    /// Returns `None` if neither half has been filled since the last
+    /// call. Clears the corresponding DMA flag.
+    ///
+    /// If both halves have been filled since the last call, data has
+    /// been overwritten, and `Second` is returned.
+    ///
+    /// Returns `Error::Dma` on a DMA transfer error, see
+    /// `Transfer::poll`. The DMA stream is then stopped, and the
+    /// capture must be stopped with `stop`.
+    pub fn readable_half(&mut self) -> Result<Option<Half>, Error> {
+        self.rx.stream.check_error()?;
+        resume_rx(&self.rx.spi.spi);
+
+        let flags = self.rx.stream.flags();
+        let half = if flags & DMA_TCIF != 0 {
+            self.rx.stream.clear(DMA_TCIF | DMA_HTIF);
+            Some(Half::Second)
+        } else if flags & DMA_HTIF != 0 {
+            self.rx.stream.clear(DMA_HTIF);
+            Some(Half::First)
+        } else {
+            None
+        };
+
+        // The half was written by the DMA stream before its flag
+        atomic::compiler_fence(Ordering::Acquire);
+
+        Ok(half)
+    }
+
+    /// Resume the capture if the master clock was suspended because
+    /// the receive FIFO was full, see `Config::master_rx_auto_suspend`.
+    ///
+    /// Once suspended, no more frames are received, so the DMA stream
+    /// may never reach the next half transfer or transfer complete
+    /// interrupt. With auto-suspend, call this periodically, for
+    /// example from a timer interrupt or the idle loop, in addition to
+    /// `readable_half`.
+    ///
+    /// Returns `Error::Dma` on a DMA transfer error, see
+    /// `readable_half`.
+    pub fn poll(&mut self) -> Result<(), Error> {
+        self.rx.stream.check_error()?;
+        resume_rx(&self.rx.spi.spi);
+
+        Ok(())
+    }
+
+    /// Returns the given half of the buffer. Only the half returned by
+    /// `readable_half` is safe to read, the other half is being written
+    /// by the DMA stream.
+    pub fn half(&self, half: Half) -> &[u8] {
+        let (first, second) = self.buf.split_at(self.buf.len() / 2);
+        match half {
+            Half::First => first,
+            Half::Second => second,
+        }
+    }
+
+    /// Stop receiving, then return the SPI, the buffer and the DMA
+    /// stream. The SPI's DMA requests are disabled and its
+    /// communication mode is restored. Any DMA transfer error is
+    /// cleared.
+    pub fn stop(
+        self,
+    ) -> (Spi<SPI, PINS>, &'static mut [u8], DmaStream<DMA>) {
+        let CircularTransfer { mut rx, buf } = self;
+
+        // Stop the master clock at the next frame boundary
+        let spi = &rx.spi.spi;
+        spi.cr1.modify(|_, w| w.csusp().set_bit());
+        while spi.sr.read().susp().bit_is_clear() {}
+        spi.ifcr.write(|w| w.suspc().set_bit());
+
+        rx.stream.stop();
+        rx.stream.clear_flags();
+        end_dma(&rx.spi.spi, rx.comm);
+
+        let (spi, stream) = rx.free();
+        (spi, buf, stream)
+    }
+}
+
+/// State of an interrupt driven transfer. Returned by
+/// `NonBlocking::on_interrupt`
+#[derive(Copy, Clone, Debug)]
+pub enum TransferState {
+    /// The transfer is still in progress
+    InProgress,
+    /// The transfer is complete. The buffers can be taken back with
+    /// `NonBlocking::finish`
+    Complete,
+    /// The transfer was aborted because of an error. The buffers can
+    /// be taken back with `NonBlocking::finish`
+    Error(Error),
+}
+
+/// An SPI that transfers in the background, driven by its interrupt.
+/// Created by `Spi::non_blocking`.
+///
+/// `start_transfer` starts the transfer and enables the TXP, RXP and
+/// EOT interrupts. Each time the interrupt occurs, `on_interrupt`
+/// feeds the transmit FIFO and drains the receive FIFO. Once the
+/// transfer is complete, `finish` returns the buffers.
+///
+/// This avoids the set up of a DMA stream, which is useful for short
+/// transfers. Frames must be 8 bits or less.
+///
+/// ```rust
+/// let mut spi = dp
+///     .SPI1
+///     .spi(
+///         pins,
+///         spi::MODE_0,
+///         1.mhz(),
+///         ccdr.peripheral.SPI1,
+///         &ccdr.clocks,
+///     )
+///     .non_blocking();
+/// spi.start_transfer(&COMMAND, rx_buffer)?;
+///
+/// // In the SPI1 interrupt handler
+/// if let TransferState::Complete = spi.on_interrupt() {
+///     let (tx, rx) = spi.finish().unwrap();
+/// }
+/// ```
+pub struct NonBlocking<SPI, PINS>
+where
+    SPI: Instance,
+{
+    spi: Spi<SPI, PINS>,
+    tx: &'static [u8],
+    rx: &'static mut [u8],
+    written: usize,
+    read: usize,
+    state: Option<TransferState>,
+}
+
+impl<SPI, PINS> NonBlocking<SPI, PINS>
+where
+    SPI: Instance,
+{
+    /// Start sending `tx` and receiving into `rx` in the background.
+    ///
+    /// Returns `Error::Busy` if a transfer has been started and its
+    /// buffers have not yet been taken back with `finish`, or if a
+    /// transaction is in progress on the SPI.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of `tx` and `rx` differ, or if they are
+    /// longer than 65535 bytes.
+    pub fn start_transfer(
+        &mut self,
+        tx: &'static [u8],
+        rx: &'static mut [u8],
+    ) -> Result<(), Error> {
+        assert!(tx.len() == rx.len());
+        assert!(tx.len() <= u16::MAX as usize);
+
+        let spi = &self.spi.spi;
+        if self.state.is_some() || spi.cr1.read().cstart().bit_is_set() {
+            return Err(Error::Busy);
+        }
+
+        self.tx = tx;
+        self.rx = rx;
+        self.written = 0;
+        self.read = 0;
+
+        if self.tx.is_empty() {
+            // With TSIZE zero, the transaction would not end
+            self.state = Some(TransferState::Complete);
+            return Ok(());
+        }
+        self.state = Some(TransferState::InProgress);
+
+        set_tsize(spi, self.tx.len() as u16);
+        spi.ier.modify(|_, w| {
+            w.txpie().not_masked().rxpie().not_masked().eotie().set_bit()
+        });
+        if !self.spi.slave {
+            spi.cr1.modify(|_, w| w.cstart().started());
+        }
+
+        Ok(())
+    }
+
+    /// Feed the transmit FIFO and drain the receive FIFO. This must be
+    /// called from the interrupt handler of the SPI, see
+    /// `Spi::interrupt`.
+    ///
+    /// On completion or error, the interrupts enabled by
+    /// `start_transfer` are disabled again. When no transfer has been
+    /// started, this returns `TransferState::Complete`.
+    pub fn on_interrupt(&mut self) -> TransferState {
+        match self.state {
+            Some(TransferState::InProgress) => {}
+            Some(state) => return state,
+            None => return TransferState::Complete,
+        }
+
+        let state = self.service();
+        if !matches!(state, TransferState::InProgress) {
+            self.end(state);
+        }
+
+        state
+    }
+
+    /// Return the buffers once the transfer is complete or has been
+    /// aborted by an error. Returns `None` while the transfer is in
+    /// progress, or if no transfer has been started.
+    pub fn finish(&mut self) -> Option<(&'static [u8], &'static mut [u8])> {
+        match self.state {
+            None | Some(TransferState::InProgress) => None,
+            Some(_) => {
+                self.state = None;
+                let rx = mem::take(&mut self.rx);
+                Some((mem::take(&mut self.tx), rx))
+            }
+        }
+    }
+
+    /// Release the SPI
+    ///
+    /// # Panics
+    ///
+    /// Panics if a transfer is in progress.
+    pub fn free(self) -> Spi<SPI, PINS> {
+        assert!(!matches!(self.state, Some(TransferState::InProgress)));
+
+        self.spi
+    }
+
+    /// Move data between the buffers and the FIFOs
+    fn service(&mut self) -> TransferState {
+        let spi = &self.spi.spi;
+        let sr = spi.sr.read();
+
+        if let Some(e) = StatusFlags(sr.bits()).error() {
+            return TransferState::Error(e);
+        } else if sr.udr().bit_is_set() {
+            return TransferState::Error(Error::Underrun);
+        }
+
+        let rxdr = &spi.rxdr as *const _ as *const u8;
+        while self.read < self.rx.len() {
+            let sr = spi.sr.read();
+            if sr.rxp().is_empty() && sr.rxplvl().bits() == 0 {
+                break;
+            }
+            // NOTE(read_volatile) read only 1 byte (the svd2rust API
+            // only allows reading a half-word)
+            let byte = unsafe { ptr::read_volatile(rxdr) };
+            self.rx[self.read] =
+                if self.spi.invert_miso { !byte } else { byte };
+            self.read += 1;
+        }
+
+        let txdr = ptr::addr_of!(spi.txdr) as *mut u8;
+        while self.written < self.tx.len()
+            && spi.sr.read().txp().is_not_full()
+        {
+            let byte = self.tx[self.written];
+            let byte = if self.spi.invert_mosi { !byte } else { byte };
+            // NOTE(write_volatile) see note above
+            unsafe { ptr::write_volatile(txdr, byte) }
+            self.written += 1;
+        }
+        if self.written == self.tx.len() {
+            // TXP stays set once TSIZE words have been written
+            spi.ier.modify(|_, w| w.txpie().masked());
+        }
+
+        if self.read == self.rx.len() && spi.sr.read().eot().bit_is_set() {
+            TransferState::Complete
+        } else {
+            TransferState::InProgress
+        }
+    }
+
+    /// Disable the interrupts and clear the flags set by the transfer,
+    /// then record `state`
+    fn end(&mut self, state: TransferState) {
+        let spi = &self.spi.spi;
+        spi.ier.modify(|_, w| {
+            w.txpie().masked().rxpie().masked().eotie().clear_bit()
+        });
+        spi.ifcr.write(|w| {
+            w.eotc()
+                .set_bit()
+                .txtfc()
+                .set_bit()
+                .ovrc()
+                .set_bit()
+                .modfc()
+                .set_bit()
+                .crcec()
+                .set_bit()
+                .udrc()
+                .set_bit()
+        });
+        if let TransferState::Error(_) = state {
+            // Abort the transaction and flush the FIFOs
+            spi.cr1.modify(|_, w| w.spe().disabled());
+            spi.cr1.modify(|_, w| w.spe().enabled());
+        }
+        set_tsize(spi, 0);
+
+        self.state = Some(state);
+    }
+}
+
+// DMA1 and DMA2 are connected to DMAMUX1, which serves SPI1 - SPI5
+macro_rules! spi_dma {
+	($($SPIX:ident,)+) => {
+	    $(
+            impl<PINS> Spi<$SPIX, PINS> {
+                /// Pair this SPI with a DMA stream for transmitting.
+                /// The stream's DMAMUX request line is set to the
+                /// transmit request of this SPI.
+                pub fn dma_tx<DMA: DmaInstance>(
+                    self,
+                    mut stream: DmaStream<DMA>,
+                ) -> TxDma<$SPIX, PINS, DMA> {
+                    stream.select_request($SPIX::DMA_TX_REQUEST);
+
+                    TxDma {
+                        comm: read_comm(&self.spi.cfg2.read()),
+                        spi: self,
+                        stream,
+                    }
+                }
+
+                /// Pair this SPI with a DMA stream for receiving. The
+                /// stream's DMAMUX request line is set to the receive
+                /// request of this SPI.
+                pub fn dma_rx<DMA: DmaInstance>(
+                    self,
+                    mut stream: DmaStream<DMA>,
+                ) -> RxDma<$SPIX, PINS, DMA> {
+                    stream.select_request($SPIX::DMA_RX_REQUEST);
+
+                    RxDma {
+                        comm: read_comm(&self.spi.cfg2.read()),
+                        spi: self,
+                        stream,
+                    }
+                }
+            }
+        )+
+    }
+}
+
+spi_dma! {
+    SPI1, SPI2, SPI3, SPI4, SPI5,
+}
+
+impl<SPI: Instance, PINS> Spi<SPI, PINS> {
+    /// Construct the SPI, checking the pins against `config`. See
+    /// `SpiExt::spi`
+    fn new<T, CONFIG>(
+        spi: SPI,
+        pins: PINS,
+        config: CONFIG,
+        freq: T,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        clocks: &CoreClocks,
+    ) -> Self
+    where
+        PINS: Pins<SPI>,
+        T: Into<Hertz>,
+        CONFIG: Into<Config>,
+    {
+        let config: Config = config.into();
+        assert!(
+            !config.managed_cs || PINS::NSS,
+            "manage_cs requires an NSS pin: (sck, miso, mosi, nss)"
+        );
+        assert!(
+            !config.nss_input || PINS::NSS,
+            "nss_input_mode requires an NSS pin: (sck, miso, mosi, nss)"
+        );
+
+        let mut spi = Self::init(spi, pins, config, freq, prec, clocks);
+        spi.pins = configure_pins(spi.pins, &config);
+        spi
+    }
+
+    /// Initialise the SPI. The pins are not checked
+    fn init<T>(
+        spi: SPI,
+        pins: PINS,
+        config: Config,
+        freq: T,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        clocks: &CoreClocks,
+    ) -> Self
+    where
+        T: Into<Hertz>,
+    {
+        let result = Self::try_init(spi, pins, config, freq, prec, clocks);
+        Self::expect(result)
+    }
+
+    /// Initialise the SPI, returning an error if the kernel
+    /// clock is not running or `config` is not supported.
+    /// The pins are not checked
+    fn try_init<T>(
+        spi: SPI,
+        pins: PINS,
+        config: Config,
+        freq: T,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        clocks: &CoreClocks,
+    ) -> Result<Self, (Error, SPI, PINS)>
+    where
+        T: Into<Hertz>,
+    {
+        let spi_freq = freq.into().0;
+        let i2s_ckin = config.i2s_ckin;
+        let divider = config.divider;
+
+        let c_ck = clocks.c_ck().0;
+        Self::configure(spi, pins, config, prec, c_ck, || {
+            Self::baud(spi_freq, i2s_ckin, divider, clocks)
+        })
+    }
+
+    /// Unwrap the result of initialising the SPI
+    fn expect(result: Result<Self, (Error, SPI, PINS)>) -> Self {
+        match result {
+            Ok(spi) => spi,
+            Err((e, _, _)) => panic!(
+                "{} initialisation failed: {:?}",
+                any::type_name::<SPI>(),
+                e
+            ),
+        }
+    }
+
+    /// Initialise the SPI with a master baud rate divider
+    /// computed at compile time. The pins are not checked
+    fn init_const<const KERNEL_HZ: u32, const TARGET_HZ: u32>(
+        spi: SPI,
+        pins: PINS,
+        config: Config,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        clocks: &CoreClocks,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        let i2s_ckin = config.i2s_ckin;
+        let c_ck = clocks.c_ck().0;
+        let result = Self::configure(spi, pins, config, prec, c_ck, || {
+            // The kernel clock is selected by now. Only checked in
+            // debug builds, release builds use KERNEL_HZ as is
+            #[cfg(debug_assertions)]
+            {
+                let spi_ker_ck = SPI::kernel_clk_ckin(clocks, i2s_ckin);
+                assert!(
+                    spi_ker_ck.map(|ck| ck.0) == Some(KERNEL_HZ),
+                    "{} kernel clock does not match KERNEL_HZ",
+                    any::type_name::<SPI>()
+                );
+            }
+            Ok((KERNEL_HZ, ConstMbr::<KERNEL_HZ, TARGET_HZ>::MBR))
+        });
+        Self::expect(result)
+    }
+
+    /// Configure the SPI. `baud` is called once the kernel
+    /// clock is selected, and returns the kernel clock
+    /// frequency and the master baud rate divider. The
+    /// software CS delays are counted in cycles of the core
+    /// clock `c_ck`. The peripheral is not enabled if an
+    /// error is returned, and it is returned with the pins
+    fn configure<F>(
+        spi: SPI,
+        pins: PINS,
+        config: Config,
+        prec: <SPI as SpiExt<SPI>>::Rec,
+        c_ck: u32,
+        baud: F,
+    ) -> Result<Self, (Error, SPI, PINS)>
+    where
+        F: FnOnce() -> Result<(u32, MBR), Error>,
+    {
+        if let Err(e) = config.validate::<SPI>() {
+            return Err((Error::Config(e), spi, pins));
+        }
+
+        let strict_pins = config.strict_pins.filter(|_| cfg!(debug_assertions));
+        if let Some(locations) = strict_pins {
+            if let Err(e) = check_pins::<SPI>(&locations) {
+                return Err((Error::Config(e), spi, pins));
+            }
+        }
+
+        let (spi_ker_ck, mbr) = match baud() {
+            Ok(baud) => baud,
+            Err(e) => return Err((e, spi, pins)),
+        };
+
+        // Enable and reset SPI, clearing any state left by
+        // a bootloader or a previous firmware image
+        let rec = prec.enable().reset();
+
+        Self::write_config(&spi, &config, spi_ker_ck, mbr);
+
+        Ok(Spi {
+            spi,
+            pins,
+            rec,
+            invert_mosi: config.invert_mosi,
+            invert_miso: config.invert_miso,
+            managed_cs: config.managed_cs,
+            cs_delay: core_delay_cycles(config.cs_delay_ns, c_ck),
+            cs_hold: core_delay_cycles(config.cs_hold_ns, c_ck),
+            sck: Hertz(spi_ker_ck / (2 << mbr as u32)),
+            slave: config.slave,
+            i2s_ckin: config.i2s_ckin,
+            #[cfg(feature = "trace")]
+            trace: None,
+            _word: PhantomData,
+        })
+    }
+}
+
+impl<SPI: Instance, PINS, WORD> Spi<SPI, PINS, WORD> {
+    /// Returns the kernel clock frequency and the master
+    /// baud rate divider for an SCK frequency of
+    /// `spi_freq`, or the fixed `divider` if any
+    fn baud(
+        spi_freq: u32,
+        i2s_ckin: Option<Hertz>,
+        divider: Option<Divider>,
+        clocks: &CoreClocks,
+    ) -> Result<(u32, MBR), Error> {
+        let spi_ker_ck = SPI::kernel_clk_ckin(clocks, i2s_ckin);
+        baud_for(spi_ker_ck, spi_freq, divider)
+    }
+
+    /// Write `config` and the master baud rate divider
+    /// `mbr` to the peripheral, then enable it. The delays
+    /// are converted to cycles of the SCK derived from
+    /// `spi_ker_ck`
+    fn write_config(spi: &SPI, config: &Config, spi_ker_ck: u32, mbr: MBR) {
+        let mode = config.mode;
+        let sck = spi_ker_ck / (2 << mbr as u32);
+        let midi = delay_cycles(config.inter_word_delay_ns, sck);
+        // A software managed CS is delayed by the driver
+        let mssi = if config.managed_cs {
+            delay_cycles(config.cs_delay_ns, sck)
+        } else {
+            0
+        };
+
+        // CFG1 and CFG2 can only be written while disabled
+        spi.cr1.modify(|_, w| w.spe().disabled());
+
+        // Disable SS output
+        spi.cfg2.write(|w| w.ssoe().disabled());
+
+        let (udrcfg, udr_pattern) = match config.underrun_data {
+            UnderrunData::Pattern(pattern) => (0b00, pattern),
+            UnderrunData::LastReceived => (0b01, 0),
+            UnderrunData::LastTransmitted => (0b10, 0),
+        };
+        let udrdet = match config.underrun_detection {
+            UnderrunDetection::BeginningOfFrame => 0b00,
+            UnderrunDetection::EndOfFrame => 0b01,
+            UnderrunDetection::BeginningOfNss => 0b10,
+        };
+        spi.cfg1.modify(|_, w| unsafe {
+            w.mbr()
+                .variant(mbr) // master baud rate
+                .dsize()
+                .bits(config.frame_size - 1) // frame size
+                .udrcfg()
+                .bits(udrcfg) // slave underrun data
+                .udrdet()
+                .bits(udrdet) // slave underrun detection
+                .fthlv()
+                .bits(config.fifo_threshold - 1) // FIFO threshold
+        });
+        spi.udrdr.write(|w| unsafe { w.bits(udr_pattern) });
+
+        // CRC. The highest order term of the polynomial is
+        // set in CRCPOLY, unless it does not fit, in which
+        // case it is provided by CRC33_17 below
+        match config.crc {
+            Some(crc) => {
+                let full = crc.size_bits == SPI::MAX_FRAME_SIZE;
+                let poly = if full {
+                    crc.polynomial
+                } else {
+                    crc.polynomial | (1 << crc.size_bits)
+                };
+                spi.crcpoly.write(|w| unsafe { w.bits(poly) });
+                spi.cfg1.modify(|_, w| {
+                    w.crcsize().bits(crc.size_bits - 1).crcen().enabled()
+                });
+            }
+            None => spi.cfg1.modify(|_, w| w.crcen().disabled()),
+        }
+
+        // ssi: internal slave select. Not selected in
+        // master mode, and always selected in slave mode
+        // unless NSS is managed by hardware
+        // crc33_17: highest order term of the CRC polynomial
+        // masrx: suspend when the receive FIFO is full
+        let crc33_17 = matches!(
+            config.crc,
+            Some(crc) if crc.size_bits == SPI::MAX_FRAME_SIZE
+        );
+        spi.cr1.write(|w| {
+            w.ssi()
+                .bit(!config.slave)
+                .crc33_17()
+                .bit(crc33_17)
+                .masrx()
+                .bit(config.master_rx_auto_suspend && !config.slave)
+        });
+
+        // mstr: master configuration, unless slave
+        // lsbfrst: bit order
+        // sp: frame format
+        // ssm: enable software slave management (NSS pin
+        // free for other uses), unless CS is managed by
+        // hardware or NSS is sensed as an input
+        // ssoe: enable SS output if CS is managed by
+        // hardware in master mode. In slave mode, NSS is
+        // then an input
+        // comm: communication mode
+        // afcntr: keep control of the pins while disabled
+        // ioswp: swap the MOSI and MISO pins
+        // midi, mssi: inter-data and CS to data idleness
+        // ssiop, ssom: CS polarity and assertion mode
+        spi.cfg2.write(|w| {
+            let w = w.midi().bits(midi).mssi().bits(mssi);
+            let w = w
+                .cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                .master()
+                .bit(!config.slave)
+                .lsbfrst()
+                .bit(config.bit_order == BitOrder::LsbFirst)
+                .sp()
+                .variant(match config.frame_format {
+                    FrameFormat::Motorola => cfg2::SP_A::MOTOROLA,
+                    FrameFormat::TI => cfg2::SP_A::TI,
+                })
+                .ssm()
+                .bit(!config.managed_cs && !config.nss_input)
+                .ssoe()
+                .bit(config.managed_cs && !config.slave && !config.nss_input)
+                .ssiop()
+                .bit(config.cs_polarity == CsPolarity::ActiveHigh)
+                .ssom()
+                .bit(config.cs_mode == CsMode::PulsedBetweenWords)
+                .afcntr()
+                .bit(config.keep_io_state)
+                .ioswp()
+                .bit(config.swap_mosi_miso);
+            comm(w, config.communication_mode)
+        });
+
+        // spe: enable the SPI bus
+        spi.cr1.modify(|_, w| w.spe().enabled());
+    }
+
+    /// Return `Error::Busy` if a transfer is in progress
+    fn check_idle(&self) -> Result<(), Error> {
+        if self.spi.sr.read().txc().is_ongoing() {
+            Err(Error::Busy)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Change the SCK frequency. The divider is recomputed
+    /// from the current kernel clock.
+    ///
+    /// The inter-word and CS to data delays keep their
+    /// current number of SCK cycles. Use `reconfigure` to
+    /// recompute them for the new frequency.
+    ///
+    /// Returns `Error::Busy`, without changing the
+    /// frequency, if a transfer is in progress.
+    pub fn set_frequency<T>(
+        &mut self,
+        freq: T,
+        clocks: &CoreClocks,
+    ) -> Result<(), Error>
+    where
+        T: Into<Hertz>,
+    {
+        self.check_idle()?;
+
+        let (spi_ker_ck, mbr) =
+            Self::baud(freq.into().0, self.i2s_ckin, None, clocks)?;
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi.cfg1.modify(|_, w| w.mbr().variant(mbr));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+        self.sck = Hertz(spi_ker_ck / (2 << mbr as u32));
+
+        Ok(())
+    }
+
+    /// Change the SPI mode (clock polarity and phase).
+    ///
+    /// Returns `Error::Busy`, without changing the mode, if
+    /// a transfer is in progress.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        self.check_idle()?;
+
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi.cfg2.modify(|_, w| {
+            w.cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+        });
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+
+        Ok(())
+    }
+
+    /// Change the order in which the bits of each frame are
+    /// transmitted.
+    ///
+    /// Returns `Error::Busy`, without changing the bit
+    /// order, if a transfer is in progress. LSB first is
+    /// not supported by the TI frame format.
+    pub fn set_bit_order(&mut self, order: BitOrder) -> Result<(), Error> {
+        self.check_idle()?;
+        if order != BitOrder::MsbFirst && self.spi.cfg2.read().sp().is_ti() {
+            return Err(Error::Config(ConfigError::TiFrameFormat));
+        }
+
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi
+            .cfg2
+            .modify(|_, w| w.lsbfrst().bit(order == BitOrder::LsbFirst));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+
+        Ok(())
+    }
+
+    /// Apply a complete new configuration and SCK
+    /// frequency, as if the SPI had been constructed with
+    /// them. The pins are not checked.
+    ///
+    /// Returns `Error::Busy`, without changing the
+    /// configuration, if a transfer is in progress.
+    pub fn reconfigure<T, CONFIG>(
+        &mut self,
+        config: CONFIG,
+        freq: T,
+        clocks: &CoreClocks,
+    ) -> Result<(), Error>
+    where
+        T: Into<Hertz>,
+        CONFIG: Into<Config>,
+    {
+        self.check_idle()?;
+
+        // The pins are unchanged, so they stay swapped
+        let mut config: Config = config.into();
+        config.swap_mosi_miso = self.spi.cfg2.read().ioswp().bit_is_set();
+        config.validate::<SPI>().map_err(Error::Config)?;
+        let (spi_ker_ck, mbr) =
+            Self::baud(freq.into().0, config.i2s_ckin, config.divider, clocks)?;
+
+        Self::write_config(&self.spi, &config, spi_ker_ck, mbr);
+
+        self.invert_mosi = config.invert_mosi;
+        self.invert_miso = config.invert_miso;
+        self.managed_cs = config.managed_cs;
+        self.cs_delay = core_delay_cycles(config.cs_delay_ns, clocks.c_ck().0);
+        self.cs_hold = core_delay_cycles(config.cs_hold_ns, clocks.c_ck().0);
+        self.sck = Hertz(spi_ker_ck / (2 << mbr as u32));
+        self.slave = config.slave;
+        self.i2s_ckin = config.i2s_ckin;
+
+        Ok(())
+    }
+
+    /// Enable interrupts for the given `event`. See
+    /// `Event`
+    pub fn listen(&mut self, event: Event) {
+        self.set_interrupt(event, true);
+    }
+
+    /// Disable interrupts for the given `event`. See
+    /// `Event`
+    pub fn unlisten(&mut self, event: Event) {
+        self.set_interrupt(event, false);
+    }
+
+    /// Set or clear the interrupt enable bits for `event`
+    fn set_interrupt(&mut self, event: Event, enable: bool) {
+        self.spi.ier.modify(|_, w| match event {
+            Event::Rxp => w.rxpie().bit(enable),
+            Event::Txp => w.txpie().bit(enable),
+            Event::Dxp => w.dxpie().bit(enable),
+            Event::Txtf => w.txtfie().bit(enable),
+            Event::Eot | Event::Suspend | Event::Txc => w.eotie().bit(enable),
+            Event::TserfReload => w.tserfie().bit(enable),
+            Event::Underrun => w.udrie().bit(enable),
+            Event::Overrun => w.ovrie().bit(enable),
+            Event::CrcError => w.crceie().bit(enable),
+            Event::ModeFault => w.modfie().bit(enable),
+            Event::FrameError => w.tifreie().bit(enable),
+            Event::Error => w
+                .udrie() // Underrun
+                .bit(enable)
+                .ovrie() // Overrun
+                .bit(enable)
+                .crceie() // CRC error
+                .bit(enable)
+                .modfie() // Mode fault
+                .bit(enable),
+        });
+    }
+
+    /// Return `true` if interrupts are enabled for the
+    /// given `event`. For `Event::Error`, all of the error
+    /// interrupts enabled by `listen` must be enabled.
+    pub fn is_listening(&self, event: Event) -> bool {
+        let ier = self.spi.ier.read();
+
+        match event {
+            Event::Rxp => ier.rxpie().is_not_masked(),
+            Event::Txp => ier.txpie().is_not_masked(),
+            Event::Dxp => ier.dxpie().bit_is_set(),
+            Event::Txtf => ier.txtfie().bit_is_set(),
+            Event::Eot | Event::Suspend | Event::Txc => {
+                ier.eotie().bit_is_set()
+            }
+            Event::TserfReload => ier.tserfie().bit_is_set(),
+            Event::Underrun => ier.udrie().is_not_masked(),
+            Event::Overrun => ier.ovrie().is_not_masked(),
+            Event::CrcError => ier.crceie().is_not_masked(),
+            Event::ModeFault => ier.modfie().is_not_masked(),
+            Event::FrameError => ier.tifreie().bit_is_set(),
+            Event::Error => {
+                ier.udrie().is_not_masked()
+                    && ier.ovrie().is_not_masked()
+                    && ier.crceie().is_not_masked()
+                    && ier.modfie().is_not_masked()
+            }
+        }
+    }
+
+    /// Returns the CRC calculated over the transmitted
+    /// frames. See `Config::hardware_crc`
+    pub fn tx_crc(&self) -> u32 {
+        self.spi.txcrc.read().bits()
+    }
+
+    /// Returns the CRC calculated over the received
+    /// frames. See `Config::hardware_crc`
+    pub fn rx_crc(&self) -> u32 {
+        self.spi.rxcrc.read().bits()
+    }
+
+    /// Clear the CRC error flag, so that the bus can be used
+    /// again after `Error::Crc` without reinitialising it
+    pub fn clear_crc_error(&mut self) {
+        self.spi.ifcr.write(|w| w.crcec().set_bit());
+    }
+
+    /// Returns the interrupt line for this SPI, for example
+    /// to unmask it in the NVIC
+    pub fn interrupt(&self) -> Interrupt {
+        SPI::INTERRUPT
+    }
+
+    /// Returns the transfer size (TSIZE) currently programmed
+    /// in hardware. Zero indicates an endless transfer.
+    pub fn transfer_size(&self) -> u16 {
+        self.spi.cr2.read().tsize().bits()
+    }
+
+    /// Returns the frame size in bits currently configured
+    /// in hardware
+    pub fn frame_size(&self) -> u8 {
+        self.spi.cfg1.read().dsize().bits() + 1
+    }
+
+    /// Change the word type exchanged by the SPI, so that
+    /// `FullDuplex` and the blocking traits are only
+    /// implemented for words of that type.
+    ///
+    /// The SPI is constructed with `u8` words, and
+    /// `FullDuplex` returns `ConfigError::WordSize` while the
+    /// frame size is not held by the word type, for example
+    /// after `Config::frame_size(12)`. If the frame size is
+    /// not held by `W` without wasting a larger word type,
+    /// it is changed to the size of `W`:
+    ///
+    /// * `u8`: 4 to 8 bits
+    /// * `u16`: 9 to 16 bits
+    /// * `u32`: 17 to 32 bits, on SPI1, SPI2 and SPI3 only
+    ///
+    /// ```rust
+    /// let config = spi::Config::new(spi::MODE_0).frame_size(12);
+    /// let mut spi: Spi<_, _, u16> = dp
+    ///     .SPI1
+    ///     .spi(
+    ///         pins,
+    ///         config,
+    ///         1.mhz(),
+    ///         ccdr.peripheral.SPI1,
+    ///         &ccdr.clocks,
+    ///     )
+    ///     .change_word_size();
+    /// spi.send(0xABCu16)?;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `W` is larger than the largest frame size of
+    /// this instance.
+    pub fn change_word_size<W: Word>(mut self) -> Spi<SPI, PINS, W> {
+        assert!(
+            W::BITS <= SPI::MAX_FRAME_SIZE,
+            "Word type is larger than the largest frame size"
+        );
+        if !(W::MIN_BITS..=W::BITS).contains(&self.frame_size()) {
+            self.set_dsize(W::BITS - 1);
+        }
+
+        let Spi {
+            spi,
+            pins,
+            rec,
+            invert_mosi,
+            invert_miso,
+            managed_cs,
+            cs_delay,
+            cs_hold,
+            sck,
+            slave,
+            i2s_ckin,
+            #[cfg(feature = "trace")]
+            trace,
+            _word,
+        } = self;
+
+        Spi {
+            spi,
+            pins,
+            rec,
+            invert_mosi,
+            invert_miso,
+            managed_cs,
+            cs_delay,
+            cs_hold,
+            sck,
+            slave,
+            i2s_ckin,
+            #[cfg(feature = "trace")]
+            trace,
+            _word: PhantomData,
+        }
+    }
+
+    /// Returns the SPI mode currently configured in hardware
+    pub fn mode(&self) -> Mode {
+        let cfg2 = self.spi.cfg2.read();
+
+        Mode {
+            polarity: if cfg2.cpol().bit_is_set() {
+                Polarity::IdleHigh
+            } else {
+                Polarity::IdleLow
+            },
+            phase: if cfg2.cpha().bit_is_set() {
+                Phase::CaptureOnSecondTransition
+            } else {
+                Phase::CaptureOnFirstTransition
+            },
+        }
+    }
+
+    /// Return `true` if SCK idles high, i.e. CPOL is set
+    /// in hardware
+    pub fn sck_idle_high(&self) -> bool {
+        self.spi.cfg2.read().cpol().bit_is_set()
+    }
+
+    /// Return the edge of SCK on which data is sampled, as
+    /// configured in hardware.
+    ///
+    /// With CPHA clear data is sampled on the first edge
+    /// of each clock pulse, and with CPHA set on the
+    /// second. Whether that edge is rising or falling
+    /// depends on CPOL. As a master, this SPI samples MISO
+    /// on this edge and changes MOSI on the opposite edge,
+    /// so MOSI is valid for half an SCK period either side
+    /// of the sampling edge.
+    ///
+    /// Note: The SPI peripheral has no setting to delay or
+    /// advance the sampling point, so there are no setup or
+    /// hold guard bands to configure. The idle times set
+    /// by MSSI and MIDI are only inserted before and
+    /// between frames. To increase the margin around the
+    /// sampling edge, reduce the SCK frequency.
+    pub fn sampling_edge(&self) -> SamplingEdge {
+        let cfg2 = self.spi.cfg2.read();
+
+        if cfg2.cpol().bit_is_set() == cfg2.cpha().bit_is_set() {
+            SamplingEdge::Rising
+        } else {
+            SamplingEdge::Falling
+        }
+    }
+
+    /// Full duplex transfer of `words` using a frame size of
+    /// `BITS` for this transfer only.
+    ///
+    /// The peripheral is briefly disabled to change the
+    /// frame size, both before and after the transfer. The
+    /// previous frame size is restored afterwards, even if
+    /// the transfer failed.
+    ///
+    /// ```rust
+    /// // Transfer 12-bit frames
+    /// spi.transfer_framed::<12>(&mut words)?;
+    /// ```
+    pub fn transfer_framed<const BITS: u8>(
+        &mut self,
+        words: &mut [u16],
+    ) -> Result<(), Error>
+    where
+        FrameSize<BITS>: HalfWordFrame,
+    {
+        let dsize = self.spi.cfg1.read().dsize().bits();
+        self.set_dsize(BITS - 1);
+
+        let mut result = Ok(());
+        for word in words.iter_mut() {
+            if let Err(e) = nb::block!(self.send_halfword(*word))
+                .and_then(|_| nb::block!(self.read_halfword()))
+                .map(|w| *word = w)
+            {
+                result = Err(e);
+                break;
+            }
+        }
+
+        self.set_dsize(dsize);
+        result
+    }
+
+    /// Send all of `tx`, recording each word received at the
+    /// same time into `log` until `log` is full.
+    ///
+    /// Unlike a symmetric transfer, `log` may be shorter
+    /// than `tx`: words received once `log` is full are
+    /// read and discarded. Returns the number of words
+    /// recorded in `log`.
+    pub fn transfer_logged(
+        &mut self,
+        tx: &[u8],
+        log: &mut [u8],
+    ) -> Result<usize, Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        let mut logged = 0;
+
+        for word in tx {
+            nb::block!(self.send(*word))?;
+            let received: u8 = nb::block!(self.read())?;
+
+            if let Some(slot) = log.get_mut(logged) {
+                *slot = received;
+                logged += 1;
+            }
+        }
+
+        Ok(logged)
+    }
+
+    /// Clock the dummy bytes required to power up an SD card
+    /// in SPI mode.
+    ///
+    /// At least 74 clock cycles are required with MOSI and
+    /// CS held high. This sends 10 bytes of `0xFF` (80
+    /// clock cycles). CS must be deasserted by the caller
+    /// beforehand. See `Config::sd_card_init`.
+    pub fn sd_card_power_up(&mut self) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        for _ in 0..10 {
+            nb::block!(self.send(0xFFu8))?;
+            let _: u8 = nb::block!(self.read())?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that the hardware managed chip select actually
+    /// toggles, by sampling the level of the NSS pin before,
+    /// during and after a dummy transaction.
+    ///
+    /// The NSS pin is found by looking for a pin from
+    /// `PinTable::NSS` that is configured with the right
+    /// alternate function, and its level is read from the
+    /// GPIO input data register. With `Config::manage_cs`,
+    /// NSS stays active until the peripheral is disabled, so
+    /// it is sampled once the dummy word has been received
+    /// and released with `end_transaction`.
+    ///
+    /// This is intended as a startup self-check, and should
+    /// be called before any other transaction on the bus.
+    /// Returns `Error::ChipSelect` if no NSS pin is
+    /// configured or the pin did not move, which usually
+    /// indicates that the NSS pin has the wrong alternate
+    /// function, or `ConfigError::ManagedCs` if
+    /// `Config::manage_cs` was not set.
+    pub fn verify_cs_toggles(&mut self) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        if !self.managed_cs {
+            return Err(Error::Config(ConfigError::ManagedCs));
+        }
+
+        let (port, id) = nss_location::<SPI>().ok_or(Error::ChipSelect)?;
+        let nss_high =
+            || gpio::port_block(port).idr.read().bits() & (1 << id) != 0;
+
+        let idle_high = nss_high();
+
+        nb::block!(self.send(0u8))?;
+        let _: u8 = nb::block!(self.read())?;
+        let active_low = !nss_high();
+
+        self.end_transaction();
+        let released_high = nss_high();
+
+        if idle_high && active_low && released_high {
+            Ok(())
+        } else {
+            Err(Error::ChipSelect)
+        }
+    }
+
+    /// Returns the actual SCK frequency.
+    ///
+    /// The master baud rate divider is the power of two
+    /// nearest to the ratio of kernel clock to requested
+    /// frequency, so this is between 0.75 and 1.5 times the
+    /// requested frequency. It is updated by `set_frequency`.
+    ///
+    /// ```rust
+    /// // 3.125 MHz from a 100 MHz kernel clock
+    /// let spi = dp.SPI1.spi(
+    ///     pins,
+    ///     spi::MODE_0,
+    ///     3.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    /// let sck: Hertz = spi.clock();
+    /// ```
+    pub fn clock(&self) -> Hertz {
+        self.sck
+    }
+
+    /// Returns the master baud rate divider currently
+    /// configured in hardware
+    pub fn divider(&self) -> Divider {
+        self.spi.cfg1.read().mbr().variant().into()
+    }
+
+    /// Returns the frequency of the kernel clock currently
+    /// selected for this instance, or `None` if that clock
+    /// is not running. The kernel clock is selected with
+    /// `ccdr.peripheral_clocks`, see `PeripheralClocks`
+    pub fn kernel_clock(clocks: &CoreClocks) -> Option<Hertz> {
+        SPI::kernel_clk(clocks)
+    }
+
+    /// Returns the idle time inserted between consecutive
+    /// frames, in SCK cycles. See
+    /// `Config::inter_word_delay`.
+    pub fn inter_word_delay_cycles(&self) -> u8 {
+        self.spi.cfg2.read().midi().bits()
+    }
+
+    /// Returns the delay between the assertion of a
+    /// hardware managed CS and the first clock, in SCK
+    /// cycles. See `Config::cs_delay`.
+    pub fn cs_delay_cycles(&self) -> u8 {
+        self.spi.cfg2.read().mssi().bits()
+    }
+
+    /// Returns the theoretical maximum throughput of the
+    /// bus in bytes per second.
+    ///
+    /// This is calculated from the SCK frequency, the
+    /// current frame size and the idle time inserted
+    /// between frames (MIDI). It does not include software
+    /// overhead, so it is only achievable when the FIFOs
+    /// are kept full, for example by DMA.
+    pub fn max_throughput_bps(&self) -> u32 {
+        let bits = u64::from(self.spi.cfg1.read().dsize().bits()) + 1;
+        let midi = u64::from(self.spi.cfg2.read().midi().bits());
+
+        // Each frame takes `bits` + `midi` SCK cycles
+        let sck = u64::from(self.sck.0);
+        (sck * bits / (8 * (bits + midi))) as u32
+    }
+
+    /// Full duplex transfer in place, with a chip select
+    /// pin `cs` managed in software.
+    ///
+    /// CS is driven low, then after the delay set by
+    /// `Config::cs_delay` the transfer is run. Once the
+    /// last word has been shifted out, and after the delay
+    /// set by `Config::cs_hold`, CS is driven high. CS is
+    /// driven high even if the transfer fails. If driving CS
+    /// fails, `Error::CsPin` is returned.
+    pub fn transfer_cs<P>(
+        &mut self,
+        cs: &mut P,
+        words: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        P: hal::digital::v2::OutputPin,
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        cs.set_low().map_err(|_| Error::CsPin)?;
+        if self.cs_delay > 0 {
+            cortex_m::asm::delay(self.cs_delay);
+        }
+
+        let result: Result<(), Error> = words.iter_mut().try_for_each(|word| {
+            nb::block!(self.send(*word))?;
+            *word = nb::block!(self.read())?;
+            Ok(())
+        });
+
+        while self.spi.sr.read().txc().is_ongoing() {}
+        if self.cs_hold > 0 {
+            cortex_m::asm::delay(self.cs_hold);
+        }
+        let released = cs.set_high().map_err(|_| Error::CsPin);
+
+        result.and(released)
+    }
+
+    /// Run `f` with a chip select pin `cs` managed in
+    /// software held low.
+    ///
+    /// CS is driven low, then after the delay set by
+    /// `Config::cs_delay` `f` is called. Afterwards the SPI
+    /// is flushed, so that CS is not raised while bits are
+    /// still being shifted out. After the delay set by
+    /// `Config::cs_hold`, CS is driven high, even if `f` or
+    /// the flush fails. If driving CS fails, `Error::CsPin`
+    /// is returned, and `f` is not called if CS could not be
+    /// driven low.
+    ///
+    /// ```rust
+    /// let id = spi.with_cs(&mut cs, |spi| {
+    ///     spi.write(&[0x9F])?;
+    ///     let mut id = [0; 3];
+    ///     spi.transfer(&mut id)?;
+    ///     Ok(id)
+    /// })?;
+    /// ```
+    pub fn with_cs<P, T, F>(&mut self, cs: &mut P, f: F) -> Result<T, Error>
+    where
+        P: hal::digital::v2::OutputPin,
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        cs.set_low().map_err(|_| Error::CsPin)?;
+        if self.cs_delay > 0 {
+            cortex_m::asm::delay(self.cs_delay);
+        }
+
+        let result = f(self);
+        let flushed = self.flush();
+
+        if self.cs_hold > 0 {
+            cortex_m::asm::delay(self.cs_hold);
+        }
+        let released = cs.set_high().map_err(|_| Error::CsPin);
+
+        let value = result?;
+        flushed?;
+        released.map(|_| value)
+    }
+
+    /// End the current transaction, deasserting CS if it is
+    /// managed by the SPI peripheral.
+    ///
+    /// Waits until the last word has been transmitted, then
+    /// waits for the hold time configured by
+    /// `Config::cs_hold` before deasserting CS. CS is
+    /// deasserted by briefly disabling the peripheral,
+    /// which also flushes both FIFOs.
+    pub fn end_transaction(&mut self) {
+        while self.spi.sr.read().txc().is_ongoing() {}
+
+        if self.managed_cs {
+            if self.cs_hold > 0 {
+                cortex_m::asm::delay(self.cs_hold);
+            }
+            self.spi.cr1.modify(|_, w| w.spe().disabled());
+            self.spi.cr1.modify(|_, w| w.spe().enabled());
+        }
+    }
+
+    /// Exchange a single byte, for low latency polling of a
+    /// status register.
+    ///
+    /// Writes TXDR, waits for RXP and reads RXDR with no
+    /// buffer management. Errors are only checked once the
+    /// byte has been received.
+    #[inline(always)]
+    pub fn exchange_byte(&mut self, out: u8) -> Result<u8, Error> {
+        if !self.is_enabled() {
+            return Err(Error::Disabled);
+        }
+
+        let out = if self.invert_mosi { !out } else { out };
+
+        // NOTE(write_volatile/read_volatile) write and
+        // read exactly one byte
+        unsafe {
+            ptr::write_volatile(ptr::addr_of!(self.spi.txdr) as *mut u8, out)
+        }
+        self.start_master();
+
+        loop {
+            let sr = self.spi.sr.read();
+
+            if sr.ovr().is_overrun() {
+                return Err(Error::Overrun);
+            } else if sr.modf().is_fault() {
+                return Err(Error::ModeFault);
+            } else if sr.rxp().is_not_empty() {
+                break;
+            }
+        }
+
+        let byte = unsafe {
+            ptr::read_volatile(&self.spi.rxdr as *const _ as *const u8)
+        };
+
+        Ok(if self.invert_miso { !byte } else { byte })
+    }
+
+    /// Send `tx` and capture the received data into `rx`,
+    /// for round-trip validation in production tests.
+    ///
+    /// Note: The SPI peripheral has no internal loopback
+    /// mode. `IOSWP` only swaps the functions of the MOSI
+    /// and MISO pins, and in half-duplex mode the receiver
+    /// is not active while transmitting. So MOSI must be
+    /// connected to MISO externally for `rx` to match
+    /// `tx`. The number of words transferred is the
+    /// shorter of `tx` and `rx`.
+    pub fn loopback_transfer(
+        &mut self,
+        tx: &[u8],
+        rx: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        self.transfer_split(tx, rx)
+    }
+
+    /// Full duplex transfer in place, calling `f(sent,
+    /// received)` for each word as it is received.
+    ///
+    /// This allows a running checksum to be computed, or a
+    /// sentinel to be detected, in lockstep with the
+    /// transfer. `f` is called between receiving one word
+    /// and sending the next, so it should be short to avoid
+    /// gaps on the bus.
+    pub fn transfer_with<F>(
+        &mut self,
+        words: &mut [u8],
+        mut f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u8, u8),
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        for word in words.iter_mut() {
+            let sent = *word;
+            nb::block!(self.send(sent))?;
+            *word = nb::block!(self.read())?;
+            f(sent, *word);
+        }
+
+        Ok(())
+    }
+
+    /// Full duplex transfer from `tx` into `rx`. The number
+    /// of words transferred is the shorter of the two.
+    fn transfer_split(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        for (out, received) in tx.iter().zip(rx.iter_mut()) {
+            nb::block!(self.send(*out))?;
+            *received = nb::block!(self.read())?;
+        }
+
+        Ok(())
+    }
+
+    /// Full duplex transfer of `tx` into `rx`, retrying the
+    /// whole transfer up to `max_retries` times on a
+    /// transient error.
+    ///
+    /// Overrun and CRC errors are considered transient: the
+    /// error flags are cleared, the FIFOs are flushed and
+    /// the transfer is restarted from the beginning. Other
+    /// errors, such as a mode fault, are returned
+    /// immediately. If the final attempt fails, its error
+    /// is returned.
+    ///
+    /// The data to send is taken separately from the
+    /// receive buffer, because a failed attempt has already
+    /// overwritten part of the receive buffer.
+    pub fn transfer_retry(
+        &mut self,
+        tx: &[u8],
+        rx: &mut [u8],
+        max_retries: u8,
+    ) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        let mut retries = 0;
+
+        loop {
+            match self.transfer_split(tx, rx) {
+                Err(Error::Overrun) | Err(Error::Crc)
+                    if retries < max_retries =>
+                {
+                    retries += 1;
+                    self.recover();
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Clear the overrun, mode fault, CRC error, underrun
+    /// and TI frame format error flags
+    pub fn clear_error_flags(&mut self) {
+        self.spi.ifcr.write(|w| {
+            w.ovrc()
+                .set_bit()
+                .modfc()
+                .set_bit()
+                .crcec()
+                .set_bit()
+                .udrc()
+                .set_bit()
+                .tifrec()
+                .set_bit()
+        });
+    }
+
+    /// Clear the end of transfer flag
+    pub fn clear_eot(&mut self) {
+        self.spi.ifcr.write(|w| w.eotc().set_bit());
+    }
+
+    /// Recover from an error: clear the error flags and the
+    /// end of transfer flag, and flush both FIFOs. The
+    /// peripheral is left enabled and ready for a new
+    /// transaction, even if it was disabled by a mode
+    /// fault.
+    ///
+    /// This is done automatically by the blocking
+    /// `Transfer` and `Write` implementations when they
+    /// return an error.
+    pub fn recover(&mut self) {
+        // Disabling the peripheral flushes both FIFOs
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.clear_error_flags();
+        self.spi
+            .ifcr
+            .write(|w| w.eotc().set_bit().txtfc().set_bit());
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+    }
+
+    /// Re-arm the peripheral after a mode fault, see
+    /// `Config::nss_input_mode`. A mode fault disables the
+    /// peripheral and switches it to slave mode; this
+    /// clears the MODF flag and restores master mode.
+    ///
+    /// Returns `Error::ModeFault` if NSS is still asserted
+    /// by another master, in which case the peripheral is
+    /// left disabled and this may be called again later.
+    pub fn rearm_after_mode_fault(&mut self) -> Result<(), Error> {
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.clear_modf();
+        self.spi
+            .ifcr
+            .write(|w| w.eotc().set_bit().txtfc().set_bit());
+
+        // MASTER can only be written while disabled
+        let slave = self.slave;
+        self.spi.cfg2.modify(|_, w| w.master().bit(!slave));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+
+        if self.spi.sr.read().modf().bit_is_set() {
+            Err(Error::ModeFault)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocking full duplex transfer in place. The bus is
+    /// recovered if an error occurs.
+    ///
+    /// The transmit FIFO is kept topped up while the
+    /// receive FIFO is drained, so that there are no gaps
+    /// between words. See `Pipeline`
+    fn transfer_recover<'w, W>(
+        &mut self,
+        words: &'w mut [W],
+    ) -> Result<&'w [W], Error>
+    where
+        W: Copy,
+        Self: FullDuplex<W, Error = Error>,
+    {
+        let depth = SPI::FIFO_SIZE / mem::size_of::<W>();
+        let result =
+            transfer_pipelined(self, words, depth).and_then(|_| self.flush());
+
+        match result {
+            Ok(()) => Ok(words),
+            Err(e) => {
+                self.recover();
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait for the slave to signal that it is ready on the
+    /// `ready` pin, then write `data`.
+    ///
+    /// The slave is ready when `ready` is high if
+    /// `active_high` is `true`, or when `ready` is low
+    /// otherwise. The pin is polled at most `max_polls`
+    /// times, after which `Error::Timeout` is returned
+    /// without any data having been written. This prevents
+    /// a stuck ready line from hanging the bus forever.
+    pub fn transaction_when_ready<P>(
+        &mut self,
+        ready: &P,
+        active_high: bool,
+        max_polls: u32,
+        data: &[u8],
+    ) -> Result<(), Error>
+    where
+        P: hal::digital::v2::InputPin,
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        let is_ready = || match ready.is_high() {
+            Ok(high) => high == active_high,
+            Err(_) => false,
+        };
+
+        if !(0..max_polls).any(|_| is_ready()) {
+            return Err(Error::Timeout);
+        }
+
+        for word in data {
+            nb::block!(self.send(*word))?;
+            let _: u8 = nb::block!(self.read())?;
+        }
+
+        Ok(())
+    }
+
+    /// Full duplex transfer that continues past transient
+    /// errors, recording which words were affected.
+    ///
+    /// `errors[i]` is set to `true` if an overrun or CRC
+    /// error was detected while transferring `words[i]`,
+    /// and `false` otherwise. The error flag is cleared and
+    /// the transfer continues with the next word. A mode
+    /// fault aborts the transfer immediately.
+    pub fn transfer_collect_errors(
+        &mut self,
+        words: &mut [u8],
+        errors: &mut [bool],
+    ) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        for (i, word) in words.iter_mut().enumerate() {
+            let result: Result<u8, Error> = nb::block!(self.send(*word))
+                .and_then(|_| nb::block!(self.read()));
+
+            let failed = match result {
+                Ok(received) => {
+                    *word = received;
+                    false
+                }
+                Err(Error::Overrun) | Err(Error::Crc) => {
+                    self.spi
+                        .ifcr
+                        .write(|w| w.ovrc().set_bit().crcec().set_bit());
+                    true
+                }
+                Err(e) => return Err(e),
+            };
+
+            if let Some(error) = errors.get_mut(i) {
+                *error = failed;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Full duplex transfer in place as a single
+    /// transaction, ended by the hardware.
+    ///
+    /// TSIZE is programmed with the length of `words`, and
+    /// the transaction is started. Once the end of the
+    /// transaction (EOT) is reached, CS is deasserted if it
+    /// is managed by the SPI peripheral, see
+    /// `end_transaction`. TSIZE is reset to zero
+    /// afterwards, also on error.
+    ///
+    /// TSIZE is limited to 65535 words, so longer
+    /// transfers are split into several transactions. The
+    /// peripheral is briefly disabled between them to
+    /// program TSIZE, which deasserts a CS managed by the
+    /// SPI peripheral.
+    ///
+    /// ```rust
+    /// let mut words = [0x9Fu8, 0, 0, 0];
+    /// spi.transaction(&mut words)?;
+    /// ```
+    pub fn transaction<W>(&mut self, words: &mut [W]) -> Result<(), Error>
+    where
+        W: Copy,
+        Self: FullDuplex<W, Error = Error>,
+    {
+        let result: Result<(), Error> =
+            words.chunks_mut(u16::MAX as usize).try_for_each(|chunk| {
+                set_tsize(&self.spi, chunk.len() as u16);
+                self.start_master();
+                for word in chunk.iter_mut() {
+                    nb::block!(self.send(*word))?;
+                    *word = nb::block!(self.read())?;
+                }
+
+                while self.spi.sr.read().eot().bit_is_clear() {}
+                self.spi
+                    .ifcr
+                    .write(|w| w.eotc().set_bit().txtfc().set_bit());
+                Ok(())
+            });
+
+        self.end_transaction();
+        set_tsize(&self.spi, 0);
+
+        result
+    }
+
+    /// Write `bytes` on the single data line of a half
+    /// duplex (3-wire) bus. The data line is driven for the
+    /// duration of the write.
+    ///
+    /// The SPI must be configured with
+    /// `CommunicationMode::HalfDuplex`, otherwise
+    /// `ConfigError::CommunicationMode` is returned.
+    ///
+    /// ```rust
+    /// let config = spi::Config::new(spi::MODE_3)
+    ///     .communication_mode(spi::CommunicationMode::HalfDuplex);
+    /// let pins = (sck, spi::NoMiso, mosi);
+    /// let mut spi = dp.SPI1.spi(
+    ///     pins,
+    ///     config,
+    ///     1.mhz(),
+    ///     ccdr.peripheral.SPI1,
+    ///     &ccdr.clocks,
+    /// );
+    ///
+    /// // Read a register
+    /// spi.half_duplex_write(&[0x80 | 0x0F])?;
+    /// let mut id = [0];
+    /// spi.half_duplex_read(&mut id)?;
+    /// ```
+    ///
+    /// Nothing is transferred if `bytes` is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is longer than 65535 bytes.
+    pub fn half_duplex_write(&mut self, bytes: &[u8]) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        // TSIZE = 0 would never end the transaction
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.half_duplex_begin(true, bytes.len())?;
+
+        let result: Result<(), Error> = bytes.iter().try_for_each(|byte| {
+            nb::block!(self.send(*byte))?;
+            Ok(())
+        });
+
+        self.half_duplex_end(result)
+    }
+
+    /// Read into `bytes` from the single data line of a
+    /// half duplex (3-wire) bus. The data line is released
+    /// for the duration of the read, and exactly
+    /// `bytes.len()` frames are clocked.
+    ///
+    /// The SPI must be configured with
+    /// `CommunicationMode::HalfDuplex`, otherwise
+    /// `ConfigError::CommunicationMode` is returned. See
+    /// `half_duplex_write`. Nothing is transferred if
+    /// `bytes` is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is longer than 65535 bytes.
+    pub fn half_duplex_read(&mut self, bytes: &mut [u8]) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        // TSIZE = 0 would never end the transaction
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.half_duplex_begin(false, bytes.len())?;
+
+        let result: Result<(), Error> = bytes.iter_mut().try_for_each(|byte| {
+            *byte = nb::block!(FullDuplex::<u8>::read(self))?;
+            Ok(())
+        });
+
+        self.half_duplex_end(result)
+    }
+
+    /// Receive exactly `buf.len()` frames in simplex
+    /// receiver mode. The master generates SCK for the
+    /// frames only, and nothing is driven on MOSI, so no
+    /// MOSI pin is needed: `(sck, miso, NoMosi)`.
+    ///
+    /// The SPI must be configured with
+    /// `CommunicationMode::Receiver`, otherwise
+    /// `ConfigError::CommunicationMode` is returned. On an
+    /// overrun, the transfer is aborted, the FIFOs are
+    /// flushed, see `recover`, and `Error::Overrun` is
+    /// returned. Nothing is transferred if `buf` is empty.
+    ///
+    /// ```rust
+    /// // Read a 24-bit ADC sample
+    /// let config = spi::Config::new(spi::MODE_1)
+    ///     .communication_mode(spi::CommunicationMode::Receiver)
+    ///     .frame_size(24);
+    /// let pins = (sck, miso, spi::NoMosi);
+    /// let mut adc = dp
+    ///     .SPI1
+    ///     .spi(
+    ///         pins,
+    ///         config,
+    ///         1.mhz(),
+    ///         ccdr.peripheral.SPI1,
+    ///         &ccdr.clocks,
+    ///     )
+    ///     .change_word_size::<u32>();
+    /// let mut sample = [0u32];
+    /// adc.read_frames(&mut sample)?;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than 65535 frames.
+    pub fn read_frames<W>(&mut self, buf: &mut [W]) -> Result<(), Error>
+    where
+        Self: FullDuplex<W, Error = Error>,
+    {
+        assert!(buf.len() <= u16::MAX as usize);
+        if !self.spi.cfg2.read().comm().is_receiver() {
+            let e = ConfigError::CommunicationMode;
+            return Err(Error::Config(e));
+        }
+        // TSIZE = 0 would receive endlessly
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        set_tsize(&self.spi, buf.len() as u16);
+        self.start_master();
+
+        let result: Result<(), Error> = buf.iter_mut().try_for_each(|word| {
+            *word = nb::block!(match self.read() {
+                // The receive FIFO is drained, so resume
+                // if it was suspended for being full
+                Err(nb::Error::WouldBlock) => {
+                    resume_rx(&self.spi);
+                    Err(nb::Error::WouldBlock)
+                }
+                result => result,
+            })?;
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => {
+                while self.spi.sr.read().eot().bit_is_clear() {}
+                self.spi
+                    .ifcr
+                    .write(|w| w.eotc().set_bit().txtfc().set_bit());
+            }
+            // Stop the transfer and flush the FIFOs
+            Err(_) => self.recover(),
+        }
+        set_tsize(&self.spi, 0);
+
+        result
+    }
+
+    /// Set the direction of the half duplex data line and
+    /// the length of the transaction. HDDIR and TSIZE can
+    /// only be changed while the peripheral is disabled
+    fn half_duplex_begin(
+        &mut self,
+        transmit: bool,
+        len: usize,
+    ) -> Result<(), Error> {
+        assert!(len <= u16::MAX as usize);
+        if !self.spi.cfg2.read().comm().is_half_duplex() {
+            let e = ConfigError::CommunicationMode;
+            return Err(Error::Config(e));
+        }
+
+        while self.spi.sr.read().txc().is_ongoing() {}
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi.cr1.modify(|_, w| w.hddir().bit(transmit));
+        self.spi.cr2.modify(|_, w| w.tsize().bits(len as u16));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+        self.start_master();
+
+        Ok(())
+    }
+
+    /// Wait for the end of a half duplex transaction, unless
+    /// it failed, then reset TSIZE
+    fn half_duplex_end(
+        &mut self,
+        result: Result<(), Error>,
+    ) -> Result<(), Error> {
+        if result.is_ok() {
+            while self.spi.sr.read().eot().bit_is_clear() {}
+        }
+        self.spi
+            .ifcr
+            .write(|w| w.eotc().set_bit().txtfc().set_bit());
+        set_tsize(&self.spi, 0);
+
+        result
+    }
+
+    /// Start a streaming master transaction of `initial`
+    /// words, which can be extended without a gap with
+    /// `reload`. See the module documentation.
+    pub fn start_streaming(&mut self, initial: u16) {
+        set_tsize(&self.spi, initial);
+        set_tser(&self.spi, 0);
+        self.spi
+            .ifcr
+            .write(|w| w.tserfc().set_bit().eotc().set_bit());
+        self.start_master();
+    }
+
+    /// Queue `next` more words, to be loaded into TSIZE when
+    /// the current count runs out. Returns `WouldBlock` if
+    /// the previous count is still pending. Also clears
+    /// TSERF.
+    pub fn reload(&mut self, next: u16) -> nb::Result<(), Error> {
+        if self.spi.cr2.read().tser().bits() != 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        set_tser(&self.spi, next);
+        self.spi.ifcr.write(|w| w.tserfc().set_bit());
+        Ok(())
+    }
+
+    /// End a streaming transaction: no further counts are
+    /// queued, and once the current and any pending counts
+    /// have run out, the end of the transaction (EOT) is
+    /// awaited. TSIZE is then reset to zero.
+    pub fn end_streaming(&mut self) {
+        while self.spi.sr.read().eot().bit_is_clear() {}
+        self.spi
+            .ifcr
+            .write(|w| w.eotc().set_bit().txtfc().set_bit().tserfc().set_bit());
+        set_tsize(&self.spi, 0);
+    }
+
+    fn mixed_phases(
+        &mut self,
+        write: WritePhase<'_>,
+        read: ReadPhase<'_>,
+    ) -> Result<(), Error> {
+        // No transaction is started yet
+        self.set_dsize(write.dsize());
+        match write {
+            WritePhase::Bits8(words) => {
+                for word in words {
+                    nb::block!(self.send_byte(*word))?;
+                    nb::block!(self.read_byte())?;
+                }
+            }
+            WritePhase::Bits16(words) => {
+                for word in words {
+                    nb::block!(self.send_halfword(*word))?;
+                    nb::block!(self.read_halfword())?;
+                }
+            }
+        }
+
+        if read.dsize() != write.dsize() {
+            self.set_dsize_suspended(read.dsize());
+        }
+        match read {
+            ReadPhase::Bits8(words) => {
+                for word in words.iter_mut() {
+                    nb::block!(self.send_byte(0))?;
+                    *word = nb::block!(self.read_byte())?;
+                }
+            }
+            ReadPhase::Bits16(words) => {
+                for word in words.iter_mut() {
+                    nb::block!(self.send_halfword(0))?;
+                    *word = nb::block!(self.read_halfword())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Change the DSIZE field within a transaction, without
+    /// ending it. An ongoing master transfer is suspended
+    /// at a frame boundary, and DSIZE is written while the
+    /// peripheral stays enabled, so that a CS managed by
+    /// the peripheral stays asserted. The transfer is then
+    /// resumed
+    fn set_dsize_suspended(&mut self, dsize: u8) {
+        if !self.transaction_active() {
+            self.set_dsize(dsize);
+            return;
+        }
+
+        self.suspend_transfer();
+        self.spi.cfg1.modify(|_, w| w.dsize().bits(dsize));
+        self.resume();
+    }
+
+    /// Suspend the ongoing transfer, run `f`, then resume
+    /// the transfer. Returns the result of `f`.
+    ///
+    /// The transfer is suspended at the next frame
+    /// boundary, and `f` is only run once the suspension
+    /// is complete, so SCK is stopped while `f` runs. This
+    /// allows GPIOs to be changed between frames, for
+    /// example to toggle the D/C pin of a display. If no
+    /// transfer is ongoing, `f` is run immediately and no
+    /// transfer is started afterwards.
+    pub fn with_suspended<R, F>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let active = self.transaction_active();
+        self.suspend_transfer();
+
+        let result = f();
+
+        if active {
+            self.spi.cr1.modify(|_, w| w.cstart().started());
+        }
+
+        result
+    }
+
+    /// Suspend an ongoing master transfer at the next frame
+    /// boundary, and wait for the suspension to complete
+    fn suspend_transfer(&mut self) {
+        nb::block!(self.suspend()).ok();
+        self.clear_suspend();
+    }
+
+    /// Request that the ongoing master transfer is
+    /// suspended at the next frame boundary. Returns
+    /// `WouldBlock` until the suspension is complete (SUSP
+    /// is set), and `Ok` immediately if no transfer is
+    /// ongoing.
+    ///
+    /// SCK is stopped while suspended. When the transaction
+    /// has a fixed size, see `prepare_transaction`, the
+    /// number of frames remaining is kept: `resume`
+    /// continues the transaction, and EOT is set once the
+    /// remaining frames have been transferred.
+    ///
+    /// ```rust
+    /// nb::block!(spi.suspend())?;
+    /// // Use another device on the bus
+    /// spi.clear_suspend();
+    /// spi.resume();
+    /// ```
+    pub fn suspend(&mut self) -> nb::Result<(), Error> {
+        if self.spi.sr.read().susp().bit_is_set()
+            || self.spi.cr1.read().cstart().bit_is_clear()
+        {
+            return Ok(());
+        }
+
+        self.spi.cr1.modify(|_, w| w.csusp().set_bit());
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Resume a master transfer suspended by `suspend`, by
+    /// setting CSTART again
+    pub fn resume(&mut self) {
+        self.spi.cr1.modify(|_, w| w.cstart().started());
+    }
+
+    /// Discard any stale data in the receive FIFO, without
+    /// disabling the peripheral.
+    ///
+    /// The RXP flag is only set once a complete packet is
+    /// available, so any trailing partial packet is drained
+    /// by reading RXDR until RXWNE and RXPLVL report the
+    /// FIFO empty. Returns `Error::Overrun` if the overrun
+    /// flag is set, as the FIFO contents cannot then be
+    /// trusted.
+    ///
+    /// Note: The transmit FIFO can not be flushed without
+    /// clearing SPE. RM0433 documents no mechanism for
+    /// discarding data that has already been written to
+    /// TXDR, other than disabling the peripheral (which
+    /// resets both FIFOs). Data in the transmit FIFO will
+    /// be sent on the next transaction.
+    pub fn flush_fifos_inline(&mut self) -> Result<(), Error> {
+        loop {
+            let sr = self.spi.sr.read();
+
+            if sr.ovr().is_overrun() {
+                return Err(Error::Overrun);
+            } else if sr.rxp().is_not_empty()
+                || sr.rxwne().bit_is_set()
+                || sr.rxplvl().bits() != 0
+            {
+                // NOTE(read_volatile) read only 1 byte
+                let _ = unsafe {
+                    ptr::read_volatile(&self.spi.rxdr as *const _ as *const u8)
+                };
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Change the communication mode at runtime.
+    ///
+    /// Briefly disables the peripheral to write the new
+    /// mode. Disabling the peripheral flushes both FIFOs,
+    /// so any received data that has not been read is
+    /// discarded rather than being left stranded in the
+    /// receive FIFO.
+    ///
+    /// Returns `Error::Busy`, without changing the mode, if
+    /// a transfer is in progress or a transaction has been
+    /// started and not yet ended, see `transaction_active`.
+    pub fn set_communication_mode(
+        &mut self,
+        mode: CommunicationMode,
+    ) -> Result<(), Error> {
+        self.check_idle()?;
+        if self.transaction_active() {
+            return Err(Error::Busy);
+        }
+
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi.cfg2.modify(|_, w| comm(w, mode));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+
+        Ok(())
+    }
+
+    /// Change the DSIZE field, which may only be written
+    /// while the peripheral is disabled
+    fn set_dsize(&mut self, dsize: u8) {
+        // Wait for any ongoing transmission to complete
+        // before disabling the peripheral
+        while self.spi.sr.read().txc().is_ongoing() {}
+
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+        self.spi.cfg1.modify(|_, w| w.dsize().bits(dsize));
+        self.spi.cr1.modify(|_, w| w.spe().enabled());
+    }
+
+    /// Write CSTART to start a transaction in master mode.
+    /// In slave mode, transactions are started by the
+    /// master
+    fn start_master(&mut self) {
+        if !self.slave {
+            self.spi.cr1.modify(|_, w| w.cstart().started());
+        }
+    }
+
+    /// Returns `true` if a master has a transfer size left
+    /// programmed without a transaction to use it. Sending
+    /// would start a transaction that ends part way through
+    /// the data
+    fn stale_tsize(&self) -> bool {
+        !self.slave
+            && self.spi.cr2.read().tsize().bits() != 0
+            && self.spi.cr1.read().cstart().is_not_started()
+    }
+
+    /// Write words to the transmit FIFO until it is full,
+    /// without starting a transaction. Returns the number
+    /// of words written.
+    ///
+    /// In slave mode, this preloads data to be transmitted
+    /// as soon as the master starts clocking, avoiding an
+    /// underrun on the first frames.
+    pub fn preload(&mut self, words: &[u8]) -> usize {
+        let mut written = 0;
+
+        for &word in words {
+            if !self.spi.sr.read().txp().is_not_full() {
+                break;
+            }
+            let word = if self.invert_mosi { !word } else { word };
+            // NOTE(write_volatile) write exactly one byte
+            unsafe {
+                ptr::write_volatile(
+                    ptr::addr_of!(self.spi.txdr) as *mut u8,
+                    word,
+                )
+            }
+            written += 1;
+        }
+
+        written
+    }
+
+    /// Send a frame of up to 16 bits
+    fn send_halfword(&mut self, word: u16) -> nb::Result<(), Error> {
+        let sr = self.spi.sr.read();
+
+        Err(if !self.is_enabled() {
+            nb::Error::Other(Error::Disabled)
+        } else if self.stale_tsize() {
+            let e = ConfigError::TransferSize;
+            nb::Error::Other(Error::Config(e))
+        } else if sr.udr().bit_is_set() {
+            // Clear the flag, so that the slave can continue
+            self.spi.ifcr.write(|w| w.udrc().set_bit());
+            nb::Error::Other(Error::Underrun)
+        } else if sr.ovr().is_overrun() {
+            nb::Error::Other(Error::Overrun)
+        } else if sr.modf().is_fault() {
+            nb::Error::Other(Error::ModeFault)
+        } else if sr.crce().is_error() {
+            nb::Error::Other(Error::Crc)
+        } else if sr.txp().is_not_full() {
+            let word = if self.invert_mosi { !word } else { word };
+            // NOTE(write_volatile) write exactly one
+            // halfword, so that exactly one frame is
+            // pushed into the FIFO
+            unsafe {
+                ptr::write_volatile(
+                    ptr::addr_of!(self.spi.txdr) as *mut u16,
+                    word,
+                )
+            }
+            self.start_master();
+
+            return Ok(());
+        } else {
+            nb::Error::WouldBlock
+        })
+    }
+
+    /// Read a frame of up to 16 bits
+    fn read_halfword(&mut self) -> nb::Result<u16, Error> {
+        let sr = self.spi.sr.read();
+
+        Err(if let Some(e) = StatusFlags(sr.bits()).error() {
+            nb::Error::Other(e)
+        } else if sr.rxp().is_not_empty() {
+            // NOTE(read_volatile) read exactly one halfword
+            let word = unsafe {
+                ptr::read_volatile(&self.spi.rxdr as *const _ as *const u16)
+            };
+
+            return Ok(if self.invert_miso {
+                // Only complement the bits in the frame
+                let bits = self.spi.cfg1.read().dsize().bits() + 1;
+                let mask = ((1u32 << bits) - 1) as u16;
+                !word & mask
+            } else {
+                word
+            });
+        } else {
+            nb::Error::WouldBlock
+        })
+    }
+
+    /// Record each `send`, `read` and error in `buf`.
+    ///
+    /// `buf` holds the most recent `TRACE_LEN` events,
+    /// each with a sequence number, so that the sequence
+    /// of operations leading up to a rare bus error can be
+    /// inspected after the fact.
+    #[cfg(feature = "trace")]
+    pub fn enable_trace(&mut self, buf: &'static mut TraceBuffer) {
+        self.trace = Some(buf);
+    }
+
+    /// Stop recording events, returning the trace buffer
+    #[cfg(feature = "trace")]
+    pub fn disable_trace(&mut self) -> Option<&'static mut TraceBuffer> {
+        self.trace.take()
+    }
+
+    #[cfg(feature = "trace")]
+    fn trace_result<T, F>(&mut self, result: &nb::Result<T, Error>, f: F)
+    where
+        F: FnOnce(&T) -> TraceEvent,
+    {
+        if let Some(trace) = self.trace.as_mut() {
+            match result {
+                Ok(value) => trace.record(f(value)),
+                Err(nb::Error::Other(e)) => trace.record(TraceEvent::Error(*e)),
+                Err(nb::Error::WouldBlock) => {}
+            }
+        }
+    }
+
+    /// Write the words produced by `words`, passing each
+    /// received word to `received` in order. The words are
+    /// generated as they are written, so there is no limit
+    /// on their number.
+    ///
+    /// The transmit FIFO is kept topped up, and no more
+    /// words are written than the receive FIFO can hold
+    /// before they are read, so that it does not
+    /// overrun. In `CommunicationMode::Transmitter` nothing
+    /// is received, so `received` is not called. The bus is
+    /// recovered if an error occurs.
+    ///
+    /// ```rust
+    /// // Expand run-length encoded pixels on the fly
+    /// let pixels = runs
+    ///     .iter()
+    ///     .flat_map(|&(n, p)| core::iter::repeat(p).take(n));
+    /// spi.transfer_iter(pixels, |_| {})?;
+    /// ```
+    pub fn transfer_iter<W, I, F>(
+        &mut self,
+        words: I,
+        received: F,
+    ) -> Result<(), Error>
+    where
+        W: Copy,
+        I: IntoIterator<Item = W>,
+        F: FnMut(W),
+        Self: FullDuplex<W, Error = Error>,
+    {
+        let result = self.stream(words, received);
+        if result.is_err() {
+            self.recover();
+        }
+        result
+    }
+
+    fn stream<W, I, F>(
+        &mut self,
+        words: I,
+        received: F,
+    ) -> Result<(), Error>
+    where
+        W: Copy,
+        I: IntoIterator<Item = W>,
+        F: FnMut(W),
+        Self: FullDuplex<W, Error = Error>,
+    {
+        self.spi.ifcr.write(|w| w.ovrc().set_bit());
+
+        // Nothing is received in transmitter mode
+        if self.spi.cfg2.read().comm().is_transmitter() {
+            for word in words {
+                nb::block!(self.send(word))?;
+            }
+        } else {
+            let depth = SPI::FIFO_SIZE / mem::size_of::<W>();
+            stream_pipelined(self, words, received, depth)?;
+        }
+
+        self.flush()
+    }
+
+    /// Returns the FIFO packet size in bytes if the FIFOs
+    /// can be accessed 32 bits at a time: 8-bit frames
+    /// and a FIFO threshold of 4, 8 or 16
+    fn packet_bytes(&self) -> Option<usize> {
+        let cfg1 = self.spi.cfg1.read();
+        let packet = usize::from(cfg1.fthlv().bits()) + 1;
+
+        if cfg1.dsize().bits() == 8 - 1 && packet % 4 == 0 {
+            Some(packet)
+        } else {
+            None
+        }
+    }
+
+    /// Wait until a whole packet can be written (TXP), or
+    /// read if `rx` is `true` (RXP)
+    fn wait_packet(&self, rx: bool) -> Result<(), Error> {
+        loop {
+            let sr = self.spi.sr.read();
+
+            if let Some(e) = StatusFlags(sr.bits()).error() {
+                return Err(e);
+            } else if (rx && sr.rxp().is_not_empty())
+                || (!rx && sr.txp().is_not_full())
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Exchange one packet of `words`, 32 bits at a
+    /// time. The received data is stored in `words` if
+    /// `store` is `true`
+    fn packet(&mut self, words: &mut [u8], store: bool) -> Result<(), Error> {
+        let txdr = ptr::addr_of!(self.spi.txdr) as *mut u32;
+        let rxdr = &self.spi.rxdr as *const _ as *const u32;
+
+        self.wait_packet(false)?;
+        for chunk in words.chunks_exact(4) {
+            let mut word =
+                u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            if self.invert_mosi {
+                word = !word;
+            }
+            // NOTE(write_volatile) TXP guarantees space for
+            // the whole packet
+            unsafe { ptr::write_volatile(txdr, word) };
+        }
+        self.start_master();
+
+        if self.spi.cfg2.read().comm().is_transmitter() {
+            return Ok(());
+        }
+
+        self.wait_packet(true)?;
+        for chunk in words.chunks_exact_mut(4) {
+            // NOTE(read_volatile) RXP guarantees a whole
+            // packet has been received
+            let mut word = unsafe { ptr::read_volatile(rxdr) };
+            if self.invert_miso {
+                word = !word;
+            }
+            if store {
+                chunk.copy_from_slice(&word.to_le_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write `bytes`, accessing the transmit FIFO 32 bits at
+    /// a time when possible. See `Config::fifo_threshold`.
+    /// The tail, and all of `bytes` if packing is not
+    /// possible, is written one byte at a time. Received
+    /// data is discarded.
+    pub fn write_packed(&mut self, bytes: &[u8]) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        let packet = match self.packet_bytes() {
+            Some(packet) => packet,
+            None => return self.stream(bytes.iter().copied(), |_| {}),
+        };
+
+        let mut buf = [0u8; 16];
+        let mut chunks = bytes.chunks_exact(packet);
+        for chunk in &mut chunks {
+            buf[..packet].copy_from_slice(chunk);
+            self.packet(&mut buf[..packet], false)?;
+        }
+
+        self.stream(chunks.remainder().iter().copied(), |_| {})
+    }
+
+    /// Read into `bytes`, transmitting zeros and accessing
+    /// the receive FIFO 32 bits at a time when possible. See
+    /// `Config::fifo_threshold`. The tail, and all of
+    /// `bytes` if packing is not possible, is read one byte
+    /// at a time.
+    pub fn read_packed(&mut self, bytes: &mut [u8]) -> Result<(), Error>
+    where
+        Self: FullDuplex<u8, Error = Error>,
+    {
+        let mut tail = bytes;
+
+        if let Some(packet) = self.packet_bytes() {
+            let mut chunks = tail.chunks_exact_mut(packet);
+            for chunk in &mut chunks {
+                chunk.iter_mut().for_each(|byte| *byte = 0);
+                self.packet(chunk, true)?;
+            }
+            tail = chunks.into_remainder();
+        }
+
+        for byte in tail {
+            nb::block!(self.send(0u8))?;
+            *byte = nb::block!(self.read())?;
+        }
+
+        Ok(())
+    }
+
+    /// Disable the SPI and its peripheral clock, then
+    /// release the peripheral, the pins and the reset and
+    /// enable control token.
+    ///
+    /// Any ongoing transmission is completed first. The
+    /// pins can then be converted to other modes, or used
+    /// to construct the SPI again with a different
+    /// configuration.
+    pub fn free(self) -> (SPI, PINS, <SPI as SpiExt<SPI>>::Rec) {
+        while self.spi.sr.read().txc().is_ongoing() {}
+        self.spi.cr1.modify(|_, w| w.spe().disabled());
+
+        (self.spi, self.pins, self.rec.disable())
+    }
+}
+
+impl<SPI: Instance, PINS> Spi<SPI, PINS> {
+    /// Transfer in the background, driven by the interrupt
+    /// of this SPI. See `NonBlocking`
+    pub fn non_blocking(self) -> NonBlocking<SPI, PINS> {
+        NonBlocking {
+            spi: self,
+            tx: &[],
+            rx: &mut [],
+            written: 0,
+            read: 0,
+            state: None,
+        }
+    }
+
+    /// Begin building a transaction where the write and read
+    /// phases use different frame sizes. See
+    /// `MixedTransaction`.
+    pub fn mixed_transaction(&mut self) -> MixedTransaction<'_, SPI, PINS> {
+        MixedTransaction {
+            spi: self,
+            write: WritePhase::Bits8(&[]),
+            read: ReadPhase::Bits16(&mut []),
+        }
+    }
+
+    /// Prepare a transaction of `len` words that can be run
+    /// repeatedly. See `Transaction`.
+    pub fn prepare_transaction(
+        &mut self,
+        len: u16,
+    ) -> Transaction<'_, SPI, PINS> {
+        set_tsize(&self.spi, len);
+
+        Transaction {
+            spi: self,
+            len: len as usize,
+        }
+    }
+
+    /// Write `cmd`, then read `response`, in a single
+    /// transaction. A CS managed by hardware stays asserted
+    /// throughout. Zeros are transmitted while `response`
+    /// is read, and the data received while `cmd` is
+    /// written is discarded.
+    ///
+    /// ```rust
+    /// // Read 6 bytes from register 0x3B
+    /// let mut data = [0; 6];
+    /// spi.write_read(&[0x80 | 0x3B], &mut data)?;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transaction is longer than 65535
+    /// frames.
+    pub fn write_read(
+        &mut self,
+        cmd: &[u8],
+        response: &mut [u8],
+    ) -> Result<(), Error> {
+        self.fixed_size(cmd.len() + response.len(), |spi| {
+            spi.exchange(cmd)?;
+            for byte in response.iter_mut() {
+                nb::block!(spi.send(0))?;
+                *byte = nb::block!(spi.read())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Write `a`, then `b`, in a single transaction, for
+    /// example a command followed by a payload held in a
+    /// separate buffer. A CS managed by hardware stays
+    /// asserted throughout. The received data is discarded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transaction is longer than 65535
+    /// frames.
+    pub fn write_write(&mut self, a: &[u8], b: &[u8]) -> Result<(), Error> {
+        self.fixed_size(a.len() + b.len(), |spi| {
+            spi.exchange(a)?;
+            spi.exchange(b)
+        })
+    }
+
+    /// Write `words`, discarding the received data
+    fn exchange(&mut self, words: &[u8]) -> Result<(), Error> {
+        for word in words {
+            nb::block!(self.send(*word))?;
+            let _: u8 = nb::block!(self.read())?;
+        }
+        Ok(())
+    }
+
+    /// Run `f` as a transaction of `len` frames (TSIZE),
+    /// then wait for its end (EOT). The bus is recovered if
+    /// an error occurs
+    fn fixed_size<F>(&mut self, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        assert!(len <= u16::MAX as usize);
+        // A TSIZE of zero is an endless transaction
+        if len == 0 {
+            return Ok(());
+        }
+
+        set_tsize(&self.spi, len as u16);
+        self.start_master();
+        let result = f(self);
+        match result {
+            Ok(()) => {
+                while self.spi.sr.read().eot().bit_is_clear() {}
+                self.spi
+                    .ifcr
+                    .write(|w| w.eotc().set_bit().txtfc().set_bit());
+            }
+            Err(_) => self.recover(),
+        }
+        set_tsize(&self.spi, 0);
+
+        result
+    }
+}
+
+impl<SPI: Instance, PINS> hal::blocking::spi::Transactional<u8>
+    for Spi<SPI, PINS>
+{
+    type Error = Error;
+
+    /// Run all of the `operations` in a single transaction,
+    /// so that a CS managed by hardware stays asserted
+    /// throughout
+    fn exec<'a>(
+        &mut self,
+        operations: &mut [Operation<'a, u8>],
+    ) -> Result<(), Error> {
+        let len: usize = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Write(words) => words.len(),
+                Operation::Transfer(words) => words.len(),
+            })
+            .sum();
+
+        self.fixed_size(len, |spi| {
+            for op in operations.iter_mut() {
+                match op {
+                    Operation::Write(words) => spi.exchange(words)?,
+                    Operation::Transfer(words) => {
+                        for word in words.iter_mut() {
+                            nb::block!(spi.send(*word))?;
+                            *word = nb::block!(spi.read())?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<'a, SPI: Instance, PINS> MixedTransaction<'a, SPI, PINS> {
+    /// Run the transaction. The frame size in use before
+    /// the transaction is restored afterwards, which ends
+    /// the transaction.
+    pub fn run(self) -> Result<(), Error> {
+        let MixedTransaction { spi, write, read } = self;
+
+        let dsize = spi.spi.cfg1.read().dsize().bits();
+        let result = spi.mixed_phases(write, read);
+        spi.set_dsize(dsize);
+
+        result
+    }
+}
+
+impl<SPI: Instance, PINS> hal::spi::FullDuplex<u8> for Spi<SPI, PINS> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.check_word_size()?;
+
+        let result = self.read_byte();
+        #[cfg(feature = "trace")]
+        self.trace_result(&result, |&byte| TraceEvent::Read(byte));
+        result
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+        self.check_word_size()?;
+
+        let result = self.send_byte(byte);
+        #[cfg(feature = "trace")]
+        self.trace_result(&result, |_| TraceEvent::Send(byte));
+        result
+    }
+}
+
+impl<SPI: Instance, PINS, WORD> Spi<SPI, PINS, WORD> {
+    /// Returns `ConfigError::WordSize` unless the frame size
+    /// configured in hardware is held by `WORD`
+    fn check_word_size(&self) -> Result<(), Error>
+    where
+        WORD: Word,
+    {
+        if (WORD::MIN_BITS..=WORD::BITS).contains(&self.frame_size()) {
+            Ok(())
+        } else {
+            Err(Error::Config(ConfigError::WordSize))
+        }
+    }
+
+    /// Mask of the bits of a byte that hold a frame. Frames
+    /// shorter than 8 bits are right aligned, one frame per
+    /// byte access
+    fn byte_mask(&self) -> u8 {
+        frame_mask(self.frame_size())
+    }
+
+    /// Read a byte from the receive FIFO
+    fn read_byte(&mut self) -> nb::Result<u8, Error> {
+        let mask = self.byte_mask();
+        fifo_read_u8(&*self.spi, self.invert_miso, mask)
+    }
+
+    /// Write a byte to the transmit FIFO, and start a
+    /// transaction
+    fn send_byte(&mut self, byte: u8) -> nb::Result<(), Error> {
+        if !self.is_enabled() {
+            return Err(nb::Error::Other(Error::Disabled));
+        } else if self.stale_tsize() {
+            let e = ConfigError::TransferSize;
+            return Err(nb::Error::Other(Error::Config(e)));
+        }
+
+        let mask = self.byte_mask();
+        fifo_write_u8(&*self.spi, byte, self.invert_mosi, mask)?;
+        self.start_master();
+
+        Ok(())
+    }
 }
 
-/// Interrupt events
-pub enum Event {
-    /// New data has been received
-    Rxp,
-    /// Data can be sent
-    Txp,
-    /// An error occurred
-    Error,
+impl<SPI: Instance, PINS> hal::spi::FullDuplex<u16> for Spi<SPI, PINS, u16> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u16, Error> {
+        self.check_word_size()?;
+
+        self.read_halfword()
+    }
+
+    fn send(&mut self, word: u16) -> nb::Result<(), Error> {
+        self.check_word_size()?;
+
+        self.send_halfword(word)
+    }
 }
 
-#[derive(Debug)]
-pub struct Spi<SPI, PINS> {
-    spi: SPI,
-    pins: PINS,
+impl<SPI: Instance, PINS> hal::blocking::spi::Transfer<u8> for Spi<SPI, PINS> {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        self.transfer_recover(words)
+    }
 }
 
-pub trait SpiExt<SPI>: Sized {
-    fn spi<PINS, T>(
-        self,
-        pins: PINS,
-        mode: Mode,
-        freq: T,
-        ccdr: &Ccdr,
-    ) -> Spi<SPI, PINS>
+impl<SPI: Instance, PINS> hal::blocking::spi::Write<u8> for Spi<SPI, PINS> {
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.transfer_iter(words.iter().copied(), |_| {})
+    }
+}
+
+impl<SPI: Instance, PINS> hal::blocking::spi::WriteIter<u8> for Spi<SPI, PINS> {
+    type Error = Error;
+
+    fn write_iter<WI>(&mut self, words: WI) -> Result<(), Error>
     where
-        PINS: Pins<SPI>,
-        T: Into<Hertz>;
+        WI: IntoIterator<Item = u8>,
+    {
+        self.transfer_iter(words, |_| {})
+    }
+}
+
+impl<SPI: Instance, PINS> hal::blocking::spi::Transfer<u16>
+    for Spi<SPI, PINS, u16>
+{
+    type Error = Error;
+
+    fn transfer<'w>(
+        &mut self,
+        words: &'w mut [u16],
+    ) -> Result<&'w [u16], Error> {
+        self.transfer_recover(words)
+    }
+}
+
+impl<SPI: Instance, PINS> hal::blocking::spi::Write<u16>
+    for Spi<SPI, PINS, u16>
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u16]) -> Result<(), Error> {
+        self.transfer_iter(words.iter().copied(), |_| {})
+    }
 }
 
 macro_rules! spi {
-	($($SPIX:ident: ($spiX:ident, $apbXenr:ident,
-                     $spiXen:ident, $pclkX:ident),)+) => {
+	($($SPIX:ident: ($spiX:ident, $Rec:ident),)+) => {
 	    $(
             impl<PINS> Spi<$SPIX, PINS> {
-                pub fn $spiX<T>(
+                pub fn $spiX<T, CONFIG>(
                     spi: $SPIX,
                     pins: PINS,
-                    mode: Mode,
+                    config: CONFIG,
                     freq: T,
-                    ccdr: &Ccdr,
+                    prec: rec::$Rec,
+                    clocks: &CoreClocks,
                 ) -> Self
                 where
                     PINS: Pins<$SPIX>,
                     T: Into<Hertz>,
+                    CONFIG: Into<Config>,
                 {
-                    // Enable clock for SPI
-                    ccdr.rb.$apbXenr.modify(|_, w| w.$spiXen().enabled());
-
-                    // Disable SS output
-                    spi.cfg2.write(|w| w.ssoe().disabled());
-
-                    let spi_freq = freq.into().0;
-	                let spi_ker_ck = match Self::kernel_clk(ccdr) {
-                        Some(ker_hz) => ker_hz.0,
-                        _ => panic!("$SPIX kernel clock not running!")
-                    };
-                    let mbr = match spi_ker_ck / spi_freq {
-                        0 => unreachable!(),
-                        1..=2 => MBR::DIV2,
-                        3..=5 => MBR::DIV4,
-                        6..=11 => MBR::DIV8,
-                        12..=23 => MBR::DIV16,
-                        24..=47 => MBR::DIV32,
-                        48..=95 => MBR::DIV64,
-                        96..=191 => MBR::DIV128,
-                        _ => MBR::DIV256,
-                    };
-                    spi.cfg1.modify(|_, w| {
-                        w.mbr()
-                            .variant(mbr) // master baud rate
-                            .dsize()
-                            .bits(8 - 1) // 8 bit frames
-                    });
-
-                    // ssi: select slave = master mode
-                    spi.cr1.write(|w| w.ssi().slave_not_selected());
-
-                    // mstr: master configuration
-                    // lsbfrst: MSB first
-                    // ssm: enable software slave management (NSS pin
-                    // free for other uses)
-                    // comm: full-duplex
-                    spi.cfg2.write(|w| {
-                        w.cpha()
-                            .bit(mode.phase ==
-                                 Phase::CaptureOnSecondTransition)
-                            .cpol()
-                            .bit(mode.polarity == Polarity::IdleHigh)
-                            .master()
-                            .master()
-                            .lsbfrst()
-                            .msbfirst()
-                            .ssm()
-                            .enabled()
-                            .comm()
-                            .full_duplex()
-                    });
-
-                    // spe: enable the SPI bus
-                    spi.cr1.write(|w| w.ssi().slave_not_selected().spe().enabled());
-
-                    Spi { spi, pins }
-                }
-
-                /// Enable interrupts for the given `event`:
-                ///  - Received data ready to be read (RXP)
-                ///  - Transmit data register empty (TXP)
-                ///  - Error
-                pub fn listen(&mut self, event: Event) {
-                    match event {
-                        Event::Rxp => self.spi.ier.modify(|_, w|
-                                                w.rxpie().not_masked()),
-                        Event::Txp => self.spi.ier.modify(|_, w|
-                                                w.txpie().not_masked()),
-                        Event::Error => self.spi.ier.modify(|_, w| {
-                            w.udrie() // Underrun
-                                .not_masked()
-                                .ovrie() // Overrun
-                                .not_masked()
-                                .crceie() // CRC error
-                                .not_masked()
-                                .modfie() // Mode fault
-                                .not_masked()
-                        }),
-                    }
-                }
-
-                /// Disable interrupts for the given `event`:
-                ///  - Received data ready to be read (RXP)
-                ///  - Transmit data register empty (TXP)
-                ///  - Error
-                pub fn unlisten(&mut self, event: Event) {
-                    match event {
-                        Event::Rxp => self.spi.ier.modify(|_, w|
-                                                w.rxpie().masked()),
-                        Event::Txp => self.spi.ier.modify(|_, w|
-                                                w.txpie().masked()),
-                        Event::Error => self.spi.ier.modify(|_, w| {
-                            w.udrie() // Underrun
-                                .masked()
-                                .ovrie() // Overrun
-                                .masked()
-                                .crceie() // CRC error
-                                .masked()
-                                .modfie() // Mode fault
-                                .masked()
-                        }),
-                    }
-                }
-
-                /// Return `true` if the TXP flag is set, i.e. new
-                /// data to transmit can be written to the SPI.
-                pub fn is_txp(&self) -> bool {
-                    self.spi.sr.read().txp().is_not_full()
-                }
-
-                /// Return `true` if the RXP flag is set, i.e. new
-                /// data has been received and can be read from the
-                /// SPI.
-                pub fn is_rxp(&self) -> bool {
-                    self.spi.sr.read().rxp().is_not_empty()
+                    Self::new(spi, pins, config, freq, prec, clocks)
                 }
+            }
 
-                /// Return `true` if the MODF flag is set, i.e. the
-                /// SPI has experienced a mode fault
-                pub fn is_modf(&self) -> bool {
-                    self.spi.sr.read().modf().is_fault()
-                }
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
+                /// Reset the peripheral through the RCC, then restore
+                /// its configuration. Unlike `recover`, this also
+                /// clears state that survives disabling the
+                /// peripheral, such as a pending CSTART or a
+                /// transfer size.
+                ///
+                /// Any transfer in progress is aborted, and all
+                /// interrupts are disabled.
+                pub fn reset(&mut self) {
+                    // SPE, CSTART and CSUSP are not restored
+                    let cr1 = self.spi.cr1.read().bits() & !0x0000_0601;
+                    let cfg1 = self.spi.cfg1.read().bits();
+                    let cfg2 = self.spi.cfg2.read().bits();
+                    let crcpoly = self.spi.crcpoly.read().bits();
+                    let udrdr = self.spi.udrdr.read().bits();
 
-                /// Return `true` if the OVR flag is set, i.e. new
-                /// data has been received while the receive data
-                /// register was already filled.
-                pub fn is_ovr(&self) -> bool {
-                    self.spi.sr.read().ovr().is_overrun()
-                }
+                    self.rec.reset_in_place();
 
-                pub fn free(self) -> ($SPIX, PINS) {
-                    (self.spi, self.pins)
+                    // NOTE(unsafe) restoring values read back from the
+                    // peripheral. CFG1 and CFG2 are written while
+                    // disabled
+                    self.spi.cr1.write(|w| unsafe { w.bits(cr1) });
+                    self.spi.cfg1.write(|w| unsafe { w.bits(cfg1) });
+                    self.spi.cfg2.write(|w| unsafe { w.bits(cfg2) });
+                    self.spi.crcpoly.write(|w| unsafe { w.bits(crcpoly) });
+                    self.spi.udrdr.write(|w| unsafe { w.bits(udrdr) });
+                    self.spi.cr1.modify(|_, w| w.spe().enabled());
                 }
             }
 
             impl SpiExt<$SPIX> for $SPIX {
-	            fn spi<PINS, T>(self,
+                type Rec = rec::$Rec;
+
+	            fn spi<PINS, T, CONFIG>(self,
                                 pins: PINS,
-                                mode: Mode,
+                                config: CONFIG,
                                 freq: T,
-                                ccdr: &Ccdr) -> Spi<$SPIX, PINS>
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks) -> Spi<$SPIX, PINS>
 	            where
 	                PINS: Pins<$SPIX>,
-	                T: Into<Hertz>
+	                T: Into<Hertz>,
+	                CONFIG: Into<Config>,
 	            {
-	                Spi::$spiX(self, pins, mode, freq, ccdr)
+	                Spi::$spiX(self, pins, config, freq, prec, clocks)
 	            }
-	        }
 
+	            fn try_spi<PINS, T, CONFIG>(self,
+                                pins: PINS,
+                                config: CONFIG,
+                                freq: T,
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks) -> Result<Spi<$SPIX, PINS>, (Error, $SPIX, PINS)>
+	            where
+	                PINS: Pins<$SPIX>,
+	                T: Into<Hertz>,
+	                CONFIG: Into<Config>,
+	            {
+	                let config: Config = config.into();
+	                assert!(
+	                    !config.managed_cs || PINS::NSS,
+	                    "manage_cs requires an NSS pin: (sck, miso, mosi, nss)"
+	                );
+	                assert!(
+	                    !config.nss_input || PINS::NSS,
+	                    "nss_input_mode requires an NSS pin: (sck, miso, mosi, nss)"
+	                );
 
-            impl<PINS> hal::spi::FullDuplex<u8> for Spi<$SPIX, PINS> {
-                type Error = Error;
+	                let mut spi = Spi::<$SPIX, _>::try_init(self, pins, config, freq, prec, clocks)?;
+	                spi.pins = configure_pins(spi.pins, &config);
+	                Ok(spi)
+	            }
+
+	            fn spi_tx<SCK, MOSI, T, CONFIG>(self,
+                                pins: (SCK, MOSI),
+                                config: CONFIG,
+                                freq: T,
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks) -> Spi<$SPIX, (SCK, MOSI)>
+	            where
+	                SCK: PinSck<$SPIX>,
+	                MOSI: PinMosi<$SPIX>,
+	                T: Into<Hertz>,
+	                CONFIG: Into<Config>,
+	            {
+	                let config: Config = config.into();
+	                let config = config
+	                    .communication_mode(CommunicationMode::Transmitter);
+
+	                let mut spi = Spi::<$SPIX, _>::init(self, pins, config, freq, prec, clocks);
+	                // There is no MISO pin to drive data out if swapped
+	                if let (Some(high), false) = (config.mosi_idle_high, config.swap_mosi_miso) {
+	                    spi.pins.1.set_idle_pull(high);
+	                }
+	                if let Some(speed) = config.pin_speed {
+	                    let (sck, mosi) = spi.pins;
+	                    spi.pins = (sck.set_speed(speed), mosi.set_speed(speed));
+	                }
+	                spi
+	            }
+
+	            fn spi_slave<SCK, MISO, MOSI, NSS, CONFIG>(self,
+                                pins: (SCK, MISO, MOSI, NSS),
+                                config: CONFIG,
+                                prec: rec::$Rec) -> Spi<$SPIX, (SCK, MISO, MOSI, NSS)>
+	            where
+	                SCK: PinSck<$SPIX>,
+	                MISO: PinMiso<$SPIX>,
+	                MOSI: PinMosi<$SPIX>,
+	                NSS: PinNss<$SPIX>,
+	                CONFIG: Into<Config>,
+	            {
+	                let config: Config = config.into();
+	                let config = config.slave().manage_cs();
+
+	                // SCK and CS are driven by the master, so the baud
+	                // rate divider and the CS delays are unused
+	                let mut spi = Spi::<$SPIX, _>::expect(Spi::<$SPIX, _>::configure(self, pins, config, prec, 0, || {
+	                    Ok((0, MBR::DIV2))
+	                }));
+	                spi.pins = configure_pins(spi.pins, &config);
+	                spi
+	            }
+
+	            fn spi_unchecked<PINS, T, CONFIG>(self,
+                                pins: PINS,
+                                config: CONFIG,
+                                freq: T,
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks) -> Spi<$SPIX, PINS>
+	            where
+	                T: Into<Hertz>,
+	                CONFIG: Into<Config>,
+	            {
+	                Spi::<$SPIX, _>::init(self, pins, config.into(), freq, prec, clocks)
+	            }
 
-                fn read(&mut self) -> nb::Result<u8, Error> {
+	            fn spi_const<PINS, CONFIG, const KERNEL_HZ: u32, const TARGET_HZ: u32>(
+                                self,
+                                pins: PINS,
+                                config: CONFIG,
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks) -> Spi<$SPIX, PINS>
+	            where
+	                PINS: Pins<$SPIX>,
+	                CONFIG: Into<Config>,
+	            {
+	                let config: Config = config.into();
+
+	                let mut spi = Spi::<$SPIX, _>::init_const::<KERNEL_HZ, TARGET_HZ>(
+	                    self, pins, config, prec, clocks);
+	                spi.pins = configure_pins(spi.pins, &config);
+	                spi
+	            }
+	        }
+        )+
+	}
+}
+
+// 32-bit frames are only supported by SPI1, SPI2 and SPI3
+macro_rules! spi32 {
+	($($SPIX:ident,)+) => {
+	    $(
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
+                /// Send a frame of up to 32 bits
+                fn send_word(&mut self, word: u32) -> nb::Result<(), Error> {
                     let sr = self.spi.sr.read();
 
-                    Err(if sr.ovr().is_overrun() {
+                    Err(if !self.is_enabled() {
+                        nb::Error::Other(Error::Disabled)
+                    } else if self.stale_tsize() {
+                        let e = ConfigError::TransferSize;
+                        nb::Error::Other(Error::Config(e))
+                    } else if sr.udr().bit_is_set() {
+                        // Clear the flag, so that the slave can continue
+                        self.spi.ifcr.write(|w| w.udrc().set_bit());
+                        nb::Error::Other(Error::Underrun)
+                    } else if sr.ovr().is_overrun() {
                         nb::Error::Other(Error::Overrun)
                     } else if sr.modf().is_fault() {
                         nb::Error::Other(Error::ModeFault)
                     } else if sr.crce().is_error() {
                         nb::Error::Other(Error::Crc)
-                    } else if sr.rxp().is_not_empty() {
-                        // NOTE(read_volatile) read only 1 byte (the
-                        // svd2rust API only allows reading a
-                        // half-word)
-                        return Ok(unsafe {
-                            ptr::read_volatile(
-                                &self.spi.rxdr as *const _ as *const u8,
+                    } else if sr.txp().is_not_full() {
+                        let word = if self.invert_mosi { !word } else { word };
+                        // NOTE(write_volatile) write exactly one word,
+                        // so that exactly one frame is pushed into the
+                        // FIFO
+                        unsafe {
+                            ptr::write_volatile(
+                                ptr::addr_of!(self.spi.txdr) as *mut u32,
+                                word,
                             )
-                        });
+                        }
+                        self.start_master();
+
+                        return Ok(());
                     } else {
                         nb::Error::WouldBlock
                     })
                 }
 
-                fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+                /// Read a frame of up to 32 bits
+                fn read_word(&mut self) -> nb::Result<u32, Error> {
                     let sr = self.spi.sr.read();
 
-                    Err(if sr.ovr().is_overrun() {
-                        nb::Error::Other(Error::Overrun)
-                    } else if sr.modf().is_fault() {
-                        nb::Error::Other(Error::ModeFault)
-                    } else if sr.crce().is_error() {
-                        nb::Error::Other(Error::Crc)
-                    } else if sr.txp().is_not_full() {
-                        // NOTE(write_volatile) see note above
-                        unsafe {
-                            ptr::write_volatile(
-                                &self.spi.txdr as *const _ as *mut u8,
-                                byte,
+                    Err(if let Some(e) = StatusFlags(sr.bits()).error() {
+                        nb::Error::Other(e)
+                    } else if sr.rxp().is_not_empty() {
+                        // NOTE(read_volatile) read exactly one word
+                        let word = unsafe {
+                            ptr::read_volatile(
+                                &self.spi.rxdr as *const _ as *const u32,
                             )
-                        }
-                        // write CSTART to start a transaction in
-                        // master mode
-                        self.spi.cr1.modify(|_, w| w.cstart().started());
+                        };
 
-                        return Ok(());
+                        return Ok(if self.invert_miso {
+                            // Only complement the bits in the frame
+                            let bits = self.frame_size();
+                            let mask = u32::MAX >> (32 - bits);
+                            !word & mask
+                        } else {
+                            word
+                        });
                     } else {
                         nb::Error::WouldBlock
                     })
                 }
             }
 
-            impl<PINS> hal::blocking::spi::transfer::Default<u8>
-                for Spi<$SPIX, PINS> {}
+            impl<PINS> hal::spi::FullDuplex<u32> for Spi<$SPIX, PINS, u32> {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<u32, Error> {
+                    self.check_word_size()?;
+
+                    self.read_word()
+                }
+
+                fn send(&mut self, word: u32) -> nb::Result<(), Error> {
+                    self.check_word_size()?;
+
+                    self.send_word(word)
+                }
+            }
+
+            impl<PINS> hal::blocking::spi::Transfer<u32>
+                for Spi<$SPIX, PINS, u32>
+            {
+                type Error = Error;
+
+                fn transfer<'w>(
+                    &mut self,
+                    words: &'w mut [u32],
+                ) -> Result<&'w [u32], Error> {
+                    self.transfer_recover(words)
+                }
+            }
+
+            impl<PINS> hal::blocking::spi::Write<u32>
+                for Spi<$SPIX, PINS, u32>
+            {
+                type Error = Error;
 
-            impl<PINS> hal::blocking::spi::write::Default<u8>
-                for Spi<$SPIX, PINS> {}
+                fn write(&mut self, words: &[u32]) -> Result<(), Error> {
+                    self.transfer_iter(words.iter().copied(), |_| {})
+                }
+            }
         )+
-	}
+    }
+}
+
+/// RCC registers holding the kernel clock selection of each
+/// instance. The selection is made with `ccdr.peripheral_clocks`, and
+/// is only read back here
+fn rcc() -> &'static rcc::RegisterBlock {
+    // NOTE(unsafe) only the kernel clock selection fields are read
+    unsafe { &*RCC::ptr() }
 }
 
 macro_rules! spi123sel {
 	($($SPIX:ident,)+) => {
 	    $(
-            impl<PINS> Spi<$SPIX, PINS> {
+            impl sealed::Sealed for $SPIX {}
+
+            impl Instance for $SPIX {
+                const INTERRUPT: Interrupt = Interrupt::$SPIX;
+                const MAX_FRAME_SIZE: u8 = 32;
+                const FIFO_SIZE: usize = 16;
+
                 /// Returns the frequency of the current kernel clock
-                /// for SPI1, SPI2, SPI3
-                fn kernel_clk(ccdr: &Ccdr) -> Option<Hertz> {
-                    match ccdr.rb.d2ccip1r.read().spi123sel().variant() {
-                        Val(d2ccip1r::SPI123SEL_A::PLL1_Q) => ccdr.clocks.pll1_q_ck(),
-                        Val(d2ccip1r::SPI123SEL_A::PLL2_P) => ccdr.clocks.pll2_p_ck(),
-                        Val(d2ccip1r::SPI123SEL_A::PLL3_P) => ccdr.clocks.pll3_p_ck(),
-                        // Need a method of specifying pin clock
-                        Val(d2ccip1r::SPI123SEL_A::I2S_CKIN) => unimplemented!(),
-                        Val(d2ccip1r::SPI123SEL_A::PER) => ccdr.clocks.per_ck(),
+                /// for SPI1, SPI2, SPI3. Returns `None` if I2S_CKIN is
+                /// selected, as its frequency is not known
+                fn kernel_clk(clocks: &CoreClocks) -> Option<Hertz> {
+                    Self::kernel_clk_ckin(clocks, None)
+                }
+
+                fn kernel_clk_ckin(
+                    clocks: &CoreClocks,
+                    i2s_ckin: Option<Hertz>,
+                ) -> Option<Hertz> {
+                    match rcc().d2ccip1r.read().spi123sel().variant() {
+                        Val(d2ccip1r::SPI123SEL_A::PLL1_Q) => clocks.pll1_q_ck(),
+                        Val(d2ccip1r::SPI123SEL_A::PLL2_P) => clocks.pll2_p_ck(),
+                        Val(d2ccip1r::SPI123SEL_A::PLL3_P) => clocks.pll3_p_ck(),
+                        // Frequency provided by the application
+                        Val(d2ccip1r::SPI123SEL_A::I2S_CKIN) => i2s_ckin,
+                        Val(d2ccip1r::SPI123SEL_A::PER) => clocks.per_ck(),
                         _ => unreachable!(),
                     }
                 }
+
+                fn kernel_clk_survives_stop() -> bool {
+                    // per_ck is only restarted automatically on wakeup
+                    // if it is derived from HSI or CSI
+                    let ckpersel = rcc().d1ccipr.read().ckpersel();
+
+                    rcc().d2ccip1r.read().spi123sel().is_per()
+                        && (ckpersel.is_hsi() || ckpersel.is_csi())
+                }
             }
         )+
     }
@@ -478,20 +6677,32 @@ macro_rules! spi123sel {
 macro_rules! spi45sel {
 	($($SPIX:ident,)+) => {
 	    $(
-            impl<PINS> Spi<$SPIX, PINS> {
+            impl sealed::Sealed for $SPIX {}
+
+            impl Instance for $SPIX {
+                const INTERRUPT: Interrupt = Interrupt::$SPIX;
+                const MAX_FRAME_SIZE: u8 = 16;
+                const FIFO_SIZE: usize = 8;
+
                 /// Returns the frequency of the current kernel clock
                 /// for SPI4, SPI5
-                fn kernel_clk(ccdr: &Ccdr) -> Option<Hertz> {
-                    match ccdr.rb.d2ccip1r.read().spi45sel().variant() {
-                        Val(d2ccip1r::SPI45SEL_A::APB) => Some(ccdr.clocks.pclk2()),
-                        Val(d2ccip1r::SPI45SEL_A::PLL2_Q) => ccdr.clocks.pll2_q_ck(),
-                        Val(d2ccip1r::SPI45SEL_A::PLL3_Q) => ccdr.clocks.pll3_q_ck(),
-                        Val(d2ccip1r::SPI45SEL_A::HSI_KER) => ccdr.clocks.hsi_ck(),
-                        Val(d2ccip1r::SPI45SEL_A::CSI_KER) => ccdr.clocks.csi_ck(),
-                        Val(d2ccip1r::SPI45SEL_A::HSE) => ccdr.clocks.hse_ck(),
+                fn kernel_clk(clocks: &CoreClocks) -> Option<Hertz> {
+                    match rcc().d2ccip1r.read().spi45sel().variant() {
+                        Val(d2ccip1r::SPI45SEL_A::APB) => Some(clocks.pclk2()),
+                        Val(d2ccip1r::SPI45SEL_A::PLL2_Q) => clocks.pll2_q_ck(),
+                        Val(d2ccip1r::SPI45SEL_A::PLL3_Q) => clocks.pll3_q_ck(),
+                        Val(d2ccip1r::SPI45SEL_A::HSI_KER) => clocks.hsi_ck(),
+                        Val(d2ccip1r::SPI45SEL_A::CSI_KER) => clocks.csi_ck(),
+                        Val(d2ccip1r::SPI45SEL_A::HSE) => clocks.hse_ck(),
                         _ => unreachable!(),
                     }
                 }
+
+                fn kernel_clk_survives_stop() -> bool {
+                    let spi45sel = rcc().d2ccip1r.read().spi45sel();
+
+                    spi45sel.is_hsi_ker() || spi45sel.is_csi_ker()
+                }
             }
         )+
     }
@@ -499,32 +6710,48 @@ macro_rules! spi45sel {
 macro_rules! spi6sel {
 	($($SPIX:ident,)+) => {
 	    $(
-            impl<PINS> Spi<$SPIX, PINS> {
+            impl sealed::Sealed for $SPIX {}
+
+            impl Instance for $SPIX {
+                const INTERRUPT: Interrupt = Interrupt::$SPIX;
+                const MAX_FRAME_SIZE: u8 = 16;
+                const FIFO_SIZE: usize = 8;
+
                 /// Returns the frequency of the current kernel clock
                 /// for SPI6
-                fn kernel_clk(ccdr: &Ccdr) -> Option<Hertz> {
-                    match ccdr.rb.d3ccipr.read().spi6sel().variant() {
-                        Val(d3ccipr::SPI6SEL_A::RCC_PCLK4) => Some(ccdr.clocks.pclk4()),
-                        Val(d3ccipr::SPI6SEL_A::PLL2_Q) => ccdr.clocks.pll2_q_ck(),
-                        Val(d3ccipr::SPI6SEL_A::PLL3_Q) => ccdr.clocks.pll3_q_ck(),
-                        Val(d3ccipr::SPI6SEL_A::HSI_KER) => ccdr.clocks.hsi_ck(),
-                        Val(d3ccipr::SPI6SEL_A::CSI_KER) => ccdr.clocks.csi_ck(),
-                        Val(d3ccipr::SPI6SEL_A::HSE) => ccdr.clocks.hse_ck(),
+                fn kernel_clk(clocks: &CoreClocks) -> Option<Hertz> {
+                    match rcc().d3ccipr.read().spi6sel().variant() {
+                        Val(d3ccipr::SPI6SEL_A::RCC_PCLK4) => Some(clocks.pclk4()),
+                        Val(d3ccipr::SPI6SEL_A::PLL2_Q) => clocks.pll2_q_ck(),
+                        Val(d3ccipr::SPI6SEL_A::PLL3_Q) => clocks.pll3_q_ck(),
+                        Val(d3ccipr::SPI6SEL_A::HSI_KER) => clocks.hsi_ck(),
+                        Val(d3ccipr::SPI6SEL_A::CSI_KER) => clocks.csi_ck(),
+                        Val(d3ccipr::SPI6SEL_A::HSE) => clocks.hse_ck(),
                         _ => unreachable!(),
                     }
                 }
+
+                fn kernel_clk_survives_stop() -> bool {
+                    let spi6sel = rcc().d3ccipr.read().spi6sel();
+
+                    spi6sel.is_hsi_ker() || spi6sel.is_csi_ker()
+                }
             }
         )+
     }
 }
 
 spi! {
-    SPI1: (spi1, apb2enr,  spi1en, pclk2),
-    SPI2: (spi2, apb1lenr, spi2en, pclk1),
-    SPI3: (spi3, apb1lenr, spi3en, pclk1),
-    SPI4: (spi4, apb2enr,  spi4en, pclk2),
-    SPI5: (spi5, apb2enr,  spi5en, pclk2),
-    SPI6: (spi6, apb4enr,  spi6en, pclk2),
+    SPI1: (spi1, Spi1),
+    SPI2: (spi2, Spi2),
+    SPI3: (spi3, Spi3),
+    SPI4: (spi4, Spi4),
+    SPI5: (spi5, Spi5),
+    SPI6: (spi6, Spi6),
+}
+
+spi32! {
+    SPI1, SPI2, SPI3,
 }
 
 spi123sel! {
@@ -536,3 +6763,431 @@ spi45sel! {
 spi6sel! {
     SPI6,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        baud_for, core_delay_cycles, delay_cycles, fifo_read_u8,
+        fifo_write_u8, frame_mask, mbr_for, mbr_for_ratio,
+        pin_af_valid, seconds_to_ns, stream_pipelined, transfer_pipelined,
+        Config, ConfigError, ConstMbr, Divider, Error, NoMiso, NoMosi,
+        NoSck, PinLocations, PinTable, Pipeline, SpiRegs, StatusFlags, MBR,
+        MODE_0,
+    };
+    use crate::hal::spi::FullDuplex;
+    use crate::stm32::{SPI1, SPI4};
+    use crate::time::Hertz;
+    use std::cell::{Cell, RefCell};
+    use std::collections::VecDeque;
+
+    // Register model of the FIFO data path of a master with MOSI
+    // looped back to MISO. On each read of SR, the shift register
+    // moves up to `speed` frames from the transmit FIFO to the receive
+    // FIFO, setting OVR if the receive FIFO is full
+    struct Model {
+        fifo: usize,
+        speed: usize,
+        tx: RefCell<VecDeque<u8>>,
+        rx: RefCell<VecDeque<u8>>,
+        flags: Cell<u32>,
+    }
+
+    impl Model {
+        fn new(fifo: usize, speed: usize) -> Self {
+            Model {
+                fifo,
+                speed,
+                tx: RefCell::new(VecDeque::new()),
+                rx: RefCell::new(VecDeque::new()),
+                flags: Cell::new(0),
+            }
+        }
+
+        fn set(&self, flags: StatusFlags) {
+            self.flags.set(self.flags.get() | flags.0);
+        }
+    }
+
+    impl SpiRegs for Model {
+        fn status(&self) -> StatusFlags {
+            let mut tx = self.tx.borrow_mut();
+            let mut rx = self.rx.borrow_mut();
+            for _ in 0..self.speed {
+                if let Some(frame) = tx.pop_front() {
+                    if rx.len() < self.fifo {
+                        rx.push_back(frame);
+                    } else {
+                        self.set(StatusFlags::OVR);
+                    }
+                }
+            }
+
+            let mut flags = self.flags.get();
+            if !rx.is_empty() {
+                flags |= StatusFlags::RXP.0;
+            }
+            if tx.len() < self.fifo {
+                flags |= StatusFlags::TXP.0;
+            }
+            StatusFlags(flags)
+        }
+
+        fn clear(&self, flags: StatusFlags) {
+            self.flags.set(self.flags.get() & !flags.0);
+        }
+
+        fn read_rxdr_u8(&self) -> u8 {
+            self.rx.borrow_mut().pop_front().unwrap()
+        }
+
+        fn write_txdr_u8(&self, byte: u8) {
+            self.tx.borrow_mut().push_back(byte);
+        }
+    }
+
+    impl FullDuplex<u8> for Model {
+        type Error = Error;
+
+        fn read(&mut self) -> nb::Result<u8, Error> {
+            fifo_read_u8(self, false, 0xFF)
+        }
+
+        fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+            fifo_write_u8(self, byte, false, 0xFF)
+        }
+    }
+
+    #[test]
+    fn model_transfer_loops_back() {
+        for &fifo in &[8, 16] {
+            for speed in 1..=fifo {
+                let mut model = Model::new(fifo, speed);
+                let mut words: Vec<u8> =
+                    (0..300).map(|i| i as u8).collect();
+
+                let result = transfer_pipelined(&mut model, &mut words, fifo);
+                assert!(result.is_ok());
+                assert!(words.iter().enumerate().all(|(i, &w)| w == i as u8));
+                assert!(!model.status().contains(StatusFlags::OVR));
+            }
+        }
+    }
+
+    #[test]
+    fn model_stream_discards_without_overrun() {
+        for &fifo in &[8, 16] {
+            for speed in 1..=fifo {
+                let mut model = Model::new(fifo, speed);
+                let mut received = 0;
+
+                let words = (0..1000).map(|i| i as u8);
+                let count = |_| received += 1;
+                let result = stream_pipelined(&mut model, words, count, fifo);
+                assert!(result.is_ok());
+                assert_eq!(received, 1000);
+                assert!(!model.status().contains(StatusFlags::OVR));
+            }
+        }
+    }
+
+    #[test]
+    fn model_overruns_when_not_drained() {
+        // Writing more words than fit the receive FIFO before reading
+        let mut model = Model::new(16, 16);
+        for _ in 0..32 {
+            nb::block!(model.send(0)).ok();
+        }
+
+        assert!(matches!(model.read(), Err(nb::Error::Other(Error::Overrun))));
+    }
+
+    #[test]
+    fn model_read_errors() {
+        let mut model = Model::new(16, 1);
+        model.write_txdr_u8(0x55);
+        model.set(StatusFlags::CRCE | StatusFlags::MODF);
+        assert!(matches!(
+            model.read(),
+            Err(nb::Error::Other(Error::ModeFault))
+        ));
+
+        model.clear(StatusFlags::MODF);
+        assert!(matches!(model.read(), Err(nb::Error::Other(Error::Crc))));
+
+        model.clear(StatusFlags::CRCE);
+        assert!(matches!(model.read(), Ok(0x55)));
+        assert!(matches!(model.read(), Err(nb::Error::WouldBlock)));
+    }
+
+    #[test]
+    fn model_send_clears_underrun() {
+        let mut model = Model::new(16, 1);
+        model.set(StatusFlags::UDR);
+        assert!(matches!(
+            model.send(0x55),
+            Err(nb::Error::Other(Error::Underrun))
+        ));
+        assert!(model.send(0x55).is_ok());
+
+        model.set(StatusFlags::OVR);
+        assert!(matches!(
+            model.send(0x55),
+            Err(nb::Error::Other(Error::Overrun))
+        ));
+    }
+
+    #[test]
+    fn model_send_blocks_when_full() {
+        let mut model = Model::new(8, 0);
+        for _ in 0..8 {
+            assert!(model.send(0).is_ok());
+        }
+        assert!(matches!(model.send(0), Err(nb::Error::WouldBlock)));
+    }
+
+    #[test]
+    fn model_inverts_then_masks() {
+        let model = Model::new(16, 1);
+        let mask = frame_mask(4);
+
+        assert!(fifo_write_u8(&model, 0x05, true, mask).is_ok());
+        assert_eq!(model.tx.borrow().front(), Some(&0x0A));
+        assert!(matches!(fifo_read_u8(&model, true, mask), Ok(0x05)));
+    }
+
+    // Run a transfer of `len` words through a model of FIFOs holding
+    // `fifo` words. Each step, the shift register moves up to `speed`
+    // words from the transmit FIFO to the receive FIFO. Returns the
+    // highest receive FIFO level
+    fn run(len: usize, fifo: usize, speed: usize) -> usize {
+        let mut pipeline = Pipeline::new(fifo);
+        let (mut tx, mut rx, mut max_rx) = (0, 0, 0);
+
+        while pipeline.read < len {
+            if pipeline.written < len && pipeline.can_write() && tx < fifo {
+                tx += 1;
+                pipeline.written += 1;
+            }
+            for _ in 0..speed {
+                if tx > 0 {
+                    tx -= 1;
+                    rx += 1;
+                }
+            }
+            max_rx = max_rx.max(rx);
+            if rx > 0 {
+                rx -= 1;
+                pipeline.read += 1;
+            }
+        }
+        assert_eq!(pipeline.written, len);
+
+        max_rx
+    }
+
+    #[test]
+    fn pipeline_never_overruns() {
+        for &fifo in &[8, 16] {
+            for &len in &[0, 1, fifo, fifo + 1, 4096] {
+                for speed in 1..=fifo {
+                    assert!(run(len, fifo, speed) <= fifo);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pipeline_writes_ahead() {
+        let mut pipeline = Pipeline::new(16);
+        while pipeline.can_write() {
+            pipeline.written += 1;
+        }
+        assert_eq!(pipeline.written, 16);
+
+        pipeline.read += 1;
+        assert!(pipeline.can_write());
+    }
+
+    #[test]
+    fn pipeline_depth_at_least_one() {
+        let pipeline = Pipeline::new(0);
+        assert!(pipeline.can_write());
+    }
+
+    #[test]
+    fn mbr_boundaries() {
+        let table = [
+            (1, MBR::DIV2),
+            (2, MBR::DIV2),
+            (3, MBR::DIV4),
+            (5, MBR::DIV4),
+            (6, MBR::DIV8),
+            (11, MBR::DIV8),
+            (12, MBR::DIV16),
+            (23, MBR::DIV16),
+            (24, MBR::DIV32),
+            (47, MBR::DIV32),
+            (48, MBR::DIV64),
+            (95, MBR::DIV64),
+            (96, MBR::DIV128),
+            (191, MBR::DIV128),
+            (192, MBR::DIV256),
+            (1000, MBR::DIV256),
+        ];
+        for &(ratio, mbr) in table.iter() {
+            assert_eq!(mbr_for_ratio(ratio), mbr, "ratio {}", ratio);
+        }
+    }
+
+    #[test]
+    fn mbr_within_factor_of_two() {
+        let ker_ck = 100_000_000;
+        for spi_freq in (ker_ck / 256..=ker_ck).step_by(9973) {
+            let mbr = mbr_for(ker_ck, spi_freq).unwrap();
+            let sck = ker_ck / (2 << mbr as u32);
+            assert!(sck < 2 * spi_freq && 2 * sck > spi_freq);
+        }
+    }
+
+    #[test]
+    fn mbr_invalid_frequency() {
+        assert!(matches!(mbr_for(1_000, 0), Err(ConfigError::ZeroFrequency)));
+        assert!(matches!(
+            mbr_for(1_000, 1_001),
+            Err(ConfigError::FrequencyTooHigh)
+        ));
+    }
+
+    #[test]
+    fn strict_pins_accepts_listed_af() {
+        // PA5 is SPI1 SCK on AF5, PB5 is SPI1 MOSI on AF5
+        assert!(pin_af_valid(SPI1::SCK, 0, 5, 0b10, 5));
+        assert!(pin_af_valid(SPI1::MOSI, 1, 5, 0b10, 5));
+        assert_eq!((NoSck, NoMiso, NoMosi).locations(), [None; 4]);
+    }
+
+    #[test]
+    fn strict_pins_rejects_other_pins() {
+        // Wrong alternate function, not in alternate function mode,
+        // not an SCK pin, and an SCK pin of another instance
+        assert!(!pin_af_valid(SPI1::SCK, 0, 5, 0b10, 6));
+        assert!(!pin_af_valid(SPI1::SCK, 0, 5, 0b00, 5));
+        assert!(!pin_af_valid(SPI1::SCK, 0, 6, 0b10, 5));
+        assert!(!pin_af_valid(SPI1::SCK, 1, 10, 0b10, 5));
+    }
+
+    #[test]
+    fn mbr_const_matches_runtime() {
+        assert_eq!(ConstMbr::<100_000_000, 10_000_000>::MBR, MBR::DIV8);
+        assert_eq!(
+            ConstMbr::<100_000_000, 10_000_000>::MBR,
+            mbr_for(100_000_000, 10_000_000).unwrap()
+        );
+        assert_eq!(ConstMbr::<48_000_000, 400_000>::MBR, MBR::DIV128);
+    }
+
+    #[test]
+    fn baud_kernel_clock_and_divider() {
+        assert!(matches!(
+            baud_for(None, 1_000_000, None),
+            Err(Error::KernelClockNotRunning)
+        ));
+
+        let (ker_ck, mbr) =
+            baud_for(Some(Hertz(100_000_000)), 1_000_000, None).unwrap();
+        assert_eq!(ker_ck, 100_000_000);
+        assert_eq!(mbr, MBR::DIV128);
+
+        // A fixed divider ignores the requested frequency
+        let (_, mbr) =
+            baud_for(Some(Hertz(100_000_000)), 0, Some(Divider::Div4))
+                .unwrap();
+        assert_eq!(mbr, MBR::DIV4);
+    }
+
+    #[test]
+    fn delay_rounds_up() {
+        let sck = 1_000_000;
+        assert_eq!(delay_cycles(0, sck), 0);
+        assert_eq!(delay_cycles(1_000, sck), 1);
+        assert_eq!(delay_cycles(1_500, sck), 2);
+        assert_eq!(delay_cycles(3_000, sck), 3);
+        assert_eq!(delay_cycles(3_250, sck), 4);
+    }
+
+    #[test]
+    fn delay_clamped() {
+        assert_eq!(delay_cycles(15_000, 1_000_000), 15);
+        assert_eq!(delay_cycles(u32::MAX, 1_000_000), 15);
+        assert_eq!(delay_cycles(u32::MAX, 0), 0);
+    }
+
+    #[test]
+    fn core_delay_rounds_up() {
+        let c_ck = 400_000_000;
+        assert_eq!(core_delay_cycles(0, c_ck), 0);
+        assert_eq!(core_delay_cycles(1, c_ck), 1);
+        assert_eq!(core_delay_cycles(10, c_ck), 4);
+        assert_eq!(core_delay_cycles(2_500, c_ck), 1_000);
+        assert_eq!(core_delay_cycles(u32::MAX, 480_000_000), 2_061_584_302);
+    }
+
+    #[test]
+    fn seconds_to_nearest_ns() {
+        assert_eq!(seconds_to_ns(-1.0), 0);
+        assert_eq!(seconds_to_ns(0.0), 0);
+        assert_eq!(seconds_to_ns(1e-6), 1_000);
+        assert_eq!(seconds_to_ns(2.5e-6), 2_500);
+        assert_eq!(seconds_to_ns(1e3), u32::MAX);
+        assert_eq!(delay_cycles(seconds_to_ns(1e-6), 1_000_000), 1);
+    }
+
+    #[test]
+    fn status_error_precedence() {
+        assert!(StatusFlags::empty().error().is_none());
+        assert!((StatusFlags::RXP | StatusFlags::TXP).error().is_none());
+        assert!(matches!(StatusFlags::CRCE.error(), Some(Error::Crc)));
+        assert!(matches!(
+            (StatusFlags::MODF | StatusFlags::CRCE).error(),
+            Some(Error::ModeFault)
+        ));
+        assert!(matches!(
+            (StatusFlags::OVR | StatusFlags::MODF | StatusFlags::CRCE).error(),
+            Some(Error::Overrun)
+        ));
+    }
+
+    #[test]
+    fn frame_mask_keeps_low_bits() {
+        assert_eq!(frame_mask(4), 0x0F);
+        assert_eq!(frame_mask(7), 0x7F);
+        assert_eq!(frame_mask(8), 0xFF);
+        assert_eq!(frame_mask(16), 0xFF);
+
+        // An inverted 4-bit frame does not leak into the high bits
+        assert_eq!(!0x05u8 & frame_mask(4), 0x0A);
+    }
+
+    #[test]
+    fn fifo_threshold_fits_fifo() {
+        let config = |frame_size, threshold| {
+            Config::new(MODE_0)
+                .frame_size(frame_size)
+                .fifo_threshold(threshold)
+        };
+        let err = Err(ConfigError::FifoThreshold);
+
+        assert_eq!(config(8, 0).validate::<SPI1>(), err);
+        assert_eq!(config(8, 16).validate::<SPI1>(), Ok(()));
+        assert_eq!(config(16, 8).validate::<SPI1>(), Ok(()));
+        assert_eq!(config(16, 9).validate::<SPI1>(), err);
+        assert_eq!(config(32, 4).validate::<SPI1>(), Ok(()));
+        assert_eq!(config(32, 5).validate::<SPI1>(), err);
+
+        // SPI4 - SPI6 have an 8 byte FIFO
+        assert_eq!(config(8, 8).validate::<SPI4>(), Ok(()));
+        assert_eq!(config(8, 16).validate::<SPI4>(), err);
+        assert_eq!(config(16, 4).validate::<SPI4>(), Ok(()));
+        assert_eq!(config(16, 5).validate::<SPI4>(), err);
+    }
+}
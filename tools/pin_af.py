@@ -0,0 +1,28 @@
+#!/usr/bin/env python3
+"""Generates the `pin_af!` table in src/gpio.rs from an alternate function
+matrix
+
+The matrix is a CSV file with one row per pin and one column per alternate
+function, transcribed from the "Alternate function" tables of the part's
+datasheet. A non-empty cell means that the alternate function is connected
+on that pin.
+
+    tools/pin_af.py tools/af_stm32h742_743_750_753.csv
+"""
+
+import csv
+import sys
+
+
+def main(path):
+    print("pin_af! {")
+    with open(path, newline="") as f:
+        for row in csv.DictReader(f):
+            pin = row["Pin"]
+            afs = [str(n) for n in range(16) if row["AF%d" % n]]
+            print("    gpio%s::%s: [%s]," % (pin[1].lower(), pin, ", ".join(afs)))
+    print("}")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])